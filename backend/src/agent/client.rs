@@ -0,0 +1,202 @@
+use domain::{LogLine, PipelineId, PipelineStatus, StepId};
+use http_body_util::{BodyExt, Full};
+use hyper::{
+    body::Bytes,
+    header::{AUTHORIZATION, CONTENT_TYPE},
+    Method, Request, StatusCode,
+};
+use hyper_rustls::{HttpsConnector, HttpsConnectorBuilder};
+use hyper_util::{
+    client::legacy::{connect::HttpConnector, Client},
+    rt::TokioExecutor,
+};
+use secrecy::{ExposeSecret, SecretString};
+use serde::{de::DeserializeOwned, Serialize};
+
+use super::error::ClientError;
+use crate::agents::protocol::{
+    ArtifactUpload, HeartbeatResponse, Job, JobResult, LogsReport, RegisterAgentRequest,
+    RegisterAgentResponse, StepStatusReport,
+};
+
+/// Talks to the agent endpoints of the backend
+#[derive(Clone)]
+pub struct BackendClient {
+    client: Client<HttpsConnector<HttpConnector>, Full<Bytes>>,
+    url: String,
+    token: SecretString,
+}
+
+impl BackendClient {
+    pub fn new(url: &str, token: SecretString) -> Result<Self, ClientError> {
+        let connector = HttpsConnectorBuilder::new()
+            .with_native_roots()
+            .map_err(ClientError::Certificates)?
+            .https_or_http()
+            .enable_http1()
+            .build();
+
+        Ok(Self {
+            client: Client::builder(TokioExecutor::new()).build(connector),
+            url: url.trim_end_matches('/').to_owned(),
+            token,
+        })
+    }
+
+    pub async fn register(&self, name: &str, labels: &[String]) -> Result<String, ClientError> {
+        let request = RegisterAgentRequest {
+            name: name.to_owned(),
+            labels: labels.to_vec(),
+        };
+        let response: RegisterAgentResponse = self.post("/agents", &request).await?.parse()?;
+
+        Ok(response.agent_id)
+    }
+
+    /// Returns `None` if there is no job for the agent right now
+    pub async fn claim(&self, agent_id: &str) -> Result<Option<Job>, ClientError> {
+        let response = self
+            .post(&format!("/agents/{agent_id}/jobs/claim"), &())
+            .await?;
+
+        match response.status {
+            StatusCode::NO_CONTENT => Ok(None),
+            _ => Ok(Some(response.parse()?)),
+        }
+    }
+
+    /// Returns whether the pipeline was cancelled
+    pub async fn heartbeat(
+        &self,
+        agent_id: &str,
+        pipeline_id: PipelineId,
+    ) -> Result<bool, ClientError> {
+        let response: HeartbeatResponse = self
+            .post(
+                &format!("/agents/{agent_id}/jobs/{pipeline_id}/heartbeat"),
+                &(),
+            )
+            .await?
+            .parse()?;
+
+        Ok(response.cancelled)
+    }
+
+    pub async fn report_step_status(
+        &self,
+        agent_id: &str,
+        pipeline_id: PipelineId,
+        step_id: StepId,
+        status: PipelineStatus,
+        exit_code: Option<i64>,
+        failure_reason: Option<String>,
+    ) -> Result<(), ClientError> {
+        self.post(
+            &format!("/agents/{agent_id}/jobs/{pipeline_id}/steps/{step_id}/status"),
+            &StepStatusReport {
+                status,
+                exit_code,
+                failure_reason,
+            },
+        )
+        .await?;
+
+        Ok(())
+    }
+
+    pub async fn report_step_logs(
+        &self,
+        agent_id: &str,
+        pipeline_id: PipelineId,
+        step_id: StepId,
+        lines: Vec<LogLine>,
+    ) -> Result<(), ClientError> {
+        self.post(
+            &format!("/agents/{agent_id}/jobs/{pipeline_id}/steps/{step_id}/logs"),
+            &LogsReport { lines },
+        )
+        .await?;
+
+        Ok(())
+    }
+
+    pub async fn upload_artifact(
+        &self,
+        agent_id: &str,
+        pipeline_id: PipelineId,
+        step_id: StepId,
+        path: &str,
+        archive: Vec<u8>,
+    ) -> Result<(), ClientError> {
+        let query = serde_urlencoded::to_string(ArtifactUpload {
+            path: path.to_owned(),
+        })?;
+
+        self.send(
+            &format!("/agents/{agent_id}/jobs/{pipeline_id}/steps/{step_id}/artifacts?{query}"),
+            "application/x-tar",
+            archive,
+        )
+        .await?;
+
+        Ok(())
+    }
+
+    pub async fn complete(
+        &self,
+        agent_id: &str,
+        pipeline_id: PipelineId,
+        result: &JobResult,
+    ) -> Result<(), ClientError> {
+        self.post(
+            &format!("/agents/{agent_id}/jobs/{pipeline_id}/complete"),
+            result,
+        )
+        .await?;
+
+        Ok(())
+    }
+
+    async fn post(&self, path: &str, body: &impl Serialize) -> Result<Response, ClientError> {
+        self.send(path, "application/json", serde_json::to_vec(body)?)
+            .await
+    }
+
+    async fn send(
+        &self,
+        path: &str,
+        content_type: &str,
+        body: Vec<u8>,
+    ) -> Result<Response, ClientError> {
+        let request = Request::builder()
+            .method(Method::POST)
+            .uri(format!("{}{path}", self.url))
+            .header(
+                AUTHORIZATION,
+                format!("Bearer {}", self.token.expose_secret()),
+            )
+            .header(CONTENT_TYPE, content_type)
+            .body(Full::new(Bytes::from(body)))?;
+
+        let response = self.client.request(request).await?;
+        let status = response.status();
+        let body = response.into_body().collect().await?.to_bytes();
+
+        match status {
+            StatusCode::NOT_FOUND => Err(ClientError::NotRegistered),
+            status if !status.is_success() => Err(ClientError::Status(status)),
+            status => Ok(Response { status, body }),
+        }
+    }
+}
+
+struct Response {
+    status: StatusCode,
+    body: Bytes,
+}
+
+impl Response {
+    fn parse<T: DeserializeOwned>(&self) -> Result<T, ClientError> {
+        Ok(serde_json::from_slice(&self.body)?)
+    }
+}
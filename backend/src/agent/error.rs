@@ -0,0 +1,22 @@
+use hyper::{http, StatusCode};
+use thiserror::Error;
+
+#[derive(Debug, Error)]
+pub enum ClientError {
+    #[error("Request to the backend failed: {0}")]
+    Request(#[from] hyper_util::client::legacy::Error),
+    #[error("Failed to build request: {0}")]
+    Http(#[from] http::Error),
+    #[error("Failed to read response: {0}")]
+    Body(#[from] hyper::Error),
+    #[error("Failed to parse response: {0}")]
+    Json(#[from] serde_json::Error),
+    #[error("Failed to encode query: {0}")]
+    Query(#[from] serde_urlencoded::ser::Error),
+    #[error("Failed to load root certificates: {0}")]
+    Certificates(std::io::Error),
+    #[error("Agent is not registered with the backend")]
+    NotRegistered,
+    #[error("Backend responded with {0}")]
+    Status(StatusCode),
+}
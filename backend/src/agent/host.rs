@@ -0,0 +1,177 @@
+use std::{collections::BTreeMap, path::PathBuf};
+
+use domain::{LogLine, Pipeline, PipelineId, PipelineStatus, Step, StepId};
+use secrecy::SecretString;
+use tokio::sync::mpsc;
+
+use super::client::BackendClient;
+use crate::{
+    registry::RegistryCredentials,
+    runner::{error::RunnerError, host::combine_registry_credentials, RunnerHost},
+};
+
+pub enum Report {
+    Status {
+        step_id: StepId,
+        status: PipelineStatus,
+        exit_code: Option<i64>,
+        failure_reason: Option<String>,
+    },
+    Logs {
+        step_id: StepId,
+        lines: Vec<LogLine>,
+    },
+    Artifact {
+        step_id: StepId,
+        path: String,
+        /// Temporary file, removed once uploaded
+        archive: PathBuf,
+    },
+}
+
+/// Runs a pipeline handed out by the backend, reporting its progress back.
+///
+/// The runner reports synchronously, so reports are queued and sent in order by
+/// [`forward_reports`].
+pub struct RemoteHost {
+    /// The secrets of every step by step name, as resolved by the backend
+    pub secrets: BTreeMap<String, BTreeMap<String, String>>,
+    /// Credentials of the agent itself
+    pub registries: RegistryCredentials,
+    /// Credentials of the repository, as resolved by the backend
+    pub registry_secret: Option<SecretString>,
+    pub reports: mpsc::UnboundedSender<Report>,
+}
+
+impl RemoteHost {
+    fn report(&self, report: Report) -> Result<(), RunnerError> {
+        self.reports
+            .send(report)
+            .map_err(|_| RunnerError::Generic("Reporting to the backend stopped".to_owned()))
+    }
+}
+
+impl RunnerHost for RemoteHost {
+    fn step_secrets(
+        &self,
+        _: &Pipeline,
+        step: &Step,
+    ) -> Result<BTreeMap<String, SecretString>, RunnerError> {
+        let secrets = self
+            .secrets
+            .get(&step.configuration.name)
+            .into_iter()
+            .flatten()
+            .map(|(name, value)| (name.clone(), SecretString::new(value.clone())))
+            .collect();
+
+        Ok(secrets)
+    }
+
+    fn registry_credentials(
+        &self,
+        pipeline: &Pipeline,
+    ) -> Result<RegistryCredentials, RunnerError> {
+        combine_registry_credentials(
+            &pipeline.trigger,
+            &self.registries,
+            self.registry_secret.as_ref(),
+        )
+    }
+
+    fn update_step_status(
+        &self,
+        _: &Pipeline,
+        step: &Step,
+        status: PipelineStatus,
+        exit_code: Option<i64>,
+        failure_reason: Option<&str>,
+    ) -> Result<(), RunnerError> {
+        self.report(Report::Status {
+            step_id: step.id,
+            status,
+            exit_code,
+            failure_reason: failure_reason.map(str::to_owned),
+        })
+    }
+
+    fn store_logs(&self, _: &Pipeline, step: &Step, lines: &[LogLine]) -> Result<(), RunnerError> {
+        self.report(Report::Logs {
+            step_id: step.id,
+            lines: lines.to_vec(),
+        })
+    }
+
+    fn store_artifact(
+        &self,
+        _: &Pipeline,
+        step: &Step,
+        path: &str,
+        archive: PathBuf,
+    ) -> Result<(), RunnerError> {
+        self.report(Report::Artifact {
+            step_id: step.id,
+            path: path.to_owned(),
+            archive,
+        })
+    }
+}
+
+/// Sends the reports to the backend until the host is dropped
+pub async fn forward_reports(
+    client: BackendClient,
+    agent_id: String,
+    pipeline_id: PipelineId,
+    mut reports: mpsc::UnboundedReceiver<Report>,
+) {
+    while let Some(report) = reports.recv().await {
+        let result = match report {
+            Report::Status {
+                step_id,
+                status,
+                exit_code,
+                failure_reason,
+            } => {
+                client
+                    .report_step_status(
+                        &agent_id,
+                        pipeline_id,
+                        step_id,
+                        status,
+                        exit_code,
+                        failure_reason,
+                    )
+                    .await
+            }
+            Report::Logs { step_id, lines } => {
+                client
+                    .report_step_logs(&agent_id, pipeline_id, step_id, lines)
+                    .await
+            }
+            Report::Artifact {
+                step_id,
+                path,
+                archive,
+            } => {
+                let uploaded = match tokio::fs::read(&archive).await {
+                    Ok(bytes) => {
+                        client
+                            .upload_artifact(&agent_id, pipeline_id, step_id, &path, bytes)
+                            .await
+                    }
+                    Err(err) => {
+                        println!("Failed to read artifact {path} of step {step_id}: {err}");
+                        Ok(())
+                    }
+                };
+                let _ = tokio::fs::remove_file(&archive).await;
+
+                uploaded
+            }
+        };
+
+        if let Err(err) = result {
+            println!("Failed to report progress of pipeline {pipeline_id}: {err}");
+        }
+    }
+}
@@ -0,0 +1,231 @@
+use std::time::Duration;
+
+use bollard::Docker;
+use domain::PipelineStatus;
+use secrecy::SecretString;
+use tokio::sync::mpsc;
+
+use self::client::BackendClient;
+use self::error::ClientError;
+use self::host::{forward_reports, RemoteHost};
+use crate::{
+    agents::protocol::{Job, JobResult},
+    cache::CacheStore,
+    cancellation::{Cancellations, RunGroup},
+    config::{CacheConfig, RegistryConfig, ResourcesConfig},
+    janitor::remove_orphans,
+    runner::PipelineRunner,
+};
+
+pub mod client;
+pub mod error;
+mod host;
+
+const POLL_INTERVAL: Duration = Duration::from_secs(5);
+const HEARTBEAT_INTERVAL: Duration = Duration::from_secs(10);
+
+pub struct AgentConfig {
+    /// Base URL of the backend, e.g. `https://ci.example.com`
+    pub url: String,
+    pub token: SecretString,
+    pub name: String,
+    /// Advertised labels, the architecture and operating system are always included
+    pub labels: Vec<String>,
+    pub cache: CacheConfig,
+    pub resources: ResourcesConfig,
+    pub registries: RegistryConfig,
+}
+
+impl AgentConfig {
+    pub fn from_environment() -> Result<AgentConfig, String> {
+        let url = std::env::var("CINNABAR_URL")
+            .map_err(|_| "Please provide the CINNABAR_URL environment variable")?;
+        let token = SecretString::new(
+            std::env::var("RUNNER_TOKEN")
+                .map_err(|_| "Please provide the RUNNER_TOKEN environment variable")?,
+        );
+        let name = std::env::var("RUNNER_NAME")
+            .ok()
+            .filter(|name| !name.is_empty())
+            .unwrap_or_else(|| "runner".to_owned());
+
+        let mut labels = vec![
+            std::env::consts::ARCH.to_owned(),
+            std::env::consts::OS.to_owned(),
+        ];
+
+        for label in std::env::var("RUNNER_LABELS")
+            .unwrap_or_default()
+            .split(',')
+        {
+            let label = label.trim();
+
+            if !label.is_empty() && !labels.iter().any(|existing| existing == label) {
+                labels.push(label.to_owned());
+            }
+        }
+
+        Ok(AgentConfig {
+            url,
+            token,
+            name,
+            labels,
+            cache: CacheConfig::from_environment()?,
+            resources: ResourcesConfig::from_environment()?,
+            registries: RegistryConfig::from_environment()?,
+        })
+    }
+}
+
+/// Polls the backend for jobs and runs them one at a time, registering again
+/// whenever the backend no longer knows the agent
+pub async fn run(config: AgentConfig) -> Result<(), String> {
+    let client =
+        BackendClient::new(&config.url, config.token.clone()).map_err(|err| err.to_string())?;
+    let docker = Docker::connect_with_socket_defaults()
+        .map_err(|err| format!("Failed to connect to Docker: {err}"))?;
+    let caches = CacheStore::new(config.cache.directory.clone(), config.cache.max_size);
+    // Agents sharing a Docker host need different names, as each removes what it owns
+    let owner = format!("agent:{}", config.name);
+
+    // Nothing runs yet, so everything left over was interrupted
+    if let Err(err) = remove_orphans(&docker, &owner, |_| Ok(true)).await {
+        println!("Failed to remove orphaned resources: {err}");
+    }
+
+    let mut agent_id = None;
+
+    loop {
+        let id = match &agent_id {
+            Some(id) => id,
+            None => match client.register(&config.name, &config.labels).await {
+                Ok(id) => {
+                    println!(
+                        "Registered as {} with labels {:?}",
+                        config.name, config.labels
+                    );
+                    agent_id.insert(id)
+                }
+                Err(err) => {
+                    println!("Failed to register with the backend: {err}");
+                    tokio::time::sleep(POLL_INTERVAL).await;
+                    continue;
+                }
+            },
+        };
+
+        match client.claim(id).await {
+            Ok(Some(job)) => {
+                let pipeline_id = job.pipeline.id;
+                println!("Running pipeline {pipeline_id}");
+
+                let result = run_job(&client, &docker, &config, &caches, &owner, id, job).await;
+
+                if let Err(err) = client.complete(id, pipeline_id, &result).await {
+                    println!("Failed to report result of pipeline {pipeline_id}: {err}");
+                }
+            }
+            Ok(None) => tokio::time::sleep(POLL_INTERVAL).await,
+            Err(ClientError::NotRegistered) => agent_id = None,
+            Err(err) => {
+                println!("Failed to claim a job: {err}");
+                tokio::time::sleep(POLL_INTERVAL).await;
+            }
+        }
+    }
+}
+
+async fn run_job(
+    client: &BackendClient,
+    docker: &Docker,
+    config: &AgentConfig,
+    caches: &CacheStore,
+    owner: &str,
+    agent_id: &str,
+    job: Job,
+) -> JobResult {
+    let Job {
+        mut pipeline,
+        access_token,
+        secrets,
+        default_branch,
+        registry_credentials,
+    } = job;
+    let pipeline_id = pipeline.id;
+
+    let cancellations = Cancellations::default();
+    let cancellation = cancellations.register(
+        pipeline_id,
+        RunGroup::new(&pipeline.trigger, &pipeline.configuration.name),
+    );
+
+    let heartbeat = tokio::spawn({
+        let client = client.clone();
+        let agent_id = agent_id.to_owned();
+
+        async move {
+            let mut interval = tokio::time::interval(HEARTBEAT_INTERVAL);
+
+            loop {
+                interval.tick().await;
+
+                match client.heartbeat(&agent_id, pipeline_id).await {
+                    Ok(true) => {
+                        cancellations.cancel(pipeline_id);
+                    }
+                    Ok(false) => {}
+                    Err(err) => println!("Failed to send heartbeat for {pipeline_id}: {err}"),
+                }
+            }
+        }
+    });
+
+    let (reports, receiver) = mpsc::unbounded_channel();
+    let forwarder = tokio::spawn(forward_reports(
+        client.clone(),
+        agent_id.to_owned(),
+        pipeline_id,
+        receiver,
+    ));
+
+    let host = RemoteHost {
+        secrets,
+        registries: config.registries.credentials.clone(),
+        registry_secret: registry_credentials.map(SecretString::new),
+        reports,
+    };
+    let access_token = SecretString::new(access_token);
+    let mut runner = PipelineRunner {
+        docker,
+        access_token: &access_token,
+        host: &host,
+        cancellation: &cancellation,
+        owner,
+        caches,
+        resources: &config.resources,
+        default_branch: default_branch.as_deref(),
+        pipeline: &mut pipeline,
+    };
+
+    let error = runner.run().await.err();
+    heartbeat.abort();
+
+    // All progress has to reach the backend before the job is completed
+    drop(host);
+    let _ = forwarder.await;
+
+    match error {
+        Some(err) => {
+            println!("Pipeline {pipeline_id} failed to run: {err}");
+
+            JobResult {
+                status: PipelineStatus::Failed,
+                error: Some(err.to_string()),
+            }
+        }
+        None => JobResult {
+            status: pipeline.status,
+            error: None,
+        },
+    }
+}
@@ -0,0 +1,13 @@
+use thiserror::Error;
+
+#[derive(Debug, Error)]
+pub enum AgentError {
+    #[error("Agent \"{0}\" is not registered")]
+    UnknownAgent(String),
+    #[error("Pipeline {0} is not assigned to the agent")]
+    UnknownJob(i32),
+    #[error("Agent running pipeline {0} stopped responding")]
+    Lost(i32),
+    #[error("No agent with labels {0:?} claimed the pipeline in time")]
+    Unclaimed(Vec<String>),
+}
@@ -0,0 +1,387 @@
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use aes_gcm::aead::{rand_core::RngCore, OsRng};
+use domain::{PipelineId, PipelineStatus};
+use tokio::{
+    sync::oneshot,
+    time::{sleep_until, Instant},
+};
+
+use self::error::AgentError;
+use self::protocol::{Job, JobResult};
+use crate::cancellation::Cancellation;
+
+pub mod error;
+pub mod protocol;
+
+/// Agents send a heartbeat well within this time while they run a pipeline
+const AGENT_TIMEOUT: Duration = Duration::from_secs(60);
+/// Jobs no agent claimed within this time fail, their access token expires after an hour
+const CLAIM_TIMEOUT: Duration = Duration::from_secs(30 * 60);
+
+struct Agent {
+    labels: Vec<String>,
+}
+
+struct RemoteJob {
+    pipeline_id: PipelineId,
+    runs_on: Vec<String>,
+    /// Taken by the agent that claims the job
+    job: Option<Job>,
+    agent_id: Option<String>,
+    last_seen: Instant,
+    result: Option<oneshot::Sender<JobResult>>,
+}
+
+#[derive(Default)]
+struct State {
+    agents: HashMap<String, Agent>,
+    /// Jobs in the order they were submitted, claimed ones stay until they finished
+    jobs: Vec<RemoteJob>,
+}
+
+/// Hands pipelines with `runs_on` labels to the registered agents advertising all of them.
+///
+/// Agents poll for jobs and report the progress of claimed ones back. Registrations are
+/// only kept in memory, agents register again when the backend does not know them.
+#[derive(Clone)]
+pub struct Agents {
+    state: Arc<Mutex<State>>,
+    claim_timeout: Duration,
+}
+
+impl Default for Agents {
+    fn default() -> Self {
+        Self::with_claim_timeout(CLAIM_TIMEOUT)
+    }
+}
+
+impl Agents {
+    pub fn with_claim_timeout(claim_timeout: Duration) -> Self {
+        Self {
+            state: Default::default(),
+            claim_timeout,
+        }
+    }
+
+    pub fn register(&self, name: &str, labels: Vec<String>) -> String {
+        let mut bytes = [0u8; 16];
+        OsRng.fill_bytes(&mut bytes);
+        let agent_id = hex::encode(bytes);
+
+        println!("Agent {name} registered with labels {labels:?}");
+
+        self.state
+            .lock()
+            .unwrap()
+            .agents
+            .insert(agent_id.clone(), Agent { labels });
+
+        agent_id
+    }
+
+    /// Assigns the oldest job the agent has all labels for
+    pub fn claim(&self, agent_id: &str) -> Result<Option<Job>, AgentError> {
+        let mut state = self.state.lock().unwrap();
+        let State { agents, jobs } = &mut *state;
+
+        let agent = agents
+            .get(agent_id)
+            .ok_or_else(|| AgentError::UnknownAgent(agent_id.to_owned()))?;
+
+        let job = jobs.iter_mut().find(|job| {
+            job.agent_id.is_none() && job.runs_on.iter().all(|label| agent.labels.contains(label))
+        });
+
+        Ok(job.and_then(|job| {
+            job.agent_id = Some(agent_id.to_owned());
+            job.last_seen = Instant::now();
+            job.job.take()
+        }))
+    }
+
+    /// Records that the agent is still working on the pipeline
+    pub fn heartbeat(&self, agent_id: &str, pipeline_id: PipelineId) -> Result<(), AgentError> {
+        let mut state = self.state.lock().unwrap();
+        let job = find_assigned(&mut state, agent_id, pipeline_id)?;
+        job.last_seen = Instant::now();
+
+        Ok(())
+    }
+
+    pub fn complete(
+        &self,
+        agent_id: &str,
+        pipeline_id: PipelineId,
+        result: JobResult,
+    ) -> Result<(), AgentError> {
+        let mut state = self.state.lock().unwrap();
+        let sender = find_assigned(&mut state, agent_id, pipeline_id)?
+            .result
+            .take();
+        state.jobs.retain(|job| job.pipeline_id != pipeline_id);
+
+        if let Some(sender) = sender {
+            // The pipeline is no longer waited for if it was given up on
+            let _ = sender.send(result);
+        }
+
+        Ok(())
+    }
+
+    /// Waits for an agent to claim and run the job.
+    ///
+    /// Jobs that were not claimed yet are dropped once the pipeline is cancelled or no agent
+    /// claimed them in time, agents learn about the cancellation of claimed ones through
+    /// their heartbeat.
+    pub async fn run(
+        &self,
+        job: Job,
+        runs_on: Vec<String>,
+        cancellation: &Cancellation,
+    ) -> Result<JobResult, AgentError> {
+        let pipeline_id = job.pipeline.id;
+        let (sender, mut receiver) = oneshot::channel();
+
+        self.state.lock().unwrap().jobs.push(RemoteJob {
+            pipeline_id,
+            runs_on: runs_on.clone(),
+            job: Some(job),
+            agent_id: None,
+            last_seen: Instant::now(),
+            result: Some(sender),
+        });
+
+        let mut interval = tokio::time::interval(AGENT_TIMEOUT / 4);
+        let mut cancelled = false;
+        let claim_deadline = Instant::now() + self.claim_timeout;
+        let mut claimed = false;
+
+        loop {
+            tokio::select! {
+                result = &mut receiver => {
+                    return result.map_err(|_| AgentError::Lost(pipeline_id.0));
+                }
+                _ = cancellation.cancelled(), if !cancelled => {
+                    cancelled = true;
+
+                    if self.remove_unclaimed(pipeline_id) {
+                        return Ok(JobResult {
+                            status: PipelineStatus::Cancelled,
+                            error: None,
+                        });
+                    }
+                }
+                _ = sleep_until(claim_deadline), if !claimed => {
+                    claimed = true;
+
+                    if self.remove_unclaimed(pipeline_id) {
+                        return Err(AgentError::Unclaimed(runs_on));
+                    }
+                }
+                _ = interval.tick() => {
+                    if self.remove_lost(pipeline_id) {
+                        return Err(AgentError::Lost(pipeline_id.0));
+                    }
+                }
+            }
+        }
+    }
+
+    fn remove_unclaimed(&self, pipeline_id: PipelineId) -> bool {
+        self.remove_if(pipeline_id, |job| job.agent_id.is_none())
+    }
+
+    fn remove_lost(&self, pipeline_id: PipelineId) -> bool {
+        self.remove_if(pipeline_id, |job| {
+            job.agent_id.is_some() && job.last_seen.elapsed() > AGENT_TIMEOUT
+        })
+    }
+
+    fn remove_if(&self, pipeline_id: PipelineId, condition: impl Fn(&RemoteJob) -> bool) -> bool {
+        let mut state = self.state.lock().unwrap();
+        let length = state.jobs.len();
+
+        state
+            .jobs
+            .retain(|job| job.pipeline_id != pipeline_id || !condition(job));
+
+        state.jobs.len() != length
+    }
+}
+
+fn find_assigned<'a>(
+    state: &'a mut State,
+    agent_id: &str,
+    pipeline_id: PipelineId,
+) -> Result<&'a mut RemoteJob, AgentError> {
+    if !state.agents.contains_key(agent_id) {
+        return Err(AgentError::UnknownAgent(agent_id.to_owned()));
+    }
+
+    state
+        .jobs
+        .iter_mut()
+        .find(|job| job.pipeline_id == pipeline_id && job.agent_id.as_deref() == Some(agent_id))
+        .ok_or(AgentError::UnknownJob(pipeline_id.0))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+
+    use chrono::DateTime;
+    use domain::{Branch, Pipeline, PipelineConfiguration, Trigger, TriggerEvent};
+
+    use super::*;
+    use crate::cancellation::{Cancellations, RunGroup};
+
+    fn job(id: i32) -> Job {
+        let trigger = Trigger {
+            repository_owner: "Owner".to_owned(),
+            repository_name: "Repo".to_owned(),
+            installation_id: 1,
+            event: TriggerEvent::Push {
+                branch: Branch {
+                    name: "main".to_owned(),
+                    commit: "abc123".to_owned(),
+                },
+                before: None,
+            },
+        };
+        let configuration = PipelineConfiguration {
+            name: "Build".to_owned(),
+            trigger: vec![],
+            clone: None,
+            timeout: None,
+            cancel_previous: false,
+            runs_on: Some(vec!["arm64".to_owned()]),
+            matrix: None,
+            services: None,
+            environment: None,
+            steps: vec![],
+        };
+
+        Job {
+            pipeline: Pipeline::new(
+                PipelineId(id),
+                trigger,
+                configuration,
+                DateTime::UNIX_EPOCH.naive_utc(),
+            ),
+            access_token: "token".to_owned(),
+            secrets: BTreeMap::new(),
+            default_branch: None,
+            registry_credentials: None,
+        }
+    }
+
+    fn labels(labels: &[&str]) -> Vec<String> {
+        labels.iter().map(|label| label.to_string()).collect()
+    }
+
+    fn cancellation(cancellations: &Cancellations, job: &Job) -> Cancellation {
+        cancellations.register(
+            job.pipeline.id,
+            RunGroup::new(&job.pipeline.trigger, &job.pipeline.configuration.name),
+        )
+    }
+
+    #[tokio::test]
+    async fn claim_should_only_hand_out_jobs_to_agents_with_all_labels() {
+        let agents = Agents::default();
+        let amd64 = agents.register("amd64", labels(&["x86_64", "linux"]));
+        let arm64 = agents.register("arm64", labels(&["aarch64", "linux", "arm64"]));
+        let cancellations = Cancellations::default();
+        let job = job(1);
+        let cancellation = cancellation(&cancellations, &job);
+
+        let run = {
+            let agents = agents.clone();
+            tokio::spawn(async move {
+                agents
+                    .run(job, labels(&["arm64", "linux"]), &cancellation)
+                    .await
+            })
+        };
+        tokio::task::yield_now().await;
+
+        assert!(agents.claim(&amd64).unwrap().is_none());
+        let claimed = agents.claim(&arm64).unwrap().unwrap();
+        assert_eq!(claimed.pipeline.id, PipelineId(1));
+        assert!(agents.claim(&arm64).unwrap().is_none());
+
+        let result = JobResult {
+            status: PipelineStatus::Passed,
+            error: None,
+        };
+        assert!(matches!(
+            agents.complete(&amd64, PipelineId(1), result),
+            Err(AgentError::UnknownJob(1))
+        ));
+
+        let result = JobResult {
+            status: PipelineStatus::Passed,
+            error: None,
+        };
+        agents.complete(&arm64, PipelineId(1), result).unwrap();
+
+        assert_eq!(
+            run.await.unwrap().unwrap(),
+            JobResult {
+                status: PipelineStatus::Passed,
+                error: None,
+            }
+        );
+    }
+
+    #[tokio::test]
+    async fn run_should_drop_unclaimed_job_when_cancelled() {
+        let agents = Agents::default();
+        let agent_id = agents.register("arm64", labels(&["arm64"]));
+        let cancellations = Cancellations::default();
+        let job = job(1);
+        let cancellation = cancellation(&cancellations, &job);
+
+        cancellations.cancel(PipelineId(1));
+        let result = agents
+            .run(job, labels(&["arm64"]), &cancellation)
+            .await
+            .unwrap();
+
+        assert_eq!(result.status, PipelineStatus::Cancelled);
+        assert!(agents.claim(&agent_id).unwrap().is_none());
+    }
+
+    #[tokio::test]
+    async fn run_should_fail_job_no_agent_claims() {
+        let agents = Agents::with_claim_timeout(Duration::from_millis(10));
+        let agent_id = agents.register("amd64", labels(&["x86_64"]));
+        let cancellations = Cancellations::default();
+        let job = job(1);
+        let cancellation = cancellation(&cancellations, &job);
+
+        let result = agents.run(job, labels(&["arm64"]), &cancellation).await;
+
+        assert!(matches!(result, Err(AgentError::Unclaimed(labels)) if labels == ["arm64"]));
+        assert!(agents.claim(&agent_id).unwrap().is_none());
+    }
+
+    #[test]
+    fn unknown_agents_should_be_rejected() {
+        let agents = Agents::default();
+
+        assert!(matches!(
+            agents.claim("unknown"),
+            Err(AgentError::UnknownAgent(_))
+        ));
+        assert!(matches!(
+            agents.heartbeat("unknown", PipelineId(1)),
+            Err(AgentError::UnknownAgent(_))
+        ));
+    }
+}
@@ -0,0 +1,59 @@
+use std::collections::BTreeMap;
+
+use domain::{LogLine, Pipeline, PipelineStatus};
+use serde::{Deserialize, Serialize};
+
+#[derive(Serialize, Deserialize)]
+pub struct RegisterAgentRequest {
+    pub name: String,
+    pub labels: Vec<String>,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct RegisterAgentResponse {
+    pub agent_id: String,
+}
+
+/// A pipeline handed to an agent along with everything it needs to run it
+#[derive(Serialize, Deserialize)]
+pub struct Job {
+    pub pipeline: Pipeline,
+    pub access_token: String,
+    /// The resolved secrets of every step, by step name
+    pub secrets: BTreeMap<String, BTreeMap<String, String>>,
+    /// Agents keep their own caches, falling back to the ones of this branch
+    pub default_branch: Option<String>,
+    /// The `DOCKER_AUTH_CONFIG` secret of the repository, agents merge it into their own
+    pub registry_credentials: Option<String>,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct StepStatusReport {
+    pub status: PipelineStatus,
+    pub exit_code: Option<i64>,
+    /// Why the step failed if the exit code does not tell, e.g. `OOMKilled`
+    pub failure_reason: Option<String>,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct LogsReport {
+    pub lines: Vec<LogLine>,
+}
+
+/// Query of an artifact upload, the body is the tar archive
+#[derive(Serialize, Deserialize)]
+pub struct ArtifactUpload {
+    pub path: String,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct HeartbeatResponse {
+    pub cancelled: bool,
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+pub struct JobResult {
+    pub status: PipelineStatus,
+    /// Why the pipeline could not be run, if it failed before its steps finished
+    pub error: Option<String>,
+}
@@ -0,0 +1,523 @@
+use axum::{
+    body::Bytes,
+    extract::{Path, Query, State},
+    http::StatusCode,
+    response::{IntoResponse, Response},
+    Json,
+};
+use chrono::Utc;
+use domain::{PipelineId, StepId};
+
+use super::{auth::AgentAuthenticated, state::RequestState, webhook::TriggerCallback};
+use crate::agents::{
+    error::AgentError,
+    protocol::{
+        ArtifactUpload, HeartbeatResponse, JobResult, LogsReport, RegisterAgentRequest,
+        RegisterAgentResponse, StepStatusReport,
+    },
+};
+
+type ApiError = (StatusCode, &'static str);
+
+/// Agents register again when they are not known, e.g. after the backend restarted
+fn agent_error(err: AgentError) -> ApiError {
+    match err {
+        AgentError::UnknownAgent(_) => (StatusCode::NOT_FOUND, "Agent not registered"),
+        AgentError::UnknownJob(_) => (StatusCode::GONE, "Job not assigned to agent"),
+        AgentError::Lost(_) | AgentError::Unclaimed(_) => (StatusCode::GONE, "Job was given up on"),
+    }
+}
+
+pub async fn register_agent<T: TriggerCallback>(
+    _: AgentAuthenticated,
+    State(RequestState { context, .. }): State<RequestState<T>>,
+    Json(request): Json<RegisterAgentRequest>,
+) -> Json<RegisterAgentResponse> {
+    let agent_id = context.agents.register(&request.name, request.labels);
+
+    Json(RegisterAgentResponse { agent_id })
+}
+
+/// Responds with the next job for the agent, or `204 No Content` if there is none
+pub async fn claim_job<T: TriggerCallback>(
+    _: AgentAuthenticated,
+    State(RequestState { context, .. }): State<RequestState<T>>,
+    Path(agent_id): Path<String>,
+) -> Result<Response, ApiError> {
+    let response = match context.agents.claim(&agent_id).map_err(agent_error)? {
+        Some(job) => Json(job).into_response(),
+        None => StatusCode::NO_CONTENT.into_response(),
+    };
+
+    Ok(response)
+}
+
+pub async fn job_heartbeat<T: TriggerCallback>(
+    _: AgentAuthenticated,
+    State(RequestState { context, .. }): State<RequestState<T>>,
+    Path((agent_id, pipeline_id)): Path<(String, i32)>,
+) -> Result<Json<HeartbeatResponse>, ApiError> {
+    let pipeline_id = PipelineId::new(pipeline_id);
+    context
+        .agents
+        .heartbeat(&agent_id, pipeline_id)
+        .map_err(agent_error)?;
+
+    Ok(Json(HeartbeatResponse {
+        cancelled: context.cancellations.is_cancelled(pipeline_id),
+    }))
+}
+
+pub async fn report_step_status<T: TriggerCallback>(
+    _: AgentAuthenticated,
+    State(RequestState { context, .. }): State<RequestState<T>>,
+    Path((agent_id, pipeline_id, step_id)): Path<(String, i32, i32)>,
+    Json(report): Json<StepStatusReport>,
+) -> Result<StatusCode, ApiError> {
+    let pipeline_id = PipelineId::new(pipeline_id);
+    let step_id = StepId::new(step_id);
+    context
+        .agents
+        .heartbeat(&agent_id, pipeline_id)
+        .map_err(agent_error)?;
+
+    context
+        .repositories
+        .pipelines
+        .lock()
+        .unwrap()
+        .update_step_status(
+            pipeline_id,
+            step_id,
+            report.status,
+            report.exit_code,
+            report.failure_reason.as_deref(),
+        )
+        .map_err(|_| {
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "Could not update step status",
+            )
+        })?;
+
+    // Agents report all logs of a step before its final status
+    if report.status.is_finished() {
+        context.log_streams.close(pipeline_id, step_id);
+    }
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+pub async fn report_step_logs<T: TriggerCallback>(
+    _: AgentAuthenticated,
+    State(RequestState { context, .. }): State<RequestState<T>>,
+    Path((agent_id, pipeline_id, step_id)): Path<(String, i32, i32)>,
+    Json(report): Json<LogsReport>,
+) -> Result<StatusCode, ApiError> {
+    let pipeline_id = PipelineId::new(pipeline_id);
+    let step_id = StepId::new(step_id);
+    context
+        .agents
+        .heartbeat(&agent_id, pipeline_id)
+        .map_err(agent_error)?;
+
+    context
+        .repositories
+        .logs
+        .lock()
+        .unwrap()
+        .append(pipeline_id, step_id, &report.lines)
+        .map_err(|_| (StatusCode::INTERNAL_SERVER_ERROR, "Could not store logs"))?;
+
+    context
+        .log_streams
+        .publish(pipeline_id, step_id, &report.lines);
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+pub async fn upload_artifact<T: TriggerCallback>(
+    _: AgentAuthenticated,
+    State(RequestState { context, .. }): State<RequestState<T>>,
+    Path((agent_id, pipeline_id, step_id)): Path<(String, i32, i32)>,
+    Query(upload): Query<ArtifactUpload>,
+    archive: Bytes,
+) -> Result<StatusCode, ApiError> {
+    let pipeline_id = PipelineId::new(pipeline_id);
+    context
+        .agents
+        .heartbeat(&agent_id, pipeline_id)
+        .map_err(agent_error)?;
+
+    let (digest, size) = context
+        .artifact_store
+        .store(&mut archive.as_ref())
+        .map_err(|_| {
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "Could not store artifact",
+            )
+        })?;
+
+    context
+        .repositories
+        .artifacts
+        .lock()
+        .unwrap()
+        .create(
+            pipeline_id,
+            StepId::new(step_id),
+            &upload.path,
+            &digest,
+            size as i64,
+            Utc::now().naive_utc(),
+        )
+        .map_err(|_| {
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "Could not store artifact",
+            )
+        })?;
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+pub async fn complete_job<T: TriggerCallback>(
+    _: AgentAuthenticated,
+    State(RequestState { context, .. }): State<RequestState<T>>,
+    Path((agent_id, pipeline_id)): Path<(String, i32)>,
+    Json(result): Json<JobResult>,
+) -> Result<StatusCode, ApiError> {
+    context
+        .agents
+        .complete(&agent_id, PipelineId::new(pipeline_id), result)
+        .map_err(agent_error)?;
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use chrono::DateTime;
+    use domain::{
+        repositories::Repositories, Branch, DockerImageReference, LogLine, LogStream,
+        PipelineConfiguration, PipelineStatus, StepConfiguration, Trigger, TriggerEvent,
+    };
+    use secrecy::SecretString;
+
+    use super::*;
+    use crate::{
+        agent::{client::BackendClient, error::ClientError},
+        agents::{protocol::Job, Agents},
+        api::Server,
+        artifacts::ArtifactStore,
+        cache::CacheStore,
+        cancellation::{Cancellations, RunGroup},
+        config::{
+            AgentsConfig, ApiConfig, AppConfig, ArtifactsConfig, CacheConfig, DatabaseConfig,
+            GitHubConfig, RegistryConfig, ResourcesConfig, RunnerConfig, SecretsConfig,
+        },
+        context::Context,
+        log_streams::LogStreams,
+        queue::JobQueue,
+        scheduler::ScheduleRefresh,
+    };
+    use domain::ResourcesConfiguration;
+
+    fn artifacts_directory(database_url: &str) -> std::path::PathBuf {
+        let name = database_url
+            .trim_start_matches("file:")
+            .split('?')
+            .next()
+            .unwrap();
+
+        std::env::temp_dir().join(format!("{name}-{}", std::process::id()))
+    }
+
+    fn context(database_url: &str) -> Context {
+        let config = AppConfig {
+            github: GitHubConfig {
+                app_id: 1,
+                private_key: SecretString::new("key".to_owned()),
+                webhook_secret: SecretString::new("secret".to_owned()),
+            },
+            database: DatabaseConfig {
+                url: database_url.to_owned(),
+            },
+            api: ApiConfig { token: None },
+            secrets: SecretsConfig { master_key: None },
+            runner: RunnerConfig {
+                clone_image: DockerImageReference {
+                    hostname: None,
+                    repository: "cinnabar/clone".to_owned(),
+                    tag: None,
+                },
+                max_concurrent_pipelines: 1,
+                max_concurrent_pipelines_per_repository: 1,
+            },
+            agents: AgentsConfig {
+                token: Some(SecretString::new("runner-token".to_owned())),
+            },
+            artifacts: ArtifactsConfig {
+                directory: artifacts_directory(database_url),
+                retention: Duration::from_secs(60 * 60),
+            },
+            cache: CacheConfig {
+                directory: artifacts_directory(database_url).join("cache"),
+                max_size: 1024,
+            },
+            registries: RegistryConfig {
+                credentials: Default::default(),
+            },
+            resources: ResourcesConfig {
+                defaults: ResourcesConfiguration::default(),
+                maximums: ResourcesConfiguration::default(),
+            },
+        };
+
+        Context {
+            repositories: Repositories::build(database_url).unwrap(),
+            artifact_store: ArtifactStore::new(config.artifacts.directory.clone()),
+            cache_store: CacheStore::new(config.cache.directory.clone(), config.cache.max_size),
+            config,
+            log_streams: LogStreams::default(),
+            cancellations: Cancellations::default(),
+            queue: JobQueue::default(),
+            agents: Agents::default(),
+            schedule_refresh: ScheduleRefresh::default(),
+            secret_cipher: None,
+        }
+    }
+
+    fn trigger() -> Trigger {
+        Trigger {
+            repository_owner: "Owner".to_owned(),
+            repository_name: "Repo".to_owned(),
+            installation_id: 1,
+            event: TriggerEvent::Push {
+                branch: Branch {
+                    name: "main".to_owned(),
+                    commit: "abc123".to_owned(),
+                },
+                before: None,
+            },
+        }
+    }
+
+    fn configuration() -> PipelineConfiguration {
+        PipelineConfiguration {
+            name: "Build".to_owned(),
+            trigger: vec![],
+            clone: None,
+            timeout: None,
+            cancel_previous: false,
+            runs_on: Some(vec!["linux".to_owned()]),
+            matrix: None,
+            services: None,
+            environment: None,
+            steps: vec![StepConfiguration {
+                name: "test".to_owned(),
+                image: DockerImageReference {
+                    hostname: None,
+                    repository: "alpine".to_owned(),
+                    tag: None,
+                },
+                commands: Some(vec!["true".to_owned()]),
+                cache: None,
+                depends_on: None,
+                environment: None,
+                secrets: None,
+                timeout: None,
+                artifacts: None,
+                resources: None,
+            }],
+        }
+    }
+
+    async fn serve(context: Context) -> String {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap();
+        let app = Server::new(context).app;
+
+        tokio::spawn(async move { axum::serve(listener, app).await });
+
+        format!("http://{address}")
+    }
+
+    async fn claim(client: &BackendClient, agent_id: &str) -> Job {
+        for _ in 0..100 {
+            if let Some(job) = client.claim(agent_id).await.unwrap() {
+                return job;
+            }
+
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+
+        panic!("No job was handed out");
+    }
+
+    #[tokio::test]
+    async fn agent_should_run_job_and_report_back() {
+        let context = context("file:agents_api?mode=memory&cache=shared");
+        let pipeline = context
+            .repositories
+            .pipelines
+            .lock()
+            .unwrap()
+            .create_new(&trigger(), configuration())
+            .unwrap();
+        let (pipeline_id, step_id) = (pipeline.id, pipeline.steps[0].id);
+        let url = serve(context.clone()).await;
+
+        let run = {
+            let context = context.clone();
+            let cancellation = context.cancellations.register(
+                pipeline_id,
+                RunGroup::new(&pipeline.trigger, &pipeline.configuration.name),
+            );
+            let job = Job {
+                pipeline,
+                access_token: "token".to_owned(),
+                secrets: Default::default(),
+                default_branch: None,
+                registry_credentials: None,
+            };
+
+            tokio::spawn(async move {
+                context
+                    .agents
+                    .run(job, vec!["linux".to_owned()], &cancellation)
+                    .await
+            })
+        };
+
+        let client =
+            BackendClient::new(&url, SecretString::new("runner-token".to_owned())).unwrap();
+        let agent_id = client
+            .register("runner", &["x86_64".to_owned(), "linux".to_owned()])
+            .await
+            .unwrap();
+
+        let job = claim(&client, &agent_id).await;
+        assert_eq!(job.pipeline.id, pipeline_id);
+        context.log_streams.open(pipeline_id, step_id);
+
+        client
+            .report_step_status(
+                &agent_id,
+                pipeline_id,
+                step_id,
+                PipelineStatus::Running,
+                None,
+                None,
+            )
+            .await
+            .unwrap();
+        let line = LogLine {
+            number: 1,
+            timestamp: DateTime::UNIX_EPOCH.naive_utc(),
+            stream: LogStream::Stdout,
+            content: "hello".to_owned(),
+        };
+        client
+            .report_step_logs(&agent_id, pipeline_id, step_id, vec![line.clone()])
+            .await
+            .unwrap();
+
+        client
+            .upload_artifact(
+                &agent_id,
+                pipeline_id,
+                step_id,
+                "target/app",
+                b"archive".to_vec(),
+            )
+            .await
+            .unwrap();
+
+        assert!(!client.heartbeat(&agent_id, pipeline_id).await.unwrap());
+        context.cancellations.cancel(pipeline_id);
+        assert!(client.heartbeat(&agent_id, pipeline_id).await.unwrap());
+
+        assert!(context
+            .log_streams
+            .subscribe(pipeline_id, step_id)
+            .is_some());
+        client
+            .report_step_status(
+                &agent_id,
+                pipeline_id,
+                step_id,
+                PipelineStatus::Cancelled,
+                None,
+                None,
+            )
+            .await
+            .unwrap();
+        assert!(context
+            .log_streams
+            .subscribe(pipeline_id, step_id)
+            .is_none());
+
+        let result = JobResult {
+            status: PipelineStatus::Cancelled,
+            error: None,
+        };
+        client
+            .complete(&agent_id, pipeline_id, &result)
+            .await
+            .unwrap();
+
+        assert_eq!(run.await.unwrap().unwrap(), result);
+
+        let pipeline = context
+            .repositories
+            .pipelines
+            .lock()
+            .unwrap()
+            .find(pipeline_id)
+            .unwrap()
+            .unwrap();
+        assert_eq!(pipeline.steps[0].status, PipelineStatus::Cancelled);
+
+        let logs = context
+            .repositories
+            .logs
+            .lock()
+            .unwrap()
+            .find_for_step(pipeline_id, step_id)
+            .unwrap();
+        assert_eq!(logs, vec![line]);
+
+        let artifacts = context
+            .repositories
+            .artifacts
+            .lock()
+            .unwrap()
+            .list_for_pipeline(pipeline_id)
+            .unwrap();
+        assert_eq!(artifacts.len(), 1);
+        assert_eq!(artifacts[0].path, "target/app");
+        assert_eq!(
+            std::fs::read(context.artifact_store.path(&artifacts[0].digest)).unwrap(),
+            b"archive"
+        );
+
+        std::fs::remove_dir_all(&context.config.artifacts.directory).unwrap();
+    }
+
+    #[tokio::test]
+    async fn agent_with_wrong_token_should_be_rejected() {
+        let context = context("file:agents_api_token?mode=memory&cache=shared");
+        let url = serve(context).await;
+
+        let client = BackendClient::new(&url, SecretString::new("wrong".to_owned())).unwrap();
+
+        assert!(matches!(
+            client.register("runner", &[]).await,
+            Err(ClientError::Status(StatusCode::UNAUTHORIZED))
+        ));
+    }
+}
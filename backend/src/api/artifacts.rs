@@ -0,0 +1,66 @@
+use axum::{
+    extract::{Path, State},
+    http::{
+        header::{CONTENT_DISPOSITION, CONTENT_TYPE},
+        StatusCode,
+    },
+    response::IntoResponse,
+    Json,
+};
+use domain::{Artifact, PipelineId};
+
+use super::{state::RequestState, webhook::TriggerCallback};
+
+type ApiError = (StatusCode, &'static str);
+
+pub async fn list_artifacts<T: TriggerCallback>(
+    State(RequestState { context, .. }): State<RequestState<T>>,
+    Path(pipeline_id): Path<i32>,
+) -> Result<Json<Vec<Artifact>>, ApiError> {
+    let artifacts = context
+        .repositories
+        .artifacts
+        .lock()
+        .unwrap()
+        .list_for_pipeline(PipelineId::new(pipeline_id))
+        .map_err(|_| {
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "Could not load artifacts",
+            )
+        })?;
+
+    Ok(Json(artifacts))
+}
+
+/// Responds with the tar archive of the artifact
+pub async fn download_artifact<T: TriggerCallback>(
+    State(RequestState { context, .. }): State<RequestState<T>>,
+    Path((pipeline_id, artifact_id)): Path<(i32, i32)>,
+) -> Result<impl IntoResponse, ApiError> {
+    let artifact = context
+        .repositories
+        .artifacts
+        .lock()
+        .unwrap()
+        .find(PipelineId::new(pipeline_id), artifact_id)
+        .map_err(|_| (StatusCode::INTERNAL_SERVER_ERROR, "Could not load artifact"))?
+        .ok_or((StatusCode::NOT_FOUND, "Artifact not found"))?;
+
+    let archive = tokio::fs::read(context.artifact_store.path(&artifact.digest))
+        .await
+        .map_err(|_| (StatusCode::GONE, "Artifact is not stored anymore"))?;
+
+    Ok((
+        [
+            (CONTENT_TYPE, "application/x-tar".to_owned()),
+            (
+                CONTENT_DISPOSITION,
+                format!(
+                    "attachment; filename=\"pipeline-{pipeline_id}-artifact-{artifact_id}.tar\""
+                ),
+            ),
+        ],
+        archive,
+    ))
+}
@@ -0,0 +1,104 @@
+use axum::{
+    async_trait,
+    extract::FromRequestParts,
+    http::{header::AUTHORIZATION, request::Parts, HeaderMap, StatusCode},
+};
+use secrecy::{ExposeSecret, SecretString};
+use subtle::ConstantTimeEq;
+
+use super::{state::RequestState, webhook::TriggerCallback};
+
+/// Extractor that rejects requests without the configured API token as bearer token
+pub struct Authenticated;
+
+#[async_trait]
+impl<T: TriggerCallback> FromRequestParts<RequestState<T>> for Authenticated {
+    type Rejection = (StatusCode, &'static str);
+
+    async fn from_request_parts(
+        parts: &mut Parts,
+        state: &RequestState<T>,
+    ) -> Result<Self, Self::Rejection> {
+        let token = state
+            .context
+            .config
+            .api
+            .token
+            .as_ref()
+            .ok_or((StatusCode::FORBIDDEN, "No API token configured"))?;
+
+        verify(&parts.headers, token)?;
+
+        Ok(Authenticated)
+    }
+}
+
+/// Extractor that rejects requests without the configured runner token, used by remote agents
+pub struct AgentAuthenticated;
+
+#[async_trait]
+impl<T: TriggerCallback> FromRequestParts<RequestState<T>> for AgentAuthenticated {
+    type Rejection = (StatusCode, &'static str);
+
+    async fn from_request_parts(
+        parts: &mut Parts,
+        state: &RequestState<T>,
+    ) -> Result<Self, Self::Rejection> {
+        let token = state
+            .context
+            .config
+            .agents
+            .token
+            .as_ref()
+            .ok_or((StatusCode::FORBIDDEN, "No runner token configured"))?;
+
+        verify(&parts.headers, token)?;
+
+        Ok(AgentAuthenticated)
+    }
+}
+
+fn verify(headers: &HeaderMap, token: &SecretString) -> Result<(), (StatusCode, &'static str)> {
+    let unauthorized = (StatusCode::UNAUTHORIZED, "Invalid API token");
+
+    let provided = headers
+        .get(AUTHORIZATION)
+        .and_then(|header| header.to_str().ok())
+        .and_then(|header| header.strip_prefix("Bearer "))
+        .ok_or(unauthorized)?;
+
+    if bool::from(provided.as_bytes().ct_eq(token.expose_secret().as_bytes())) {
+        Ok(())
+    } else {
+        Err(unauthorized)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::http::HeaderValue;
+
+    use super::*;
+
+    #[test]
+    fn verify_should_accept_matching_bearer_token() {
+        let mut headers = HeaderMap::new();
+        headers.insert(AUTHORIZATION, HeaderValue::from_static("Bearer token"));
+
+        assert!(verify(&headers, &SecretString::new("token".to_owned())).is_ok());
+    }
+
+    #[test]
+    fn verify_should_reject_wrong_or_missing_token() {
+        let token = SecretString::new("token".to_owned());
+        let mut headers = HeaderMap::new();
+
+        assert!(verify(&headers, &token).is_err());
+
+        headers.insert(AUTHORIZATION, HeaderValue::from_static("Bearer other"));
+        assert!(verify(&headers, &token).is_err());
+
+        headers.insert(AUTHORIZATION, HeaderValue::from_static("token"));
+        assert!(verify(&headers, &token).is_err());
+    }
+}
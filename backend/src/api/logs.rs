@@ -0,0 +1,110 @@
+use std::convert::Infallible;
+
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    response::sse::{Event, KeepAlive, Sse},
+};
+use domain::{repositories::Repositories, LogLine, PipelineId, StepId};
+use futures::{stream, Stream, StreamExt};
+use tokio::sync::broadcast::{error::RecvError, Receiver};
+
+use super::{state::RequestState, webhook::TriggerCallback};
+
+pub async fn stream_step_logs<T: TriggerCallback>(
+    State(RequestState { context, .. }): State<RequestState<T>>,
+    Path((pipeline_id, step_id)): Path<(i32, i32)>,
+) -> Result<Sse<impl Stream<Item = Result<Event, Infallible>>>, (StatusCode, &'static str)> {
+    let pipeline_id = PipelineId::new(pipeline_id);
+    let step_id = StepId::new(step_id);
+    let repositories = context.repositories;
+
+    let pipeline = repositories
+        .pipelines
+        .lock()
+        .unwrap()
+        .find(pipeline_id)
+        .map_err(|_| (StatusCode::INTERNAL_SERVER_ERROR, "Could not load pipeline"))?;
+
+    if !pipeline.is_some_and(|pipeline| pipeline.steps.iter().any(|step| step.id == step_id)) {
+        return Err((StatusCode::NOT_FOUND, "Step not found"));
+    }
+
+    // Subscribe before reading the stored lines, so no line is lost in between.
+    // Lines that show up in both are skipped by their number.
+    let receiver = context.log_streams.subscribe(pipeline_id, step_id);
+
+    let stored_lines = repositories
+        .logs
+        .lock()
+        .unwrap()
+        .find_for_step(pipeline_id, step_id)
+        .map_err(|_| (StatusCode::INTERNAL_SERVER_ERROR, "Could not load logs"))?;
+
+    let last_number = stored_lines.last().map_or(0, |line| line.number);
+
+    let live_lines = stream::unfold(
+        LiveLogs {
+            receiver,
+            last_number,
+            repositories,
+            pipeline_id,
+            step_id,
+        },
+        LiveLogs::next,
+    )
+    .flat_map(stream::iter);
+
+    let events = stream::iter(stored_lines)
+        .chain(live_lines)
+        .map(|line| Ok(to_event(&line)));
+
+    Ok(Sse::new(events).keep_alive(KeepAlive::default()))
+}
+
+struct LiveLogs {
+    receiver: Option<Receiver<LogLine>>,
+    last_number: i32,
+    repositories: Repositories,
+    pipeline_id: PipelineId,
+    step_id: StepId,
+}
+
+impl LiveLogs {
+    async fn next(mut self) -> Option<(Vec<LogLine>, Self)> {
+        loop {
+            let receiver = self.receiver.as_mut()?;
+
+            let lines = match receiver.recv().await {
+                Ok(line) if line.number <= self.last_number => continue,
+                Ok(line) => vec![line],
+                // The subscriber fell behind, so the missed lines are read from the database
+                Err(RecvError::Lagged(_)) => self
+                    .repositories
+                    .logs
+                    .lock()
+                    .unwrap()
+                    .find_for_step(self.pipeline_id, self.step_id)
+                    .ok()?
+                    .into_iter()
+                    .filter(|line| line.number > self.last_number)
+                    .collect(),
+                Err(RecvError::Closed) => return None,
+            };
+
+            if let Some(line) = lines.last() {
+                self.last_number = line.number;
+            }
+
+            return Some((lines, self));
+        }
+    }
+}
+
+fn to_event(line: &LogLine) -> Event {
+    Event::default()
+        .event("log")
+        .id(line.number.to_string())
+        .json_data(line)
+        .unwrap_or_else(|_| Event::default().comment("Could not serialize log line"))
+}
@@ -1,25 +1,106 @@
+mod agents;
+mod artifacts;
+mod auth;
+mod logs;
+mod pipelines;
+mod runs;
+mod secrets;
 mod state;
 mod webhook;
 
-use axum::{routing::post, Router};
+use axum::{
+    extract::DefaultBodyLimit,
+    routing::{get, post, put},
+    Router,
+};
 use std::io;
 use tokio::signal::{self, unix::SignalKind};
 
-use crate::{config::AppConfig, orchestrator::handle_trigger};
+use crate::{context::Context, orchestrator::handle_trigger};
 
+use agents::{
+    claim_job, complete_job, job_heartbeat, register_agent, report_step_logs, report_step_status,
+    upload_artifact,
+};
+use artifacts::{download_artifact, list_artifacts};
+use logs::stream_step_logs;
+use pipelines::{cancel_pipeline, get_pipeline, get_step_logs, list_pipelines};
+use runs::dispatch_pipeline;
+use secrets::{
+    delete_installation_secret, delete_repository_secret, list_installation_secrets,
+    list_repository_secrets, put_installation_secret, put_repository_secret,
+};
 use state::RequestState;
 use webhook::{handle_webhook, Callbacks};
 
+/// Uploads of artifacts by remote agents are larger than the default body limit allows
+const MAX_ARTIFACT_SIZE: usize = 1024 * 1024 * 1024;
+
 pub struct Server {
     app: Router,
 }
 
 impl Server {
-    pub fn new(config: AppConfig) -> Self {
+    pub fn new(context: Context) -> Self {
         let app = Router::new()
             .route("/webhook", post(handle_webhook))
+            .route("/pipelines", get(list_pipelines))
+            .route("/pipelines/:pipeline_id", get(get_pipeline))
+            .route("/pipelines/:pipeline_id/cancel", post(cancel_pipeline))
+            .route("/pipelines/:pipeline_id/artifacts", get(list_artifacts))
+            .route(
+                "/pipelines/:pipeline_id/artifacts/:artifact_id",
+                get(download_artifact),
+            )
+            .route(
+                "/pipelines/:pipeline_id/steps/:step_id/logs",
+                get(get_step_logs),
+            )
+            .route(
+                "/pipelines/:pipeline_id/steps/:step_id/logs/stream",
+                get(stream_step_logs),
+            )
+            .route(
+                "/repos/:owner/:repo/pipelines/:name/runs",
+                post(dispatch_pipeline),
+            )
+            .route("/repos/:owner/:repo/secrets", get(list_repository_secrets))
+            .route(
+                "/repos/:owner/:repo/secrets/:name",
+                put(put_repository_secret).delete(delete_repository_secret),
+            )
+            .route(
+                "/installations/:installation_id/secrets",
+                get(list_installation_secrets),
+            )
+            .route(
+                "/installations/:installation_id/secrets/:name",
+                put(put_installation_secret).delete(delete_installation_secret),
+            )
+            .route("/agents", post(register_agent))
+            .route("/agents/:agent_id/jobs/claim", post(claim_job))
+            .route(
+                "/agents/:agent_id/jobs/:pipeline_id/heartbeat",
+                post(job_heartbeat),
+            )
+            .route(
+                "/agents/:agent_id/jobs/:pipeline_id/steps/:step_id/status",
+                post(report_step_status),
+            )
+            .route(
+                "/agents/:agent_id/jobs/:pipeline_id/steps/:step_id/logs",
+                post(report_step_logs),
+            )
+            .route(
+                "/agents/:agent_id/jobs/:pipeline_id/steps/:step_id/artifacts",
+                post(upload_artifact).layer(DefaultBodyLimit::max(MAX_ARTIFACT_SIZE)),
+            )
+            .route(
+                "/agents/:agent_id/jobs/:pipeline_id/complete",
+                post(complete_job),
+            )
             .with_state(RequestState {
-                config,
+                context,
                 callbacks: Callbacks {
                     trigger: handle_trigger,
                 },
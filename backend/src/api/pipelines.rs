@@ -0,0 +1,176 @@
+use axum::{
+    extract::{Path, Query, State},
+    http::StatusCode,
+    Json,
+};
+use domain::{
+    repositories::{Page, Pagination, PipelineFilter},
+    LogLine, Pipeline, PipelineId, PipelineStatus, StepId,
+};
+use serde::Deserialize;
+
+use super::{auth::Authenticated, state::RequestState, webhook::TriggerCallback};
+
+type ApiError = (StatusCode, &'static str);
+
+#[derive(Deserialize)]
+pub struct ListPipelinesQuery {
+    /// In the format `<owner>/<name>`
+    repository: Option<String>,
+    branch: Option<String>,
+    status: Option<PipelineStatus>,
+    page: Option<u32>,
+    per_page: Option<u32>,
+}
+
+#[derive(Deserialize)]
+pub struct PaginationQuery {
+    page: Option<u32>,
+    per_page: Option<u32>,
+}
+
+pub async fn list_pipelines<T: TriggerCallback>(
+    State(RequestState { context, .. }): State<RequestState<T>>,
+    Query(query): Query<ListPipelinesQuery>,
+) -> Result<Json<Page<Pipeline>>, ApiError> {
+    let (repository_owner, repository_name) = match query.repository.as_deref() {
+        Some(repository) => {
+            let (owner, name) = parse_repository(repository)?;
+            (Some(owner), Some(name))
+        }
+        None => (None, None),
+    };
+
+    let filter = PipelineFilter {
+        repository_owner,
+        repository_name,
+        branch: query.branch,
+        status: query.status,
+    };
+
+    let page = context
+        .repositories
+        .pipelines
+        .lock()
+        .unwrap()
+        .list(&filter, Pagination::new(query.page, query.per_page))
+        .map_err(|_| {
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "Could not load pipelines",
+            )
+        })?;
+
+    Ok(Json(page))
+}
+
+pub async fn get_pipeline<T: TriggerCallback>(
+    State(RequestState { context, .. }): State<RequestState<T>>,
+    Path(pipeline_id): Path<i32>,
+) -> Result<Json<Pipeline>, ApiError> {
+    let pipeline = context
+        .repositories
+        .pipelines
+        .lock()
+        .unwrap()
+        .find(PipelineId::new(pipeline_id))
+        .map_err(|_| (StatusCode::INTERNAL_SERVER_ERROR, "Could not load pipeline"))?
+        .ok_or((StatusCode::NOT_FOUND, "Pipeline not found"))?;
+
+    Ok(Json(pipeline))
+}
+
+/// Stops a running pipeline, its check run is concluded as cancelled once the steps stopped.
+/// Queued pipelines are concluded by the queue without being started.
+pub async fn cancel_pipeline<T: TriggerCallback>(
+    _: Authenticated,
+    State(RequestState { context, .. }): State<RequestState<T>>,
+    Path(pipeline_id): Path<i32>,
+) -> Result<StatusCode, ApiError> {
+    let pipeline_id = PipelineId::new(pipeline_id);
+
+    if context.cancellations.cancel(pipeline_id) {
+        context.queue.notify();
+        return Ok(StatusCode::ACCEPTED);
+    }
+
+    let pipeline = context
+        .repositories
+        .pipelines
+        .lock()
+        .unwrap()
+        .find(pipeline_id)
+        .map_err(|_| (StatusCode::INTERNAL_SERVER_ERROR, "Could not load pipeline"))?;
+
+    match pipeline {
+        Some(_) => Err((StatusCode::CONFLICT, "Pipeline is not running")),
+        None => Err((StatusCode::NOT_FOUND, "Pipeline not found")),
+    }
+}
+
+pub async fn get_step_logs<T: TriggerCallback>(
+    State(RequestState { context, .. }): State<RequestState<T>>,
+    Path((pipeline_id, step_id)): Path<(i32, i32)>,
+    Query(query): Query<PaginationQuery>,
+) -> Result<Json<Page<LogLine>>, ApiError> {
+    let pipeline_id = PipelineId::new(pipeline_id);
+    let step_id = StepId::new(step_id);
+
+    let pipeline = context
+        .repositories
+        .pipelines
+        .lock()
+        .unwrap()
+        .find(pipeline_id)
+        .map_err(|_| (StatusCode::INTERNAL_SERVER_ERROR, "Could not load pipeline"))?;
+
+    if !pipeline.is_some_and(|pipeline| pipeline.steps.iter().any(|step| step.id == step_id)) {
+        return Err((StatusCode::NOT_FOUND, "Step not found"));
+    }
+
+    let logs = context
+        .repositories
+        .logs
+        .lock()
+        .unwrap()
+        .find_page_for_step(
+            pipeline_id,
+            step_id,
+            Pagination::new(query.page, query.per_page),
+        )
+        .map_err(|_| (StatusCode::INTERNAL_SERVER_ERROR, "Could not load logs"))?;
+
+    Ok(Json(logs))
+}
+
+fn parse_repository(repository: &str) -> Result<(String, String), ApiError> {
+    match repository.split_once('/') {
+        Some((owner, name)) if !owner.is_empty() && !name.is_empty() && !name.contains('/') => {
+            Ok((owner.to_owned(), name.to_owned()))
+        }
+        _ => Err((
+            StatusCode::BAD_REQUEST,
+            "Repository needs to be in the format <owner>/<name>",
+        )),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_repository_should_split_owner_and_name() {
+        assert_eq!(
+            parse_repository("Owner/Repo"),
+            Ok(("Owner".to_owned(), "Repo".to_owned()))
+        );
+    }
+
+    #[test]
+    fn parse_repository_should_reject_malformed_repository() {
+        assert!(parse_repository("Repo").is_err());
+        assert!(parse_repository("/Repo").is_err());
+        assert!(parse_repository("Owner/Repo/Other").is_err());
+    }
+}
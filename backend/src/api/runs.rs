@@ -0,0 +1,113 @@
+use std::collections::BTreeMap;
+
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    Json,
+};
+use domain::{resolve_inputs, Branch, Trigger, TriggerEvent};
+use serde::Deserialize;
+use serde_json::Value;
+use source_control::{github::GitHub, SourceControl, SourceControlInstallation};
+
+use super::{auth::Authenticated, state::RequestState, webhook::TriggerCallback};
+use crate::orchestrator::find_pipeline_configuration;
+
+type ApiError = (StatusCode, String);
+
+#[derive(Deserialize)]
+pub struct DispatchPipelineRequest {
+    /// A branch, tag or commit sha
+    r#ref: String,
+    #[serde(default)]
+    inputs: BTreeMap<String, Value>,
+}
+
+pub async fn dispatch_pipeline<T: TriggerCallback>(
+    _: Authenticated,
+    State(RequestState { context, callbacks }): State<RequestState<T>>,
+    Path((owner, repo, name)): Path<(String, String, String)>,
+    Json(request): Json<DispatchPipelineRequest>,
+) -> Result<StatusCode, ApiError> {
+    let error = |status: StatusCode, message: &str| (status, message.to_owned());
+
+    let github = GitHub::build(
+        context.config.github.app_id,
+        &context.config.github.private_key,
+    )
+    .map_err(|_| {
+        error(
+            StatusCode::INTERNAL_SERVER_ERROR,
+            "Could not connect to GitHub",
+        )
+    })?;
+
+    let installation_id = github
+        .find_installation_id(&owner, &repo)
+        .await
+        .map_err(|_| error(StatusCode::NOT_FOUND, "Repository not found"))?;
+
+    let installation = github
+        .get_installation(&owner, &repo, installation_id)
+        .await
+        .map_err(|_| {
+            error(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "Could not connect to GitHub",
+            )
+        })?;
+
+    let commit = installation
+        .resolve_ref(&request.r#ref)
+        .await
+        .map_err(|_| error(StatusCode::NOT_FOUND, "Ref not found"))?;
+
+    let configuration = find_pipeline_configuration(&name, &commit, &installation)
+        .await
+        .map_err(|_| {
+            error(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "Could not read pipelines",
+            )
+        })?
+        .ok_or_else(|| error(StatusCode::NOT_FOUND, "Pipeline not found"))?;
+
+    let declared_inputs = configuration
+        .manual_inputs()
+        .ok_or_else(|| error(StatusCode::BAD_REQUEST, "Pipeline has no manual trigger"))?;
+
+    let inputs = resolve_inputs(&declared_inputs, &request.inputs)
+        .map_err(|err| (StatusCode::UNPROCESSABLE_ENTITY, err.to_string()))?;
+
+    let trigger = Trigger {
+        repository_owner: owner,
+        repository_name: repo,
+        installation_id,
+        event: TriggerEvent::Manual {
+            branch: Branch {
+                name: request.r#ref,
+                commit,
+            },
+            pipeline: name,
+            inputs,
+        },
+    };
+
+    callbacks
+        .trigger
+        .call(trigger, context)
+        .await
+        .map_err(|err| {
+            if err.is_invalid_configuration() {
+                error(StatusCode::BAD_REQUEST, "Could not parse pipeline config")
+            } else {
+                println!("Failed to start manual pipeline: {err}");
+                error(
+                    StatusCode::INTERNAL_SERVER_ERROR,
+                    "Could not start pipeline",
+                )
+            }
+        })?;
+
+    Ok(StatusCode::ACCEPTED)
+}
@@ -0,0 +1,153 @@
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    Json,
+};
+use chrono::Utc;
+use domain::{is_valid_secret_name, Secret, SecretScope};
+use secrecy::SecretString;
+use serde::Deserialize;
+
+use super::{auth::Authenticated, state::RequestState, webhook::TriggerCallback};
+use crate::context::Context;
+
+type ApiError = (StatusCode, &'static str);
+
+#[derive(Deserialize)]
+pub struct PutSecretRequest {
+    value: String,
+}
+
+pub async fn list_repository_secrets<T: TriggerCallback>(
+    _: Authenticated,
+    State(RequestState { context, .. }): State<RequestState<T>>,
+    Path((owner, name)): Path<(String, String)>,
+) -> Result<Json<Vec<Secret>>, ApiError> {
+    list_secrets(&context, SecretScope::Repository { owner, name })
+}
+
+pub async fn put_repository_secret<T: TriggerCallback>(
+    _: Authenticated,
+    State(RequestState { context, .. }): State<RequestState<T>>,
+    Path((owner, name, secret)): Path<(String, String, String)>,
+    Json(request): Json<PutSecretRequest>,
+) -> Result<StatusCode, ApiError> {
+    put_secret(
+        &context,
+        SecretScope::Repository { owner, name },
+        &secret,
+        request,
+    )
+}
+
+pub async fn delete_repository_secret<T: TriggerCallback>(
+    _: Authenticated,
+    State(RequestState { context, .. }): State<RequestState<T>>,
+    Path((owner, name, secret)): Path<(String, String, String)>,
+) -> Result<StatusCode, ApiError> {
+    delete_secret(&context, SecretScope::Repository { owner, name }, &secret)
+}
+
+pub async fn list_installation_secrets<T: TriggerCallback>(
+    _: Authenticated,
+    State(RequestState { context, .. }): State<RequestState<T>>,
+    Path(installation_id): Path<u64>,
+) -> Result<Json<Vec<Secret>>, ApiError> {
+    list_secrets(&context, SecretScope::Installation(installation_id))
+}
+
+pub async fn put_installation_secret<T: TriggerCallback>(
+    _: Authenticated,
+    State(RequestState { context, .. }): State<RequestState<T>>,
+    Path((installation_id, secret)): Path<(u64, String)>,
+    Json(request): Json<PutSecretRequest>,
+) -> Result<StatusCode, ApiError> {
+    put_secret(
+        &context,
+        SecretScope::Installation(installation_id),
+        &secret,
+        request,
+    )
+}
+
+pub async fn delete_installation_secret<T: TriggerCallback>(
+    _: Authenticated,
+    State(RequestState { context, .. }): State<RequestState<T>>,
+    Path((installation_id, secret)): Path<(u64, String)>,
+) -> Result<StatusCode, ApiError> {
+    delete_secret(
+        &context,
+        SecretScope::Installation(installation_id),
+        &secret,
+    )
+}
+
+fn list_secrets(context: &Context, scope: SecretScope) -> Result<Json<Vec<Secret>>, ApiError> {
+    let secrets = context
+        .repositories
+        .secrets
+        .lock()
+        .unwrap()
+        .list(&scope)
+        .map_err(|_| (StatusCode::INTERNAL_SERVER_ERROR, "Could not load secrets"))?;
+
+    Ok(Json(secrets))
+}
+
+fn put_secret(
+    context: &Context,
+    scope: SecretScope,
+    name: &str,
+    request: PutSecretRequest,
+) -> Result<StatusCode, ApiError> {
+    if !is_valid_secret_name(name) {
+        return Err((
+            StatusCode::BAD_REQUEST,
+            "Secret names may only contain letters, digits, _ and -",
+        ));
+    }
+
+    let cipher = context
+        .secret_cipher
+        .as_ref()
+        .ok_or((StatusCode::FORBIDDEN, "No secrets master key configured"))?;
+
+    let encrypted = cipher
+        .encrypt(&scope, name, &SecretString::new(request.value))
+        .map_err(|_| {
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "Could not encrypt secret",
+            )
+        })?;
+
+    context
+        .repositories
+        .secrets
+        .lock()
+        .unwrap()
+        .upsert(&scope, name, &encrypted, Utc::now().naive_utc())
+        .map_err(|_| (StatusCode::INTERNAL_SERVER_ERROR, "Could not store secret"))?;
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+fn delete_secret(
+    context: &Context,
+    scope: SecretScope,
+    name: &str,
+) -> Result<StatusCode, ApiError> {
+    let deleted = context
+        .repositories
+        .secrets
+        .lock()
+        .unwrap()
+        .delete(&scope, name)
+        .map_err(|_| (StatusCode::INTERNAL_SERVER_ERROR, "Could not delete secret"))?;
+
+    if deleted {
+        Ok(StatusCode::NO_CONTENT)
+    } else {
+        Err((StatusCode::NOT_FOUND, "Secret not found"))
+    }
+}
@@ -1,9 +1,9 @@
-use crate::config::AppConfig;
+use crate::context::Context;
 
 use super::webhook::{Callbacks, TriggerCallback};
 
 #[derive(Clone)]
 pub struct RequestState<T: TriggerCallback> {
-    pub config: AppConfig,
+    pub context: Context,
     pub callbacks: Callbacks<T>,
 }
@@ -9,26 +9,26 @@ use axum::{
 };
 use serde::{de::Visitor, Deserialize};
 
-use crate::{api::RequestState, config::AppConfig};
+use crate::{api::RequestState, context::Context, orchestrator::error::TriggerError};
 
 use checksum::VerifiedBody;
 use domain::{Branch, Trigger, TriggerEvent};
 
 pub trait TriggerCallback: Send + Sync {
-    type Output: Future<Output = Result<(), ()>> + Send;
+    type Output: Future<Output = Result<(), TriggerError>> + Send;
 
-    fn call(self, trigger: Trigger, config: AppConfig) -> Self::Output;
+    fn call(self, trigger: Trigger, context: Context) -> Self::Output;
 }
 
 impl<T, Output> TriggerCallback for T
 where
-    T: Send + Sync + FnOnce(Trigger, AppConfig) -> Output,
-    Output: Future<Output = Result<(), ()>> + Send,
+    T: Send + Sync + FnOnce(Trigger, Context) -> Output,
+    Output: Future<Output = Result<(), TriggerError>> + Send,
 {
     type Output = Output;
 
-    fn call(self, trigger: Trigger, config: AppConfig) -> Self::Output {
-        self(trigger, config)
+    fn call(self, trigger: Trigger, context: Context) -> Self::Output {
+        self(trigger, context)
     }
 }
 
@@ -38,11 +38,11 @@ pub struct Callbacks<T: TriggerCallback> {
 }
 
 pub async fn handle_webhook<T: TriggerCallback>(
-    State(RequestState { config, callbacks }): State<RequestState<T>>,
+    State(RequestState { context, callbacks }): State<RequestState<T>>,
     headers: HeaderMap,
     body: String,
 ) -> impl IntoResponse {
-    let body = match checksum::verify(&headers, body, &config.github.webhook_secret) {
+    let body = match checksum::verify(&headers, body, &context.config.github.webhook_secret) {
         Ok(body) => body,
         Err(message) => return (StatusCode::BAD_REQUEST, message),
     };
@@ -51,10 +51,19 @@ pub async fn handle_webhook<T: TriggerCallback>(
 
     match trigger {
         Ok(Some(trigger)) => {
-            let result = callbacks.trigger.call(trigger, config).await;
+            let result = callbacks.trigger.call(trigger, context).await;
             match result {
                 Ok(()) => (StatusCode::CREATED, "OK"),
-                Err(()) => (StatusCode::BAD_REQUEST, "Could not parse pipeline config"),
+                Err(err) if err.is_invalid_configuration() => {
+                    (StatusCode::BAD_REQUEST, "Could not parse pipeline config")
+                }
+                Err(err) => {
+                    println!("Failed to start pipelines: {err}");
+                    (
+                        StatusCode::INTERNAL_SERVER_ERROR,
+                        "Could not start pipelines",
+                    )
+                }
             }
         }
         Ok(None) => (StatusCode::NO_CONTENT, "OK"),
@@ -107,6 +116,7 @@ impl WebhookEvent {
 #[derive(Deserialize)]
 struct PushEventData {
     r#ref: String,
+    before: Option<String>,
     head_commit: Option<HeadCommit>,
     repository: Repository,
     installation: Installation,
@@ -117,26 +127,36 @@ impl PushEventData {
         let repository_owner = self.repository.owner.login;
         let repository_name = self.repository.name;
         let installation_id = self.installation.id;
-        self.r#ref
-            .strip_prefix("refs/heads/")
-            .zip(self.head_commit)
-            .map(move |(branch, commit)| {
-                let branch = branch.to_owned();
-                let commit = commit.id;
-                let event = TriggerEvent::Push {
-                    branch: Branch {
-                        name: branch,
-                        commit,
-                    },
-                };
+        // GitHub sends an all-zero commit if the branch was just created
+        let before = self
+            .before
+            .filter(|before| before.chars().any(|char| char != '0'));
+        let commit = self.head_commit?.id;
+        let event = if let Some(branch) = self.r#ref.strip_prefix("refs/heads/") {
+            TriggerEvent::Push {
+                branch: Branch {
+                    name: branch.to_owned(),
+                    commit,
+                },
+                before,
+            }
+        } else if let Some(tag) = self.r#ref.strip_prefix("refs/tags/") {
+            TriggerEvent::Tag {
+                tag: Branch {
+                    name: tag.to_owned(),
+                    commit,
+                },
+            }
+        } else {
+            return None;
+        };
 
-                Trigger {
-                    repository_owner,
-                    repository_name,
-                    installation_id,
-                    event,
-                }
-            })
+        Some(Trigger {
+            repository_owner,
+            repository_name,
+            installation_id,
+            event,
+        })
     }
 }
 
@@ -175,7 +195,14 @@ struct PullRequestEventData {
 
 impl PullRequestEventData {
     fn extract_trigger(self) -> Option<Trigger> {
+        // The head repository is missing if the fork was deleted
+        let from_fork = match (&self.pull_request.head.repo, &self.pull_request.base.repo) {
+            (Some(head), Some(base)) => head.full_name != base.full_name,
+            _ => true,
+        };
+
         let event = TriggerEvent::PullRequest {
+            number: self.pull_request.number,
             source: Branch {
                 name: self.pull_request.head.r#ref.get_name(),
                 commit: self.pull_request.head.sha,
@@ -184,6 +211,7 @@ impl PullRequestEventData {
                 name: self.pull_request.base.r#ref.get_name(),
                 commit: self.pull_request.base.sha,
             },
+            from_fork,
         };
 
         Some(Trigger {
@@ -218,6 +246,7 @@ struct Installation {
 
 #[derive(Deserialize)]
 struct PullRequest {
+    number: u64,
     head: PullRequestRef,
     base: PullRequestRef,
 }
@@ -226,6 +255,12 @@ struct PullRequest {
 struct PullRequestRef {
     r#ref: Ref,
     sha: String,
+    repo: Option<PullRequestRepository>,
+}
+
+#[derive(Deserialize)]
+struct PullRequestRepository {
+    full_name: String,
 }
 
 enum Ref {
@@ -330,6 +365,7 @@ mod tests {
         let body = VerifiedBody::from_static(
             r#"{
                     "ref": "refs/heads/branch",
+                    "before": "0000000000000000000000000000000000000000",
                     "head_commit": {
                         "id": "123"
                     },
@@ -354,6 +390,78 @@ mod tests {
                     branch: Branch {
                         name: "branch".to_owned(),
                         commit: "123".to_owned()
+                    },
+                    before: None
+                },
+                installation_id: 789,
+                repository_name: "Repo".to_owned(),
+                repository_owner: "Owner".to_owned()
+            }))
+        );
+    }
+
+    #[test]
+    fn parse_trigger_should_keep_previous_commit_of_push_event() {
+        let mut headers = HeaderMap::new();
+        headers.insert("X-GitHub-Event", HeaderValue::from_static("push"));
+
+        let body = VerifiedBody::from_static(
+            r#"{
+                    "ref": "refs/heads/branch",
+                    "before": "456",
+                    "head_commit": {
+                        "id": "123"
+                    },
+                    "repository": {
+                        "name": "Repo",
+                        "owner": {
+                            "login": "Owner"
+                        }
+                    },
+                    "installation": {
+                        "id": 789
+                    }
+                }"#,
+        );
+
+        let trigger = parse_trigger(headers, body).unwrap().unwrap();
+
+        assert_eq!(trigger.event.changed_range(), Some(("456", "123")));
+    }
+
+    #[test]
+    fn parse_trigger_should_parse_tag_push_event() {
+        let mut headers = HeaderMap::new();
+        headers.insert("X-GitHub-Event", HeaderValue::from_static("push"));
+
+        let body = VerifiedBody::from_static(
+            r#"{
+                    "ref": "refs/tags/v1.0.0",
+                    "before": "0000000000000000000000000000000000000000",
+                    "head_commit": {
+                        "id": "123"
+                    },
+                    "repository": {
+                        "name": "Repo",
+                        "owner": {
+                            "login": "Owner"
+                        }
+                    },
+                    "installation": {
+                        "id": 789
+                    }
+                }"#,
+        );
+
+        let result = parse_trigger(headers, body);
+
+        assert_eq!(
+            result,
+            Ok(Some(Trigger {
+                event: TriggerEvent::Tag {
+                    tag: Branch {
+                        name: "v1.0.0".to_owned(),
+                        commit: "123".to_owned()
                     }
                 },
                 installation_id: 789,
@@ -363,6 +471,33 @@ mod tests {
         );
     }
 
+    #[test]
+    fn parse_trigger_should_ignore_deleted_tag() {
+        let mut headers = HeaderMap::new();
+        headers.insert("X-GitHub-Event", HeaderValue::from_static("push"));
+
+        let body = VerifiedBody::from_static(
+            r#"{
+                    "ref": "refs/tags/v1.0.0",
+                    "before": "123",
+                    "head_commit": null,
+                    "repository": {
+                        "name": "Repo",
+                        "owner": {
+                            "login": "Owner"
+                        }
+                    },
+                    "installation": {
+                        "id": 789
+                    }
+                }"#,
+        );
+
+        let result = parse_trigger(headers, body);
+
+        assert_eq!(result, Ok(None));
+    }
+
     #[test]
     fn parse_trigger_should_parse_pull_request_opened_event() {
         let mut headers = HeaderMap::new();
@@ -372,13 +507,20 @@ mod tests {
             r#"{
                     "action": "opened",
                     "pull_request": {
+                        "number": 1,
                         "head": {
                             "sha": "123",
-                            "ref": "refs/heads/head-branch"
+                            "ref": "refs/heads/head-branch",
+                            "repo": {
+                                "full_name": "Owner/Repo"
+                            }
                         },
                         "base": {
                             "sha": "456",
-                            "ref": "refs/heads/base-branch"
+                            "ref": "refs/heads/base-branch",
+                            "repo": {
+                                "full_name": "Owner/Repo"
+                            }
                         }
                     },
                     "repository": {
@@ -399,6 +541,7 @@ mod tests {
             result,
             Ok(Some(Trigger {
                 event: TriggerEvent::PullRequest {
+                    number: 1,
                     source: Branch {
                         name: "head-branch".to_owned(),
                         commit: "123".to_owned()
@@ -406,7 +549,8 @@ mod tests {
                     target: Branch {
                         name: "base-branch".to_owned(),
                         commit: "456".to_owned()
-                    }
+                    },
+                    from_fork: false
                 },
                 installation_id: 789,
                 repository_name: "Repo".to_owned(),
@@ -424,13 +568,20 @@ mod tests {
             r#"{
                     "action": "reopened",
                     "pull_request": {
+                        "number": 1,
                         "head": {
                             "sha": "123",
-                            "ref": "refs/heads/head-branch"
+                            "ref": "refs/heads/head-branch",
+                            "repo": {
+                                "full_name": "Owner/Repo"
+                            }
                         },
                         "base": {
                             "sha": "456",
-                            "ref": "refs/heads/base-branch"
+                            "ref": "refs/heads/base-branch",
+                            "repo": {
+                                "full_name": "Owner/Repo"
+                            }
                         }
                     },
                     "repository": {
@@ -451,6 +602,7 @@ mod tests {
             result,
             Ok(Some(Trigger {
                 event: TriggerEvent::PullRequest {
+                    number: 1,
                     source: Branch {
                         name: "head-branch".to_owned(),
                         commit: "123".to_owned()
@@ -458,7 +610,8 @@ mod tests {
                     target: Branch {
                         name: "base-branch".to_owned(),
                         commit: "456".to_owned()
-                    }
+                    },
+                    from_fork: false
                 },
                 installation_id: 789,
                 repository_name: "Repo".to_owned(),
@@ -476,13 +629,20 @@ mod tests {
             r#"{
                     "action": "synchronize",
                     "pull_request": {
+                        "number": 1,
                         "head": {
                             "sha": "123",
-                            "ref": "refs/heads/head-branch"
+                            "ref": "refs/heads/head-branch",
+                            "repo": {
+                                "full_name": "Owner/Repo"
+                            }
                         },
                         "base": {
                             "sha": "456",
-                            "ref": "refs/heads/base-branch"
+                            "ref": "refs/heads/base-branch",
+                            "repo": {
+                                "full_name": "Owner/Repo"
+                            }
                         }
                     },
                     "repository": {
@@ -503,6 +663,7 @@ mod tests {
             result,
             Ok(Some(Trigger {
                 event: TriggerEvent::PullRequest {
+                    number: 1,
                     source: Branch {
                         name: "head-branch".to_owned(),
                         commit: "123".to_owned()
@@ -510,7 +671,8 @@ mod tests {
                     target: Branch {
                         name: "base-branch".to_owned(),
                         commit: "456".to_owned()
-                    }
+                    },
+                    from_fork: false
                 },
                 installation_id: 789,
                 repository_name: "Repo".to_owned(),
@@ -518,4 +680,46 @@ mod tests {
             }))
         );
     }
+
+    #[test]
+    fn parse_trigger_should_flag_pull_request_from_fork() {
+        let mut headers = HeaderMap::new();
+        headers.insert("X-GitHub-Event", HeaderValue::from_static("pull_request"));
+
+        let body = VerifiedBody::from_static(
+            r#"{
+                    "action": "opened",
+                    "pull_request": {
+                        "number": 1,
+                        "head": {
+                            "sha": "123",
+                            "ref": "refs/heads/head-branch",
+                            "repo": {
+                                "full_name": "Contributor/Repo"
+                            }
+                        },
+                        "base": {
+                            "sha": "456",
+                            "ref": "refs/heads/base-branch",
+                            "repo": {
+                                "full_name": "Owner/Repo"
+                            }
+                        }
+                    },
+                    "repository": {
+                        "name": "Repo",
+                        "owner": {
+                            "login": "Owner"
+                        }
+                    },
+                    "installation": {
+                        "id": 789
+                    }
+                }"#,
+        );
+
+        let trigger = parse_trigger(headers, body).unwrap().unwrap();
+
+        assert!(!trigger.event.allows_secrets());
+    }
 }
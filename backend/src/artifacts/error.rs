@@ -0,0 +1,10 @@
+use domain::repositories::RepositoryError;
+use thiserror::Error;
+
+#[derive(Debug, Error)]
+pub enum ArtifactError {
+    #[error(transparent)]
+    Repository(#[from] RepositoryError),
+    #[error("Could not access artifact storage: {0}")]
+    Io(#[from] std::io::Error),
+}
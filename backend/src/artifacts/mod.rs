@@ -0,0 +1,224 @@
+use std::{
+    collections::HashSet,
+    fs,
+    io::{self, Read, Write},
+    path::{Path, PathBuf},
+    sync::atomic::{AtomicU64, Ordering},
+    time::{Duration, SystemTime},
+};
+
+use chrono::Utc;
+use sha2::{Digest, Sha256};
+use tokio::time::MissedTickBehavior;
+
+use self::error::ArtifactError;
+use crate::context::Context;
+
+pub mod error;
+
+const CLEANUP_INTERVAL: Duration = Duration::from_secs(60 * 60);
+
+/// Archives not referenced by an artifact are only removed after this long, so ones
+/// stored right before their record is created are kept
+const UNREFERENCED_GRACE_PERIOD: Duration = Duration::from_secs(60 * 60);
+
+/// Archives are written here before they are moved to their digest, leftovers of a crash
+/// are removed like unreferenced archives
+const TEMPORARY_DIRECTORY: &str = "tmp";
+
+/// Numbers the temporary files of the process, so archives stored at the same time never
+/// share one
+static NEXT_TEMPORARY: AtomicU64 = AtomicU64::new(0);
+
+/// Stores archives on disk content-addressed by their SHA-256 digest, so identical
+/// archives are only stored once
+#[derive(Clone)]
+pub struct ArtifactStore {
+    directory: PathBuf,
+}
+
+impl ArtifactStore {
+    pub fn new(directory: PathBuf) -> Self {
+        Self { directory }
+    }
+
+    /// Reads the archive into the store, returning the digest it is stored under and its size
+    pub fn store(&self, archive: &mut impl Read) -> Result<(String, u64), io::Error> {
+        let temporary_directory = self.directory.join(TEMPORARY_DIRECTORY);
+        fs::create_dir_all(&temporary_directory)?;
+
+        // Written to a temporary file first, so a partial archive is never served
+        let temporary = temporary_directory.join(format!(
+            "{}-{}",
+            std::process::id(),
+            NEXT_TEMPORARY.fetch_add(1, Ordering::Relaxed)
+        ));
+        let written = write_hashed(archive, &temporary);
+        let (digest, size) = match written {
+            Ok(written) => written,
+            Err(err) => {
+                let _ = fs::remove_file(&temporary);
+                return Err(err);
+            }
+        };
+        let path = self.path(&digest);
+
+        // An archive that is already stored may not be referenced anymore, it is touched so
+        // it is not removed before the artifact that is about to refer to it is created
+        match touch(&path) {
+            Ok(()) => fs::remove_file(&temporary)?,
+            Err(err) if err.kind() == io::ErrorKind::NotFound => {
+                fs::create_dir_all(path.parent().unwrap())?;
+                fs::rename(&temporary, &path)?;
+            }
+            Err(err) => {
+                let _ = fs::remove_file(&temporary);
+                return Err(err);
+            }
+        }
+
+        Ok((digest, size))
+    }
+
+    pub fn path(&self, digest: &str) -> PathBuf {
+        self.directory.join(&digest[..2]).join(digest)
+    }
+
+    /// Removes all archives that are not referenced anymore, returning how many there were
+    pub fn remove_unreferenced(&self, referenced: &HashSet<String>) -> Result<usize, io::Error> {
+        let mut removed = 0;
+
+        if !self.directory.exists() {
+            return Ok(removed);
+        }
+
+        for prefix in fs::read_dir(&self.directory)? {
+            for entry in fs::read_dir(prefix?.path())? {
+                let entry = entry?;
+                let name = entry.file_name().to_string_lossy().into_owned();
+                let age = SystemTime::now()
+                    .duration_since(entry.metadata()?.modified()?)
+                    .unwrap_or_default();
+
+                if !referenced.contains(&name) && age > UNREFERENCED_GRACE_PERIOD {
+                    fs::remove_file(entry.path())?;
+                    removed += 1;
+                }
+            }
+        }
+
+        Ok(removed)
+    }
+}
+
+/// Copies the archive to the file, returning its digest and size
+fn write_hashed(archive: &mut impl Read, path: &Path) -> Result<(String, u64), io::Error> {
+    let mut file = fs::File::create(path)?;
+    let mut hasher = Sha256::new();
+    let mut buffer = vec![0; 64 * 1024];
+    let mut size = 0;
+
+    loop {
+        let read = match archive.read(&mut buffer) {
+            Ok(0) => break,
+            Ok(read) => read,
+            Err(err) if err.kind() == io::ErrorKind::Interrupted => continue,
+            Err(err) => return Err(err),
+        };
+
+        hasher.update(&buffer[..read]);
+        file.write_all(&buffer[..read])?;
+        size += read as u64;
+    }
+
+    file.sync_all()?;
+
+    Ok((hex::encode(hasher.finalize()), size))
+}
+
+fn touch(path: &Path) -> Result<(), io::Error> {
+    fs::File::options()
+        .write(true)
+        .open(path)?
+        .set_modified(SystemTime::now())
+}
+
+/// Periodically deletes artifacts older than the configured retention along with
+/// the archives no artifact refers to anymore
+pub async fn run_cleanup(context: Context) {
+    let mut interval = tokio::time::interval(CLEANUP_INTERVAL);
+    interval.set_missed_tick_behavior(MissedTickBehavior::Skip);
+
+    loop {
+        interval.tick().await;
+
+        if let Err(err) = remove_expired(&context) {
+            println!("Failed to remove expired artifacts: {err}");
+        }
+    }
+}
+
+fn remove_expired(context: &Context) -> Result<(), ArtifactError> {
+    let expired_before = (Utc::now() - context.config.artifacts.retention).naive_utc();
+
+    let referenced = {
+        let mut artifacts = context.repositories.artifacts.lock().unwrap();
+        let deleted = artifacts.delete_created_before(expired_before)?;
+
+        if deleted > 0 {
+            println!("Removed {deleted} expired artifacts");
+        }
+
+        artifacts.referenced_digests()?
+    };
+
+    context.artifact_store.remove_unreferenced(&referenced)?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn store_should_address_archives_by_digest() {
+        let directory = std::env::temp_dir().join(format!("artifacts-{}", std::process::id()));
+        let store = ArtifactStore::new(directory.clone());
+
+        let (digest, size) = store.store(&mut &b"archive"[..]).unwrap();
+
+        assert_eq!(digest, hex::encode(Sha256::digest(b"archive")));
+        assert_eq!(size, 7);
+        assert_eq!(store.store(&mut &b"archive"[..]).unwrap().0, digest);
+        assert_eq!(fs::read(store.path(&digest)).unwrap(), b"archive");
+
+        // Recently stored archives are kept even if they are not referenced yet
+        assert_eq!(store.remove_unreferenced(&HashSet::new()).unwrap(), 0);
+        assert!(store.path(&digest).exists());
+
+        fs::remove_dir_all(directory).unwrap();
+    }
+
+    #[test]
+    fn store_should_refresh_archives_stored_before() {
+        let directory =
+            std::env::temp_dir().join(format!("artifacts-refresh-{}", std::process::id()));
+        let store = ArtifactStore::new(directory.clone());
+
+        let (digest, _) = store.store(&mut &b"archive"[..]).unwrap();
+        fs::File::options()
+            .write(true)
+            .open(store.path(&digest))
+            .unwrap()
+            .set_modified(SystemTime::now() - 2 * UNREFERENCED_GRACE_PERIOD)
+            .unwrap();
+
+        store.store(&mut &b"archive"[..]).unwrap();
+
+        assert_eq!(store.remove_unreferenced(&HashSet::new()).unwrap(), 0);
+        assert!(store.path(&digest).exists());
+
+        fs::remove_dir_all(directory).unwrap();
+    }
+}
@@ -0,0 +1,8 @@
+use backend::agent::{self, AgentConfig};
+
+#[tokio::main]
+async fn main() -> Result<(), String> {
+    let config = AgentConfig::from_environment()?;
+
+    agent::run(config).await
+}
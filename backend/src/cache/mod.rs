@@ -0,0 +1,591 @@
+use std::{
+    fs, io,
+    path::{Path, PathBuf},
+    sync::atomic::{AtomicU64, Ordering},
+    time::{Duration, SystemTime},
+};
+
+use domain::{Trigger, TriggerEvent};
+use sha2::{Digest, Sha256};
+use tar::{Archive, Builder, EntryType};
+
+/// Temporary files older than this are left over from saves that were interrupted
+const STALE_TEMPORARY_AGE: Duration = Duration::from_secs(60 * 60);
+
+/// Numbers the temporary files of the process, so saves running at the same time never
+/// share one
+static NEXT_TEMPORARY: AtomicU64 = AtomicU64::new(0);
+
+/// Where a run saves its caches and which ones it may restore
+pub struct CacheScope<'a> {
+    pub repository_owner: &'a str,
+    pub repository_name: &'a str,
+    /// Caches are saved here, and restored from here first
+    pub namespace: CacheNamespace<'a>,
+    /// Branches whose caches are restored if the namespace has none, in order
+    pub fallback_branches: Vec<&'a str>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CacheNamespace<'a> {
+    Branch(&'a str),
+    /// Pull requests run code of their source branch, which may come from a fork, so
+    /// they get a namespace of their own rather than saving into a branch
+    PullRequest(u64),
+}
+
+impl<'a> CacheScope<'a> {
+    /// Pull requests restore caches of their target branch, everything else the ones of its
+    /// branch. Both fall back to the default branch.
+    pub fn new(trigger: &'a Trigger, default_branch: Option<&'a str>) -> Self {
+        let (namespace, mut fallback_branches) = match &trigger.event {
+            TriggerEvent::PullRequest { number, target, .. } => (
+                CacheNamespace::PullRequest(*number),
+                vec![target.name.as_str()],
+            ),
+            event => (CacheNamespace::Branch(&event.branch().name), vec![]),
+        };
+        fallback_branches.extend(default_branch);
+
+        Self {
+            repository_owner: &trigger.repository_owner,
+            repository_name: &trigger.repository_name,
+            namespace,
+            fallback_branches,
+        }
+    }
+
+    /// Names of the directories caches are restored from, in order
+    fn directory_names(&self) -> Vec<String> {
+        let mut names = vec![self.namespace.directory_name()];
+
+        for branch in &self.fallback_branches {
+            let name = encode(branch);
+
+            if !names.contains(&name) {
+                names.push(name);
+            }
+        }
+
+        names
+    }
+}
+
+impl CacheNamespace<'_> {
+    /// `@` is escaped in branch names, so pull requests never collide with a branch
+    fn directory_name(&self) -> String {
+        match self {
+            Self::Branch(branch) => encode(branch),
+            Self::PullRequest(number) => format!("@pull-{number}"),
+        }
+    }
+}
+
+pub struct CacheHit {
+    pub key: String,
+    /// Whether the key matched exactly rather than by one of the restore keys
+    pub exact: bool,
+    pub archive: Vec<u8>,
+}
+
+/// Stores caches as tar archives on the local disk, removing the least recently used
+/// ones once they take up more than the maximum size.
+///
+/// Archives are laid out as `<owner>/<repository>/<namespace>/<key>.tar`, the modification
+/// time of an archive is when it was last saved or restored.
+#[derive(Clone)]
+pub struct CacheStore {
+    directory: PathBuf,
+    max_size: u64,
+}
+
+struct CacheEntry {
+    path: PathBuf,
+    size: u64,
+    last_used: SystemTime,
+}
+
+impl CacheStore {
+    pub fn new(directory: PathBuf, max_size: u64) -> Self {
+        Self {
+            directory,
+            max_size,
+        }
+    }
+
+    /// Looks for the exact key, then for the most recently used cache starting with one of
+    /// the restore keys, first in the namespace and then on the fallback branches
+    pub fn restore(
+        &self,
+        scope: &CacheScope,
+        key: &str,
+        restore_keys: &[String],
+    ) -> Result<Option<CacheHit>, io::Error> {
+        for name in scope.directory_names() {
+            let directory = self.scope_directory(scope, &name);
+            let exact = directory.join(archive_name(key));
+
+            if exact.exists() {
+                return Ok(Some(CacheHit {
+                    key: key.to_owned(),
+                    exact: true,
+                    archive: read_and_touch(&exact)?,
+                }));
+            }
+
+            for restore_key in restore_keys {
+                if let Some((key, path)) = find_latest_with_prefix(&directory, restore_key)? {
+                    return Ok(Some(CacheHit {
+                        key,
+                        exact: false,
+                        archive: read_and_touch(&path)?,
+                    }));
+                }
+            }
+        }
+
+        Ok(None)
+    }
+
+    /// Saves the archive in the namespace of the scope, returning whether it was stored at all.
+    /// Archives larger than the maximum size are not.
+    pub fn save(&self, scope: &CacheScope, key: &str, archive: &[u8]) -> Result<bool, io::Error> {
+        if archive.len() as u64 > self.max_size {
+            return Ok(false);
+        }
+
+        let directory = self.scope_directory(scope, &scope.namespace.directory_name());
+        fs::create_dir_all(&directory)?;
+
+        // Written to a temporary file first, so a partial archive is never restored
+        let path = directory.join(archive_name(key));
+        let temporary = directory.join(format!(
+            "{}-{}.tmp",
+            std::process::id(),
+            NEXT_TEMPORARY.fetch_add(1, Ordering::Relaxed)
+        ));
+
+        fs::write(&temporary, archive)?;
+        fs::rename(&temporary, &path)?;
+
+        self.evict()?;
+
+        Ok(true)
+    }
+
+    /// Removes the least recently used archives until all of them fit into the maximum size,
+    /// along with stale temporary files, returning how many bytes were freed
+    pub fn evict(&self) -> Result<u64, io::Error> {
+        let (mut entries, temporaries): (Vec<_>, Vec<_>) = self
+            .entries()?
+            .into_iter()
+            .partition(|entry| entry.path.extension().is_some_and(|ext| ext == "tar"));
+        let mut freed = 0;
+
+        for temporary in temporaries {
+            let age = SystemTime::now()
+                .duration_since(temporary.last_used)
+                .unwrap_or_default();
+
+            if age > STALE_TEMPORARY_AGE {
+                fs::remove_file(&temporary.path)?;
+                freed += temporary.size;
+            }
+        }
+
+        entries.sort_by_key(|entry| entry.last_used);
+        let mut size = entries.iter().map(|entry| entry.size).sum::<u64>();
+
+        for entry in entries {
+            if size <= self.max_size {
+                break;
+            }
+
+            fs::remove_file(&entry.path)?;
+            size -= entry.size;
+            freed += entry.size;
+        }
+
+        Ok(freed)
+    }
+
+    fn scope_directory(&self, scope: &CacheScope, name: &str) -> PathBuf {
+        self.directory
+            .join(encode(scope.repository_owner))
+            .join(encode(scope.repository_name))
+            .join(name)
+    }
+
+    fn entries(&self) -> Result<Vec<CacheEntry>, io::Error> {
+        let mut entries = vec![];
+
+        for owner in read_directories(&self.directory)? {
+            for repository in read_directories(&owner)? {
+                for namespace in read_directories(&repository)? {
+                    for entry in fs::read_dir(namespace)? {
+                        let entry = entry?;
+                        let metadata = entry.metadata()?;
+
+                        if metadata.is_file() {
+                            entries.push(CacheEntry {
+                                path: entry.path(),
+                                size: metadata.len(),
+                                last_used: metadata.modified()?,
+                            });
+                        }
+                    }
+                }
+            }
+        }
+
+        Ok(entries)
+    }
+}
+
+/// Combines archives of paths relative to the workspace, as returned by Docker, into one
+/// archive that can be extracted into the workspace
+pub fn combine_archives(archives: &[(&str, Vec<u8>)]) -> Result<Vec<u8>, io::Error> {
+    let mut builder = Builder::new(vec![]);
+
+    for (path, archive) in archives {
+        // Docker roots the archive at the last component of the path
+        let parent = Path::new(path).parent().unwrap_or(Path::new(""));
+
+        for entry in Archive::new(archive.as_slice()).entries()? {
+            let mut entry = entry?;
+            let mut header = entry.header().clone();
+            let entry_path = parent.join(entry.path()?);
+
+            match header.entry_type() {
+                EntryType::Symlink => {
+                    let target = entry.link_name()?.unwrap_or_default().into_owned();
+                    builder.append_link(&mut header, entry_path, target)?;
+                }
+                EntryType::Link => {
+                    let target = parent.join(entry.link_name()?.unwrap_or_default());
+                    builder.append_link(&mut header, entry_path, target)?;
+                }
+                _ => builder.append_data(&mut header, entry_path, &mut entry)?,
+            }
+        }
+    }
+
+    builder.into_inner()
+}
+
+/// Adds the names and contents of all files in the archive to the hash
+pub fn hash_archive_files(hasher: &mut Sha256, archive: &[u8]) -> Result<(), io::Error> {
+    for entry in Archive::new(archive).entries()? {
+        let mut entry = entry?;
+
+        if entry.header().entry_type().is_file() {
+            hasher.update(entry.path_bytes());
+            hasher.update([0]);
+            io::copy(&mut entry, hasher)?;
+        }
+    }
+
+    Ok(())
+}
+
+fn find_latest_with_prefix(
+    directory: &Path,
+    prefix: &str,
+) -> Result<Option<(String, PathBuf)>, io::Error> {
+    if !directory.exists() {
+        return Ok(None);
+    }
+
+    let prefix = encode(prefix);
+    let mut latest: Option<(SystemTime, String, PathBuf)> = None;
+
+    for entry in fs::read_dir(directory)? {
+        let entry = entry?;
+        let name = entry.file_name().to_string_lossy().into_owned();
+
+        let Some(key) = name
+            .strip_suffix(".tar")
+            .filter(|key| key.starts_with(&prefix))
+        else {
+            continue;
+        };
+
+        let modified = entry.metadata()?.modified()?;
+
+        if latest
+            .as_ref()
+            .is_none_or(|(latest, ..)| modified > *latest)
+        {
+            latest = Some((modified, decode(key), entry.path()));
+        }
+    }
+
+    Ok(latest.map(|(_, key, path)| (key, path)))
+}
+
+fn read_directories(directory: &Path) -> Result<Vec<PathBuf>, io::Error> {
+    if !directory.exists() {
+        return Ok(vec![]);
+    }
+
+    let mut directories = vec![];
+
+    for entry in fs::read_dir(directory)? {
+        let entry = entry?;
+
+        if entry.file_type()?.is_dir() {
+            directories.push(entry.path());
+        }
+    }
+
+    Ok(directories)
+}
+
+/// Marks the archive as used, so it is evicted last
+fn read_and_touch(path: &Path) -> Result<Vec<u8>, io::Error> {
+    fs::File::options()
+        .append(true)
+        .open(path)?
+        .set_modified(SystemTime::now())?;
+
+    fs::read(path)
+}
+
+fn archive_name(key: &str) -> String {
+    format!("{}.tar", encode(key))
+}
+
+/// Escapes everything but ASCII letters, digits, `-` and `_` as `%XX`. Prefixes of names
+/// stay prefixes of their encoded form.
+fn encode(name: &str) -> String {
+    name.bytes()
+        .map(|byte| match byte {
+            b'a'..=b'z' | b'A'..=b'Z' | b'0'..=b'9' | b'-' | b'_' => (byte as char).to_string(),
+            _ => format!("%{byte:02X}"),
+        })
+        .collect()
+}
+
+fn decode(name: &str) -> String {
+    let mut bytes = vec![];
+    let mut rest = name.as_bytes();
+
+    while let Some((&byte, remaining)) = rest.split_first() {
+        match (byte, remaining.get(..2)) {
+            (b'%', Some(hex)) => {
+                let hex = std::str::from_utf8(hex).unwrap_or_default();
+                bytes.push(u8::from_str_radix(hex, 16).unwrap_or_default());
+                rest = &remaining[2..];
+            }
+            _ => {
+                bytes.push(byte);
+                rest = remaining;
+            }
+        }
+    }
+
+    String::from_utf8_lossy(&bytes).into_owned()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use domain::Branch;
+
+    use super::*;
+
+    fn scope<'a>(branch: &'a str) -> CacheScope<'a> {
+        CacheScope {
+            repository_owner: "owner",
+            repository_name: "repo",
+            namespace: CacheNamespace::Branch(branch),
+            fallback_branches: vec!["main"],
+        }
+    }
+
+    fn age(store: &CacheStore, branch: &str, key: &str, seconds: u64) {
+        let path = store
+            .scope_directory(&scope(branch), &encode(branch))
+            .join(archive_name(key));
+
+        fs::File::options()
+            .append(true)
+            .open(path)
+            .unwrap()
+            .set_modified(SystemTime::now() - Duration::from_secs(seconds))
+            .unwrap();
+    }
+
+    #[test]
+    fn restore_should_fall_back_to_restore_keys_and_default_branch() {
+        let directory = std::env::temp_dir().join(format!("cache-restore-{}", std::process::id()));
+        let store = CacheStore::new(directory.clone(), 1024);
+
+        store.save(&scope("main"), "cargo-aaa", b"main").unwrap();
+        store.save(&scope("feature"), "cargo-bbb", b"old").unwrap();
+        store.save(&scope("feature"), "cargo-ccc", b"new").unwrap();
+        age(&store, "feature", "cargo-bbb", 60);
+        age(&store, "feature", "cargo-ccc", 30);
+
+        let hit = store
+            .restore(&scope("feature"), "cargo-bbb", &[])
+            .unwrap()
+            .unwrap();
+        assert!(hit.exact);
+        assert_eq!(hit.archive, b"old");
+
+        // The restored cache is now the most recently used one
+        let hit = store
+            .restore(&scope("feature"), "cargo-ddd", &["cargo-".to_owned()])
+            .unwrap()
+            .unwrap();
+        assert!(!hit.exact);
+        assert_eq!(hit.key, "cargo-bbb");
+
+        let hit = store
+            .restore(&scope("other/branch"), "cargo-ddd", &["cargo-".to_owned()])
+            .unwrap()
+            .unwrap();
+        assert_eq!(hit.key, "cargo-aaa");
+
+        assert!(store
+            .restore(&scope("feature"), "npm-aaa", &["npm-".to_owned()])
+            .unwrap()
+            .is_none());
+
+        fs::remove_dir_all(directory).unwrap();
+    }
+
+    #[test]
+    fn save_should_evict_least_recently_used_caches() {
+        let directory = std::env::temp_dir().join(format!("cache-evict-{}", std::process::id()));
+        let store = CacheStore::new(directory.clone(), 10);
+
+        store.save(&scope("main"), "a", b"aaaa").unwrap();
+        store.save(&scope("main"), "b", b"bbbb").unwrap();
+        age(&store, "main", "a", 60);
+        age(&store, "main", "b", 120);
+        store.restore(&scope("main"), "b", &[]).unwrap();
+
+        assert!(store.save(&scope("main"), "c", b"cccc").unwrap());
+        assert!(!store
+            .save(&scope("main"), "d", b"too large for the cache")
+            .unwrap());
+
+        assert!(store.restore(&scope("main"), "a", &[]).unwrap().is_none());
+        assert!(store.restore(&scope("main"), "b", &[]).unwrap().is_some());
+        assert!(store.restore(&scope("main"), "c", &[]).unwrap().is_some());
+
+        fs::remove_dir_all(directory).unwrap();
+    }
+
+    #[test]
+    fn evict_should_remove_stale_temporary_files() {
+        let directory = std::env::temp_dir().join(format!("cache-stale-{}", std::process::id()));
+        let store = CacheStore::new(directory.clone(), 1024);
+
+        store.save(&scope("main"), "a", b"aaaa").unwrap();
+        let branch = store.scope_directory(&scope("main"), "main");
+        let (stale, recent) = (branch.join("stale.tmp"), branch.join("recent.tmp"));
+        fs::write(&stale, b"partial").unwrap();
+        fs::write(&recent, b"partial").unwrap();
+        fs::File::options()
+            .append(true)
+            .open(&stale)
+            .unwrap()
+            .set_modified(SystemTime::now() - STALE_TEMPORARY_AGE * 2)
+            .unwrap();
+
+        assert_eq!(store.evict().unwrap(), 7);
+        assert!(!stale.exists());
+        assert!(recent.exists());
+        assert!(store.restore(&scope("main"), "a", &[]).unwrap().is_some());
+
+        fs::remove_dir_all(directory).unwrap();
+    }
+
+    #[test]
+    fn fork_pull_request_should_not_save_into_branch() {
+        let directory = std::env::temp_dir().join(format!("cache-fork-{}", std::process::id()));
+        let store = CacheStore::new(directory.clone(), 1024);
+        let branch = |name: &str| Branch {
+            name: name.to_owned(),
+            commit: "123".to_owned(),
+        };
+        let trigger = |event| Trigger {
+            repository_owner: "owner".to_owned(),
+            repository_name: "repo".to_owned(),
+            installation_id: 1,
+            event,
+        };
+        let fork = trigger(TriggerEvent::PullRequest {
+            number: 7,
+            source: branch("main"),
+            target: branch("main"),
+            from_fork: true,
+        });
+        let push = trigger(TriggerEvent::Push {
+            branch: branch("main"),
+            before: None,
+        });
+
+        store
+            .save(&CacheScope::new(&push, Some("main")), "cargo-aaa", b"main")
+            .unwrap();
+        store
+            .save(&CacheScope::new(&fork, Some("main")), "cargo-bbb", b"fork")
+            .unwrap();
+
+        let restore = |trigger, key: &str| {
+            store
+                .restore(
+                    &CacheScope::new(trigger, Some("main")),
+                    key,
+                    &["cargo-".to_owned()],
+                )
+                .unwrap()
+                .unwrap()
+        };
+        assert_eq!(restore(&push, "cargo-bbb").archive, b"main");
+        assert_eq!(restore(&fork, "cargo-bbb").archive, b"fork");
+        assert!(!directory.join("owner/repo/main/cargo-bbb.tar").exists());
+
+        fs::remove_dir_all(directory).unwrap();
+    }
+
+    #[test]
+    fn combine_archives_should_root_entries_in_workspace() {
+        let archive = |name: &str| {
+            let mut builder = Builder::new(vec![]);
+            let mut header = tar::Header::new_gnu();
+            header.set_size(4);
+            header.set_mode(0o644);
+            builder
+                .append_data(&mut header, format!("{name}/file"), b"data".as_slice())
+                .unwrap();
+            builder.into_inner().unwrap()
+        };
+
+        let combined = combine_archives(&[
+            ("target", archive("target")),
+            (".cargo/registry", archive("registry")),
+        ])
+        .unwrap();
+
+        let paths = Archive::new(combined.as_slice())
+            .entries()
+            .unwrap()
+            .map(|entry| entry.unwrap().path().unwrap().into_owned())
+            .collect::<Vec<_>>();
+
+        assert_eq!(
+            paths,
+            vec![
+                PathBuf::from("target/file"),
+                PathBuf::from(".cargo/registry/file")
+            ]
+        );
+        assert_eq!(decode(&encode("cargo/ä-1.2")), "cargo/ä-1.2");
+    }
+}
@@ -0,0 +1,202 @@
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+};
+
+use domain::{PipelineId, Trigger, TriggerEvent};
+use tokio::sync::watch;
+
+/// Identifies the runs that supersede each other, those of one pipeline for the same branch or
+/// pull request
+#[derive(Clone, PartialEq, Eq, Hash, Debug)]
+pub struct RunGroup {
+    repository: String,
+    pipeline: String,
+    event: String,
+}
+
+impl RunGroup {
+    pub fn new(trigger: &Trigger, pipeline: &str) -> Self {
+        let event = match &trigger.event {
+            // Forks may use the same branch names, so pull requests are told apart by number
+            TriggerEvent::PullRequest {
+                number: 0,
+                source,
+                target,
+                ..
+            } => format!("pull_request/{}/{}", source.name, target.name),
+            TriggerEvent::PullRequest { number, .. } => format!("pull_request/{number}"),
+            event => format!("{}/{}", event.name(), event.branch().name),
+        };
+
+        Self {
+            repository: format!("{}/{}", trigger.repository_owner, trigger.repository_name),
+            pipeline: pipeline.to_owned(),
+            event,
+        }
+    }
+}
+
+/// Signals a running pipeline to stop
+#[derive(Clone)]
+pub struct Cancellation {
+    receiver: watch::Receiver<bool>,
+}
+
+impl Cancellation {
+    pub fn is_cancelled(&self) -> bool {
+        *self.receiver.borrow()
+    }
+
+    /// Resolves once the pipeline is cancelled, never if it is not
+    pub async fn cancelled(&self) {
+        let mut receiver = self.receiver.clone();
+
+        if receiver.wait_for(|cancelled| *cancelled).await.is_err() {
+            std::future::pending::<()>().await;
+        }
+    }
+}
+
+type Runs = HashMap<PipelineId, (RunGroup, watch::Sender<bool>)>;
+
+/// Keeps track of the running pipelines so they can be cancelled.
+///
+/// A pipeline is registered from the moment it is queued until it finished running.
+#[derive(Clone, Default)]
+pub struct Cancellations {
+    runs: Arc<Mutex<Runs>>,
+}
+
+impl Cancellations {
+    /// Registering a pipeline again returns the cancellation of the first registration
+    pub fn register(&self, pipeline_id: PipelineId, group: RunGroup) -> Cancellation {
+        let mut runs = self.runs.lock().unwrap();
+        let (_, sender) = runs
+            .entry(pipeline_id)
+            .or_insert_with(|| (group, watch::channel(false).0));
+
+        Cancellation {
+            receiver: sender.subscribe(),
+        }
+    }
+
+    pub fn unregister(&self, pipeline_id: PipelineId) {
+        self.runs.lock().unwrap().remove(&pipeline_id);
+    }
+
+    /// Returns `false` if the pipeline is not running
+    pub fn cancel(&self, pipeline_id: PipelineId) -> bool {
+        match self.runs.lock().unwrap().get(&pipeline_id) {
+            Some((_, sender)) => {
+                sender.send_replace(true);
+                true
+            }
+            None => false,
+        }
+    }
+
+    pub fn is_registered(&self, pipeline_id: PipelineId) -> bool {
+        self.runs.lock().unwrap().contains_key(&pipeline_id)
+    }
+
+    pub fn is_cancelled(&self, pipeline_id: PipelineId) -> bool {
+        self.runs
+            .lock()
+            .unwrap()
+            .get(&pipeline_id)
+            .is_some_and(|(_, sender)| *sender.borrow())
+    }
+
+    /// Cancels all running pipelines of the group, returning their ids
+    pub fn cancel_group(&self, group: &RunGroup) -> Vec<PipelineId> {
+        self.runs
+            .lock()
+            .unwrap()
+            .iter()
+            .filter(|(_, (run_group, _))| run_group == group)
+            .map(|(pipeline_id, (_, sender))| {
+                sender.send_replace(true);
+                *pipeline_id
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use domain::Branch;
+
+    use super::*;
+
+    fn trigger(branch: &str) -> Trigger {
+        Trigger {
+            repository_owner: "Owner".to_owned(),
+            repository_name: "Repo".to_owned(),
+            installation_id: 1,
+            event: TriggerEvent::Push {
+                branch: Branch {
+                    name: branch.to_owned(),
+                    commit: "abc123".to_owned(),
+                },
+                before: None,
+            },
+        }
+    }
+
+    fn pull_request(number: u64, source: &str) -> Trigger {
+        let branch = |name: &str| Branch {
+            name: name.to_owned(),
+            commit: "abc123".to_owned(),
+        };
+
+        Trigger {
+            event: TriggerEvent::PullRequest {
+                number,
+                source: branch(source),
+                target: branch("main"),
+                from_fork: true,
+            },
+            ..trigger("main")
+        }
+    }
+
+    #[tokio::test]
+    async fn cancel_should_signal_registered_pipeline() {
+        let cancellations = Cancellations::default();
+        let cancellation =
+            cancellations.register(PipelineId(1), RunGroup::new(&trigger("main"), "Build"));
+
+        assert!(!cancellation.is_cancelled());
+        assert!(cancellations.cancel(PipelineId(1)));
+
+        cancellation.cancelled().await;
+        assert!(cancellation.is_cancelled());
+        assert!(!cancellations.cancel(PipelineId(2)));
+    }
+
+    #[test]
+    fn cancel_group_should_only_cancel_pipelines_of_same_group() {
+        let cancellations = Cancellations::default();
+        let main = cancellations.register(PipelineId(1), RunGroup::new(&trigger("main"), "Build"));
+        let other_branch =
+            cancellations.register(PipelineId(2), RunGroup::new(&trigger("feature"), "Build"));
+        let other_pipeline =
+            cancellations.register(PipelineId(3), RunGroup::new(&trigger("main"), "Lint"));
+
+        let cancelled = cancellations.cancel_group(&RunGroup::new(&trigger("main"), "Build"));
+
+        assert_eq!(cancelled, vec![PipelineId(1)]);
+        assert!(main.is_cancelled());
+        assert!(!other_branch.is_cancelled());
+        assert!(!other_pipeline.is_cancelled());
+    }
+
+    #[test]
+    fn run_group_should_tell_apart_pull_requests_with_same_branches() {
+        let first = RunGroup::new(&pull_request(1, "main"), "Build");
+
+        assert_eq!(first, RunGroup::new(&pull_request(1, "main"), "Build"));
+        assert_ne!(first, RunGroup::new(&pull_request(2, "main"), "Build"));
+    }
+}
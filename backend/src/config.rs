@@ -1,9 +1,23 @@
+use std::{path::PathBuf, time::Duration};
+
+use domain::{DockerImageReference, MemorySize, ResourcesConfiguration};
 use secrecy::SecretString;
+use serde::{de::IntoDeserializer, Deserialize};
+
+use crate::registry::{RegistryCredentials, DOCKER_AUTH_CONFIG};
 
 #[derive(Clone)]
 pub struct AppConfig {
     pub github: GitHubConfig,
     pub database: DatabaseConfig,
+    pub api: ApiConfig,
+    pub secrets: SecretsConfig,
+    pub runner: RunnerConfig,
+    pub agents: AgentsConfig,
+    pub artifacts: ArtifactsConfig,
+    pub cache: CacheConfig,
+    pub resources: ResourcesConfig,
+    pub registries: RegistryConfig,
 }
 
 #[derive(Clone)]
@@ -18,11 +32,77 @@ pub struct DatabaseConfig {
     pub url: String,
 }
 
+#[derive(Clone)]
+pub struct ApiConfig {
+    /// Bearer token required by endpoints that change state, these are disabled if unset
+    pub token: Option<SecretString>,
+}
+
+#[derive(Clone)]
+pub struct SecretsConfig {
+    /// 32 bytes encoded as hex, used to encrypt secrets at rest. Secrets are disabled if unset
+    pub master_key: Option<SecretString>,
+}
+
+#[derive(Clone)]
+pub struct RunnerConfig {
+    /// Image of the implicit clone step, needs to provide `git` and `git-lfs`
+    pub clone_image: DockerImageReference,
+    /// Maximum number of pipelines running at the same time, further pipelines are queued
+    pub max_concurrent_pipelines: usize,
+    pub max_concurrent_pipelines_per_repository: usize,
+}
+
+#[derive(Clone)]
+pub struct AgentsConfig {
+    /// Bearer token remote agents authenticate with, agents can not register if unset
+    pub token: Option<SecretString>,
+}
+
+#[derive(Clone)]
+pub struct ArtifactsConfig {
+    /// Where archives are stored, named by their SHA-256 digest
+    pub directory: PathBuf,
+    /// How long artifacts are kept after they were created
+    pub retention: Duration,
+}
+
+#[derive(Clone)]
+pub struct CacheConfig {
+    /// Where cache archives are stored, per repository and branch
+    pub directory: PathBuf,
+    /// Total size of all archives in bytes, the least recently used ones are removed beyond it
+    pub max_size: u64,
+}
+
+#[derive(Clone)]
+pub struct ResourcesConfig {
+    /// Limits of steps that do not set their own
+    pub defaults: ResourcesConfiguration,
+    /// Upper bounds of the limits of steps, also applied to steps that set none
+    pub maximums: ResourcesConfiguration,
+}
+
+#[derive(Clone)]
+pub struct RegistryConfig {
+    /// Used for every repository but not for pull requests from forks, repositories can
+    /// override them per hostname with a secret
+    pub credentials: RegistryCredentials,
+}
+
 impl AppConfig {
     pub fn from_environment() -> Result<AppConfig, String> {
         Ok(AppConfig {
             github: GitHubConfig::from_environment()?,
             database: DatabaseConfig::from_environment()?,
+            api: ApiConfig::from_environment(),
+            secrets: SecretsConfig::from_environment(),
+            runner: RunnerConfig::from_environment()?,
+            agents: AgentsConfig::from_environment(),
+            artifacts: ArtifactsConfig::from_environment()?,
+            cache: CacheConfig::from_environment()?,
+            resources: ResourcesConfig::from_environment()?,
+            registries: RegistryConfig::from_environment()?,
         })
     }
 }
@@ -58,3 +138,146 @@ impl DatabaseConfig {
         Ok(DatabaseConfig { url })
     }
 }
+
+impl ApiConfig {
+    fn from_environment() -> ApiConfig {
+        let token = std::env::var("API_TOKEN")
+            .ok()
+            .filter(|token| !token.is_empty())
+            .map(SecretString::new);
+
+        ApiConfig { token }
+    }
+}
+
+impl SecretsConfig {
+    fn from_environment() -> SecretsConfig {
+        let master_key = std::env::var("SECRETS_MASTER_KEY")
+            .ok()
+            .filter(|master_key| !master_key.is_empty())
+            .map(SecretString::new);
+
+        SecretsConfig { master_key }
+    }
+}
+
+impl RunnerConfig {
+    fn from_environment() -> Result<RunnerConfig, String> {
+        let clone_image = std::env::var("CLONE_IMAGE")
+            .ok()
+            .filter(|image| !image.is_empty())
+            .unwrap_or_else(|| "cinnabar/clone".to_owned());
+        let clone_image =
+            DockerImageReference::deserialize(clone_image.as_str().into_deserializer())
+                .map_err(|err: serde::de::value::Error| format!("CLONE_IMAGE is invalid: {err}"))?;
+
+        Ok(RunnerConfig {
+            clone_image,
+            max_concurrent_pipelines: positive_integer("MAX_CONCURRENT_PIPELINES", 4)?,
+            max_concurrent_pipelines_per_repository: positive_integer(
+                "MAX_CONCURRENT_PIPELINES_PER_REPOSITORY",
+                2,
+            )?,
+        })
+    }
+}
+
+impl AgentsConfig {
+    fn from_environment() -> AgentsConfig {
+        let token = std::env::var("RUNNER_TOKEN")
+            .ok()
+            .filter(|token| !token.is_empty())
+            .map(SecretString::new);
+
+        AgentsConfig { token }
+    }
+}
+
+impl ArtifactsConfig {
+    fn from_environment() -> Result<ArtifactsConfig, String> {
+        let directory = std::env::var("ARTIFACTS_DIRECTORY")
+            .ok()
+            .filter(|directory| !directory.is_empty())
+            .unwrap_or_else(|| "/var/lib/cinnabar/artifacts".to_owned());
+        let retention_days = positive_integer("ARTIFACT_RETENTION_DAYS", 30)?;
+
+        Ok(ArtifactsConfig {
+            directory: PathBuf::from(directory),
+            retention: Duration::from_secs(retention_days as u64 * 24 * 60 * 60),
+        })
+    }
+}
+
+impl CacheConfig {
+    /// Also used by remote agents, which keep their own caches
+    pub fn from_environment() -> Result<CacheConfig, String> {
+        let directory = std::env::var("CACHE_DIRECTORY")
+            .ok()
+            .filter(|directory| !directory.is_empty())
+            .unwrap_or_else(|| "/var/lib/cinnabar/cache".to_owned());
+        let max_size_gb = positive_integer("CACHE_MAX_SIZE_GB", 10)?;
+
+        Ok(CacheConfig {
+            directory: PathBuf::from(directory),
+            max_size: max_size_gb as u64 * 1024 * 1024 * 1024,
+        })
+    }
+}
+
+impl ResourcesConfig {
+    /// Also used by remote agents, which enforce their own limits
+    pub fn from_environment() -> Result<ResourcesConfig, String> {
+        let resources = |prefix: &str| -> Result<ResourcesConfiguration, String> {
+            let resources = ResourcesConfiguration {
+                cpus: optional(&format!("{prefix}_CPUS"))?,
+                memory: optional::<MemorySize>(&format!("{prefix}_MEMORY"))?,
+                pids: optional(&format!("{prefix}_PIDS"))?,
+            };
+            resources
+                .validate()
+                .map_err(|err| format!("{prefix} resources are invalid: {err}"))?;
+
+            Ok(resources)
+        };
+
+        Ok(ResourcesConfig {
+            defaults: resources("STEP_DEFAULT")?,
+            maximums: resources("STEP_MAX")?,
+        })
+    }
+}
+
+impl RegistryConfig {
+    /// Also used by remote agents, which pull images with their own credentials
+    pub fn from_environment() -> Result<RegistryConfig, String> {
+        let credentials = match std::env::var(DOCKER_AUTH_CONFIG)
+            .ok()
+            .filter(|config| !config.is_empty())
+        {
+            Some(config) => RegistryCredentials::parse(&config)
+                .map_err(|err| format!("{DOCKER_AUTH_CONFIG} is invalid: {err}"))?,
+            None => RegistryCredentials::default(),
+        };
+
+        Ok(RegistryConfig { credentials })
+    }
+}
+
+fn optional<T: std::str::FromStr>(name: &str) -> Result<Option<T>, String> {
+    std::env::var(name)
+        .ok()
+        .filter(|value| !value.is_empty())
+        .map(|value| value.parse().map_err(|_| format!("{name} is invalid")))
+        .transpose()
+}
+
+fn positive_integer(name: &str, default: usize) -> Result<usize, String> {
+    match std::env::var(name).ok().filter(|value| !value.is_empty()) {
+        Some(value) => value
+            .parse()
+            .ok()
+            .filter(|value| *value > 0)
+            .ok_or_else(|| format!("{name} needs to be a positive integer")),
+        None => Ok(default),
+    }
+}
@@ -0,0 +1,22 @@
+use domain::repositories::Repositories;
+
+use crate::{
+    agents::Agents, artifacts::ArtifactStore, cache::CacheStore, cancellation::Cancellations,
+    config::AppConfig, log_streams::LogStreams, queue::JobQueue, scheduler::ScheduleRefresh,
+    secrets::SecretCipher,
+};
+
+#[derive(Clone)]
+pub struct Context {
+    pub config: AppConfig,
+    pub repositories: Repositories,
+    pub log_streams: LogStreams,
+    pub cancellations: Cancellations,
+    pub queue: JobQueue,
+    pub agents: Agents,
+    pub artifact_store: ArtifactStore,
+    pub cache_store: CacheStore,
+    pub schedule_refresh: ScheduleRefresh,
+    /// `None` if no master key is configured
+    pub secret_cipher: Option<SecretCipher>,
+}
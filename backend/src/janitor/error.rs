@@ -0,0 +1,13 @@
+use bollard::errors::Error as DockerError;
+use domain::repositories::RepositoryError;
+use thiserror::Error;
+
+#[derive(Debug, Error)]
+pub enum JanitorError {
+    #[error(transparent)]
+    Docker(#[from] DockerError),
+    #[error(transparent)]
+    Repository(#[from] RepositoryError),
+    #[error("{} failures while removing orphaned resources", .0.len())]
+    Incomplete(Vec<JanitorError>),
+}
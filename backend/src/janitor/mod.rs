@@ -0,0 +1,191 @@
+use std::{collections::HashMap, time::Duration};
+
+use bollard::{
+    container::{ListContainersOptions, RemoveContainerOptions},
+    errors::Error as DockerError,
+    network::ListNetworksOptions,
+    volume::ListVolumesOptions,
+    Docker,
+};
+use domain::{PipelineId, PipelineStatus};
+use tokio::time::MissedTickBehavior;
+
+use self::error::JanitorError;
+use crate::{
+    context::Context,
+    runner::labels::{BACKEND_OWNER, OWNER_LABEL, PIPELINE_LABEL},
+};
+
+pub mod error;
+
+const INTERVAL: Duration = Duration::from_secs(15 * 60);
+
+/// Periodically removes the Docker resources of pipelines that are not running anymore
+/// and the least recently used caches beyond the size limit
+pub async fn run(context: Context) {
+    let mut interval = tokio::time::interval(INTERVAL);
+    interval.set_missed_tick_behavior(MissedTickBehavior::Skip);
+
+    loop {
+        interval.tick().await;
+
+        if let Err(err) = reconcile(&context).await {
+            println!("Failed to remove orphaned resources: {err}");
+        }
+
+        match context.cache_store.evict() {
+            Ok(0) => {}
+            Ok(freed) => println!("Evicted {freed} bytes of caches"),
+            Err(err) => println!("Failed to evict caches: {err}"),
+        }
+    }
+}
+
+/// Removes the resources of pipelines run by the backend that are either not running
+/// according to the database or not running in this process, e.g. because the backend
+/// crashed while running them
+pub async fn reconcile(context: &Context) -> Result<(), JanitorError> {
+    let docker = Docker::connect_with_socket_defaults()?;
+
+    remove_orphans(&docker, BACKEND_OWNER, |pipeline_id| {
+        if !context.cancellations.is_registered(pipeline_id) {
+            return Ok(true);
+        }
+
+        let pipeline = context
+            .repositories
+            .pipelines
+            .lock()
+            .unwrap()
+            .find(pipeline_id)?;
+
+        Ok(pipeline.is_none_or(|pipeline| pipeline.status != PipelineStatus::Running))
+    })
+    .await
+}
+
+/// Removes the containers, networks and volumes of the owner whose pipeline is orphaned,
+/// along with ones that carry no valid pipeline label. A failure does not stop the sweep,
+/// every failure is logged and the returned error tells how many there were.
+///
+/// Cache volumes of earlier versions are left behind, they were named after the cached
+/// directory without any labels and can not be told apart from other volumes of the host.
+pub async fn remove_orphans(
+    docker: &Docker,
+    owner: &str,
+    is_orphaned: impl Fn(PipelineId) -> Result<bool, JanitorError>,
+) -> Result<(), JanitorError> {
+    let owner_filter = format!("{OWNER_LABEL}={owner}");
+    let filters = HashMap::from([("label", vec![owner_filter.as_str()])]);
+    let mut errors = vec![];
+    let mut is_orphaned = |labels: Option<&HashMap<String, String>>| {
+        let pipeline_id = labels
+            .and_then(|labels| labels.get(PIPELINE_LABEL))
+            .and_then(|pipeline_id| pipeline_id.parse().ok());
+
+        match pipeline_id.map(|pipeline_id| is_orphaned(PipelineId::new(pipeline_id))) {
+            Some(Ok(orphaned)) => orphaned,
+            Some(Err(err)) => {
+                println!("Failed to check whether pipeline {pipeline_id:?} is orphaned: {err}");
+                errors.push(err);
+                false
+            }
+            None => true,
+        }
+    };
+
+    // Containers go first, as networks and volumes can not be removed while in use
+    let containers = docker
+        .list_containers(Some(ListContainersOptions {
+            all: true,
+            filters: filters.clone(),
+            ..Default::default()
+        }))
+        .await;
+    let mut orphaned_containers = vec![];
+    let mut list_errors = vec![];
+
+    match containers {
+        Ok(containers) => orphaned_containers.extend(
+            containers
+                .into_iter()
+                .filter(|container| is_orphaned(container.labels.as_ref()))
+                .filter_map(|container| container.id),
+        ),
+        Err(err) => list_errors.push(("containers", err)),
+    }
+
+    let networks = docker
+        .list_networks(Some(ListNetworksOptions {
+            filters: filters.clone(),
+        }))
+        .await;
+    let mut orphaned_networks = vec![];
+
+    match networks {
+        Ok(networks) => orphaned_networks.extend(
+            networks
+                .into_iter()
+                .filter(|network| is_orphaned(network.labels.as_ref()))
+                .filter_map(|network| network.name),
+        ),
+        Err(err) => list_errors.push(("networks", err)),
+    }
+
+    let volumes = docker
+        .list_volumes(Some(ListVolumesOptions { filters }))
+        .await;
+    let mut orphaned_volumes = vec![];
+
+    match volumes {
+        Ok(volumes) => orphaned_volumes.extend(
+            volumes
+                .volumes
+                .unwrap_or_default()
+                .into_iter()
+                .filter(|volume| is_orphaned(Some(&volume.labels)))
+                .map(|volume| volume.name),
+        ),
+        Err(err) => list_errors.push(("volumes", err)),
+    }
+
+    for (resources, err) in list_errors {
+        println!("Failed to list {resources}: {err}");
+        errors.push(err.into());
+    }
+
+    let mut record = |resource: &str, result: Result<(), DockerError>| {
+        if let Err(err) = result {
+            println!("Failed to remove orphaned {resource}: {err}");
+            errors.push(err.into());
+        }
+    };
+
+    for id in orphaned_containers {
+        println!("Removing orphaned container {id}");
+        let options = RemoveContainerOptions {
+            force: true,
+            ..Default::default()
+        };
+        let result = docker.remove_container(&id, Some(options)).await;
+        record(&format!("container {id}"), result);
+    }
+
+    for name in orphaned_networks {
+        println!("Removing orphaned network {name}");
+        let result = docker.remove_network(&name).await;
+        record(&format!("network {name}"), result);
+    }
+
+    for name in orphaned_volumes {
+        println!("Removing orphaned volume {name}");
+        let result = docker.remove_volume(&name, None).await;
+        record(&format!("volume {name}"), result);
+    }
+
+    if errors.is_empty() {
+        Ok(())
+    } else {
+        Err(JanitorError::Incomplete(errors))
+    }
+}
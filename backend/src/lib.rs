@@ -0,0 +1,17 @@
+pub mod agent;
+pub mod agents;
+pub mod api;
+pub mod artifacts;
+pub mod cache;
+pub mod cancellation;
+pub mod config;
+pub mod context;
+pub mod janitor;
+pub mod log_streams;
+pub mod orchestrator;
+pub mod parser;
+pub mod queue;
+pub mod registry;
+pub mod runner;
+pub mod scheduler;
+pub mod secrets;
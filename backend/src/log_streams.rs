@@ -0,0 +1,93 @@
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+};
+
+use domain::{LogLine, PipelineId, StepId};
+use tokio::sync::broadcast;
+
+const CHANNEL_CAPACITY: usize = 1024;
+
+type Senders = HashMap<(PipelineId, StepId), broadcast::Sender<LogLine>>;
+
+/// Fans the log lines of running steps out to everyone following them live.
+///
+/// A channel exists from the moment a pipeline starts until the step finished,
+/// closing it ends the streams of all subscribers.
+#[derive(Clone, Default)]
+pub struct LogStreams {
+    senders: Arc<Mutex<Senders>>,
+}
+
+impl LogStreams {
+    pub fn open(&self, pipeline_id: PipelineId, step_id: StepId) {
+        let (sender, _) = broadcast::channel(CHANNEL_CAPACITY);
+
+        self.senders
+            .lock()
+            .unwrap()
+            .insert((pipeline_id, step_id), sender);
+    }
+
+    pub fn publish(&self, pipeline_id: PipelineId, step_id: StepId, lines: &[LogLine]) {
+        let senders = self.senders.lock().unwrap();
+
+        if let Some(sender) = senders.get(&(pipeline_id, step_id)) {
+            for line in lines {
+                // Sending only fails if nobody is listening right now
+                let _ = sender.send(line.clone());
+            }
+        }
+    }
+
+    pub fn subscribe(
+        &self,
+        pipeline_id: PipelineId,
+        step_id: StepId,
+    ) -> Option<broadcast::Receiver<LogLine>> {
+        self.senders
+            .lock()
+            .unwrap()
+            .get(&(pipeline_id, step_id))
+            .map(|sender| sender.subscribe())
+    }
+
+    pub fn close(&self, pipeline_id: PipelineId, step_id: StepId) {
+        self.senders.lock().unwrap().remove(&(pipeline_id, step_id));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::DateTime;
+    use domain::LogStream;
+
+    use super::*;
+    use tokio::sync::broadcast::error::RecvError;
+
+    fn line(number: i32) -> LogLine {
+        LogLine {
+            number,
+            timestamp: DateTime::from_timestamp(0, 0).unwrap().naive_utc(),
+            stream: LogStream::Stdout,
+            content: format!("line {number}"),
+        }
+    }
+
+    #[tokio::test]
+    async fn subscribers_should_receive_published_lines_until_closed() {
+        let log_streams = LogStreams::default();
+        let (pipeline_id, step_id) = (PipelineId::new(1), StepId::new(1));
+
+        log_streams.open(pipeline_id, step_id);
+        let mut receiver = log_streams.subscribe(pipeline_id, step_id).unwrap();
+
+        log_streams.publish(pipeline_id, step_id, &[line(1), line(2)]);
+        log_streams.close(pipeline_id, step_id);
+
+        assert_eq!(receiver.recv().await, Ok(line(1)));
+        assert_eq!(receiver.recv().await, Ok(line(2)));
+        assert_eq!(receiver.recv().await, Err(RecvError::Closed));
+        assert!(log_streams.subscribe(pipeline_id, step_id).is_none());
+    }
+}
@@ -1,17 +1,59 @@
-use api::Server;
-use config::AppConfig;
-
-mod api;
-mod config;
-mod orchestrator;
-mod parser;
-mod runner;
+use backend::{
+    agents::Agents,
+    api::Server,
+    artifacts::{self, ArtifactStore},
+    cache::CacheStore,
+    cancellation::Cancellations,
+    config::AppConfig,
+    context::Context,
+    janitor,
+    log_streams::LogStreams,
+    queue,
+    queue::JobQueue,
+    scheduler::{self, ScheduleRefresh},
+    secrets::SecretCipher,
+};
+use domain::repositories::Repositories;
 
 #[tokio::main]
 async fn main() -> Result<(), String> {
     let config = AppConfig::from_environment()?;
+    let repositories = Repositories::build(&config.database.url)
+        .map_err(|e| format!("Failed to set up database {e}"))?;
+    let secret_cipher = config
+        .secrets
+        .master_key
+        .as_ref()
+        .map(SecretCipher::new)
+        .transpose()
+        .map_err(|e| e.to_string())?;
+    let artifact_store = ArtifactStore::new(config.artifacts.directory.clone());
+    let cache_store = CacheStore::new(config.cache.directory.clone(), config.cache.max_size);
+
+    let context = Context {
+        config,
+        repositories,
+        log_streams: LogStreams::default(),
+        cancellations: Cancellations::default(),
+        queue: JobQueue::default(),
+        agents: Agents::default(),
+        artifact_store,
+        cache_store,
+        schedule_refresh: ScheduleRefresh::default(),
+        secret_cipher,
+    };
+
+    // Resources left over from before a restart would clash with the pipelines requeued
+    if let Err(err) = janitor::reconcile(&context).await {
+        println!("Failed to remove orphaned resources: {err}");
+    }
+
+    tokio::spawn(queue::run(context.clone()));
+    tokio::spawn(janitor::run(context.clone()));
+    tokio::spawn(scheduler::run(context.clone()));
+    tokio::spawn(artifacts::run_cleanup(context.clone()));
 
-    let server = Server::new(config);
+    let server = Server::new(context);
 
     server
         .start()
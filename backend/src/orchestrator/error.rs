@@ -0,0 +1,28 @@
+use domain::{ConfigurationError, repositories::RepositoryError};
+use source_control::github::error::GitHubError;
+use thiserror::Error;
+
+use crate::parser::error::ParserError;
+
+#[derive(Debug, Error)]
+pub enum TriggerError {
+    #[error(transparent)]
+    Parser(#[from] ParserError),
+    #[error(transparent)]
+    Configuration(#[from] ConfigurationError),
+    #[error(transparent)]
+    GitHub(#[from] GitHubError),
+    #[error(transparent)]
+    Repository(#[from] RepositoryError),
+}
+
+impl TriggerError {
+    /// Whether the pipelines of the repository are at fault rather than the backend
+    pub fn is_invalid_configuration(&self) -> bool {
+        match self {
+            Self::Parser(ParserError::Fetch(_)) => false,
+            Self::Parser(_) | Self::Configuration(_) => true,
+            Self::GitHub(_) | Self::Repository(_) => false,
+        }
+    }
+}
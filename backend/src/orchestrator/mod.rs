@@ -1,58 +1,91 @@
+use std::collections::BTreeMap;
+
 use bollard::Docker;
-use domain::{
-    Branch, Pipeline, PipelineConfiguration, PipelineId, PipelineStatus, Trigger, TriggerEvent,
-};
+use domain::{Pipeline, PipelineConfiguration, PipelineStatus, Trigger, TriggerEvent};
 use itertools::Itertools;
+use secrecy::ExposeSecret;
 use source_control::{
     CheckStatus, File, SourceControl, SourceControlInstallation,
     github::{GitHub, GitHubInstallation, error::GitHubError},
 };
 use tokio::task::JoinSet;
 
+use self::error::TriggerError;
 use crate::{
+    agents::protocol::Job,
+    cancellation::{Cancellation, RunGroup},
     config::AppConfig,
+    context::Context,
     parser::{error::ParserError, parse_pipeline},
+    registry::DOCKER_AUTH_CONFIG,
     runner,
+    secrets::{resolve_runner_secret, resolve_step_secrets},
 };
 
-pub async fn handle_trigger(trigger: Trigger, config: AppConfig) -> Result<(), ()> {
-    let installation = get_installation(&trigger, &config).await.map_err(|_| ())?;
+pub mod error;
 
-    let commit = extract_commit(&trigger);
-    let pipeline_files = find_pipeline_files(commit, &installation)
-        .await
-        .map_err(|_| ())?;
+pub async fn handle_trigger(trigger: Trigger, context: Context) -> Result<(), TriggerError> {
+    // The push may have changed the schedules of the pipelines
+    if let TriggerEvent::Push { .. } = trigger.event {
+        context
+            .schedule_refresh
+            .request(&trigger.repository_owner, &trigger.repository_name);
+    }
+
+    let installation = get_installation(&trigger, &context.config).await?;
+
+    let commit = trigger.event.commit();
+    let pipeline_files = find_pipeline_files(commit, &installation).await?;
 
     let parse_results = parse_pipeline_files(&trigger, &installation, pipeline_files).await;
     let (pipelines, parser_errors): (Vec<_>, Vec<_>) = parse_results.into_iter().partition_result();
 
-    if !parser_errors.is_empty() {
-        return Err(());
+    // A file that could not be read is not blamed on the configuration
+    if let Some(err) = parser_errors
+        .into_iter()
+        .find_or_first(|err| matches!(err, ParserError::Fetch(_)))
+    {
+        return Err(err.into());
     }
 
     let matched_pipelines: Vec<_> = pipelines.into_iter().flatten().collect();
+    let matched_pipelines =
+        filter_changed_paths(&trigger, &installation, matched_pipelines).await?;
+    let matched_pipelines = expand_matrices(matched_pipelines)?;
 
     for configuration in matched_pipelines {
-        tokio::spawn(process_pipeline(
-            installation.clone(),
-            commit.clone(),
-            configuration,
-        ));
+        if configuration.cancel_previous {
+            let group = RunGroup::new(&trigger, &configuration.name);
+
+            for pipeline_id in context.cancellations.cancel_group(&group) {
+                println!("Cancelling pipeline {pipeline_id}, superseded by a new run");
+            }
+        }
+
+        enqueue_pipeline(&installation, &trigger, configuration, &context).await?;
     }
 
     Ok(())
 }
 
-fn extract_commit(trigger: &Trigger) -> &String {
-    match &trigger.event {
-        TriggerEvent::Push {
-            branch: Branch { commit, .. },
-        } => commit,
-        TriggerEvent::PullRequest {
-            source: Branch { commit, .. },
-            ..
-        } => commit,
+fn expand_matrices(
+    configurations: Vec<PipelineConfiguration>,
+) -> Result<Vec<PipelineConfiguration>, TriggerError> {
+    let mut expanded = vec![];
+
+    for configuration in configurations {
+        let name = configuration.name.clone();
+
+        match configuration.expand_matrix() {
+            Ok(configurations) => expanded.extend(configurations),
+            Err(err) => {
+                println!("Failed to expand matrix of pipeline {name}: {err}");
+                return Err(err.into());
+            }
+        }
     }
+
+    Ok(expanded)
 }
 
 async fn get_installation(
@@ -70,7 +103,7 @@ async fn get_installation(
         .await
 }
 
-async fn find_pipeline_files(
+pub async fn find_pipeline_files(
     commit: &str,
     installation: &GitHubInstallation,
 ) -> Result<impl Iterator<Item = File>, GitHubError> {
@@ -82,6 +115,23 @@ async fn find_pipeline_files(
         .filter(|file| file.path.starts_with(".cinnabar/pipelines/")))
 }
 
+/// Finds the pipeline with the given name at the commit, files that fail to parse are skipped
+pub async fn find_pipeline_configuration(
+    name: &str,
+    commit: &str,
+    installation: &GitHubInstallation,
+) -> Result<Option<PipelineConfiguration>, GitHubError> {
+    for file in find_pipeline_files(commit, installation).await? {
+        if let Ok(configuration) = parse_pipeline(&file, installation).await
+            && configuration.name == name
+        {
+            return Ok(Some(configuration));
+        }
+    }
+
+    Ok(None)
+}
+
 async fn parse_pipeline_files(
     trigger: &Trigger,
     installation: &GitHubInstallation,
@@ -96,11 +146,7 @@ async fn parse_pipeline_files(
         join_set.spawn(async move {
             let configuration = parse_pipeline(&file, &installation).await?;
 
-            if configuration
-                .trigger
-                .iter()
-                .any(|trigger_configuration| trigger_configuration.matches(&trigger))
-            {
+            if configuration.is_triggered_by(&trigger) {
                 Ok::<_, ParserError>(Some(configuration))
             } else {
                 Ok(None)
@@ -118,17 +164,145 @@ async fn parse_pipeline_files(
     results
 }
 
-async fn process_pipeline(
-    installation: GitHubInstallation,
-    commit: String,
+/// Drops the pipelines whose matching triggers all have path filters that none of the changed
+/// files match. The changed files are only fetched if a matching trigger has a path filter,
+/// path filters are ignored if there are too many changed files to list.
+async fn filter_changed_paths(
+    trigger: &Trigger,
+    installation: &GitHubInstallation,
+    configurations: Vec<PipelineConfiguration>,
+) -> Result<Vec<PipelineConfiguration>, GitHubError> {
+    let needs_changed_files = configurations
+        .iter()
+        .flat_map(|configuration| &configuration.trigger)
+        .any(|trigger_configuration| {
+            trigger_configuration.matches(trigger) && trigger_configuration.path_filter().is_some()
+        });
+
+    let changed_files = match trigger.event.changed_range() {
+        Some((base, head)) if needs_changed_files => {
+            installation.list_changed_files(base, head).await?
+        }
+        _ => None,
+    };
+
+    Ok(configurations
+        .into_iter()
+        .filter(|configuration| {
+            configuration.trigger.iter().any(|trigger_configuration| {
+                trigger_configuration.matches(trigger)
+                    && trigger_configuration.matches_changed_files(changed_files.as_deref())
+            })
+        })
+        .collect())
+}
+
+/// Stores the pipeline as queued, the queue starts it once a slot is free
+async fn enqueue_pipeline(
+    installation: &GitHubInstallation,
+    trigger: &Trigger,
     configuration: PipelineConfiguration,
-) {
-    let pipeline_id = rand::random();
-    let mut pipeline = Pipeline::new(PipelineId::new(pipeline_id), configuration);
+    context: &Context,
+) -> Result<(), TriggerError> {
+    let configuration =
+        runner::add_clone_step(configuration, trigger, &context.config.runner.clone_image);
+    let pipeline = context
+        .repositories
+        .pipelines
+        .lock()
+        .unwrap()
+        .create_new(trigger, configuration)?;
+    context.cancellations.register(
+        pipeline.id,
+        RunGroup::new(trigger, &pipeline.configuration.name),
+    );
+
+    installation
+        .update_status_check(
+            trigger.event.commit(),
+            &pipeline.configuration.name,
+            pipeline.id.0,
+            CheckStatus::Pending,
+        )
+        .await?;
+
+    context.queue.notify();
+
+    Ok(())
+}
+
+/// Reports the status of a pipeline that was concluded without running to completion,
+/// its status is already stored by the queue
+pub async fn report_status(pipeline: Pipeline, status: PipelineStatus, context: Context) {
+    let trigger = &pipeline.trigger;
+    let concluded = match get_installation(trigger, &context.config).await {
+        Ok(installation) => {
+            installation
+                .update_status_check(
+                    trigger.event.commit(),
+                    &pipeline.configuration.name,
+                    pipeline.id.0,
+                    check_status(status),
+                )
+                .await
+        }
+        Err(err) => Err(err),
+    };
+
+    if let Err(err) = concluded {
+        println!("Failed to report status of pipeline {}: {err}", pipeline.id);
+    }
+}
+
+/// Runs a queued pipeline to completion, called by the queue
+pub async fn process_pipeline(mut pipeline: Pipeline, context: Context) {
+    let repositories = &context.repositories;
+    let trigger = pipeline.trigger.clone();
+    let commit = trigger.event.commit();
+    let cancellation = context.cancellations.register(
+        pipeline.id,
+        RunGroup::new(&trigger, &pipeline.configuration.name),
+    );
+
+    let installation = match get_installation(&trigger, &context.config).await {
+        Ok(installation) => installation,
+        Err(err) => {
+            println!("Pipeline {} failed to start: {err}", pipeline.id);
+            context.cancellations.unregister(pipeline.id);
+            repositories
+                .pipelines
+                .lock()
+                .unwrap()
+                .update_status(pipeline.id, PipelineStatus::Failed)
+                .unwrap();
+            return;
+        }
+    };
+
+    // Cancelled after the queue picked it up, before any of its steps started
+    if cancellation.is_cancelled() {
+        context.cancellations.unregister(pipeline.id);
+        repositories
+            .pipelines
+            .lock()
+            .unwrap()
+            .update_status(pipeline.id, PipelineStatus::Cancelled)
+            .unwrap();
+        installation
+            .update_status_check(
+                commit,
+                &pipeline.configuration.name,
+                pipeline.id.0,
+                CheckStatus::Cancelled,
+            )
+            .await
+            .unwrap();
+        return;
+    }
 
     installation
         .update_status_check(
-            &commit,
+            commit,
             &pipeline.configuration.name,
             pipeline.id.0,
             CheckStatus::Running,
@@ -136,26 +310,172 @@ async fn process_pipeline(
         .await
         .unwrap();
 
-    let docker = Docker::connect_with_socket_defaults().unwrap();
-    let mut runner = runner::PipelineRunner {
-        docker: &docker,
-        access_token: installation.get_access_token(),
-        pipeline: &mut pipeline,
+    repositories
+        .pipelines
+        .lock()
+        .unwrap()
+        .update_status(pipeline.id, PipelineStatus::Running)
+        .unwrap();
+
+    for step in &pipeline.steps {
+        context.log_streams.open(pipeline.id, step.id);
+    }
+
+    // Only needed to restore caches, so the pipeline still runs if it can not be looked up
+    let default_branch = match installation.default_branch().await {
+        Ok(default_branch) => Some(default_branch),
+        Err(err) => {
+            println!(
+                "Could not look up default branch for pipeline {}: {err}",
+                pipeline.id
+            );
+            None
+        }
     };
-    runner.run().await.unwrap();
+
+    match pipeline.configuration.runs_on.clone() {
+        Some(labels) => {
+            pipeline.status = run_remote(
+                &pipeline,
+                labels,
+                default_branch,
+                &installation,
+                &cancellation,
+                &context,
+            )
+            .await;
+        }
+        None => {
+            let host = runner::LocalHost {
+                repositories,
+                log_streams: &context.log_streams,
+                secret_cipher: context.secret_cipher.as_ref(),
+                artifact_store: &context.artifact_store,
+                registries: &context.config.registries.credentials,
+            };
+            let docker = Docker::connect_with_socket_defaults().unwrap();
+            let mut runner = runner::PipelineRunner {
+                docker: &docker,
+                access_token: installation.get_access_token(),
+                host: &host,
+                cancellation: &cancellation,
+                owner: runner::labels::BACKEND_OWNER,
+                caches: &context.cache_store,
+                resources: &context.config.resources,
+                default_branch: default_branch.as_deref(),
+                pipeline: &mut pipeline,
+            };
+
+            if let Err(err) = runner.run().await {
+                println!("Pipeline {} failed to run: {err}", pipeline.id);
+                pipeline.status = PipelineStatus::Failed;
+            }
+        }
+    }
+
+    // Steps are closed once they finished, this closes the ones that never did
+    for step in &pipeline.steps {
+        context.log_streams.close(pipeline.id, step.id);
+    }
+
+    context.cancellations.unregister(pipeline.id);
+
+    repositories
+        .pipelines
+        .lock()
+        .unwrap()
+        .update_status(pipeline.id, pipeline.status)
+        .unwrap();
 
     installation
         .update_status_check(
-            &commit,
+            commit,
             &pipeline.configuration.name,
             pipeline.id.0,
-            match pipeline.status {
-                PipelineStatus::Passed => CheckStatus::Passed,
-                PipelineStatus::Failed => CheckStatus::Failed,
-                PipelineStatus::Pending => CheckStatus::Pending,
-                PipelineStatus::Running => CheckStatus::Running,
-            },
+            check_status(pipeline.status),
         )
         .await
         .unwrap();
 }
+
+fn check_status(status: PipelineStatus) -> CheckStatus {
+    match status {
+        PipelineStatus::Passed => CheckStatus::Passed,
+        PipelineStatus::Failed => CheckStatus::Failed,
+        PipelineStatus::Pending => CheckStatus::Pending,
+        PipelineStatus::Running => CheckStatus::Running,
+        PipelineStatus::Skipped => CheckStatus::Skipped,
+        PipelineStatus::TimedOut => CheckStatus::TimedOut,
+        PipelineStatus::Cancelled => CheckStatus::Cancelled,
+    }
+}
+
+/// Hands the pipeline to an agent with all of the labels and waits for its result.
+///
+/// Secrets are resolved here, so agents never need access to the database or master key.
+async fn run_remote(
+    pipeline: &Pipeline,
+    labels: Vec<String>,
+    default_branch: Option<String>,
+    installation: &GitHubInstallation,
+    cancellation: &Cancellation,
+    context: &Context,
+) -> PipelineStatus {
+    let mut secrets = BTreeMap::new();
+
+    for step in &pipeline.steps {
+        let step_secrets = match resolve_step_secrets(
+            &context.repositories,
+            context.secret_cipher.as_ref(),
+            &pipeline.trigger,
+            &step.configuration,
+        ) {
+            Ok(step_secrets) => step_secrets,
+            Err(err) => {
+                println!("Pipeline {} failed to run: {err}", pipeline.id);
+                return PipelineStatus::Failed;
+            }
+        };
+
+        let step_secrets = step_secrets
+            .into_iter()
+            .map(|(name, value)| (name, value.expose_secret().clone()))
+            .collect();
+        secrets.insert(step.configuration.name.clone(), step_secrets);
+    }
+
+    let registry_credentials = match resolve_runner_secret(
+        &context.repositories,
+        context.secret_cipher.as_ref(),
+        &pipeline.trigger,
+        DOCKER_AUTH_CONFIG,
+    ) {
+        Ok(secret) => secret.map(|secret| secret.expose_secret().clone()),
+        Err(err) => {
+            println!("Pipeline {} failed to run: {err}", pipeline.id);
+            return PipelineStatus::Failed;
+        }
+    };
+
+    let job = Job {
+        pipeline: pipeline.clone(),
+        access_token: installation.get_access_token().expose_secret().clone(),
+        secrets,
+        default_branch,
+        registry_credentials,
+    };
+
+    match context.agents.run(job, labels, cancellation).await {
+        Ok(result) => {
+            if let Some(error) = result.error {
+                println!("Pipeline {} failed to run: {error}", pipeline.id);
+            }
+
+            result.status
+        }
+        Err(err) => {
+            println!("Pipeline {} failed to run: {err}", pipeline.id);
+            PipelineStatus::Failed
+        }
+    }
+}
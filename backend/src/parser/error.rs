@@ -1,11 +1,17 @@
+use domain::ConfigurationError;
 use thiserror::Error;
 
 #[derive(Debug, Error)]
 pub enum ParserError {
     #[error("{0}")]
     File(String),
+    /// Reading a file from the repository failed
+    #[error("{0}")]
+    Fetch(String),
     #[error("{0}")]
     Generic(String),
+    #[error(transparent)]
+    Configuration(#[from] ConfigurationError),
 }
 
 pub type Result<T> = core::result::Result<T, ParserError>;
@@ -14,7 +14,7 @@ impl PipelineParser for JsonParser {
         let content = installation
             .read_file_contents(&file.sha)
             .await
-            .map_err(|err| ParserError::File(format!("Could not read file contents: {err:?}")))?;
+            .map_err(|err| ParserError::Fetch(format!("Could not read file contents: {err:?}")))?;
 
         serde_json::from_str(&content)
             .map_err(|err| ParserError::Generic(format!("Could not parse json file: {err}")))
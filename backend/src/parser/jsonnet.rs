@@ -14,7 +14,7 @@ impl PipelineParser for JsonnetParser {
         let content = installation
             .read_file_contents(&file.sha)
             .await
-            .map_err(|err| ParserError::File(format!("Could not read file contents: {err}")))?;
+            .map_err(|err| ParserError::Fetch(format!("Could not read file contents: {err}")))?;
 
         let mut program = rsjsonnet_lang::program::Program::new();
         let (span_context, _) = program
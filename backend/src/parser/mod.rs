@@ -15,20 +15,26 @@ where
     I: SourceControlInstallation,
 {
     let file_extension = file.path.extension().unwrap_or_default();
-    match file_extension.to_str() {
+    let configuration = match file_extension.to_str() {
         Some("jsonnet") | Some("libsonnet") => {
             let parser = JsonnetParser;
-            Ok(parser.parse(file, installation).await?)
+            parser.parse(file, installation).await?
         }
         Some("json") => {
             let parser = JsonParser;
-            Ok(parser.parse(file, installation).await?)
+            parser.parse(file, installation).await?
         }
-        extension => Err(ParserError::File(format!(
-            "Unknown extension \"{}\"",
-            extension.unwrap_or_default()
-        ))),
-    }
+        extension => {
+            return Err(ParserError::File(format!(
+                "Unknown extension \"{}\"",
+                extension.unwrap_or_default()
+            )));
+        }
+    };
+
+    configuration.validate()?;
+
+    Ok(configuration)
 }
 
 trait PipelineParser {
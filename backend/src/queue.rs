@@ -0,0 +1,344 @@
+use std::{collections::HashMap, sync::Arc};
+
+use domain::{Pipeline, PipelineId, PipelineStatus};
+use futures::{stream::FuturesUnordered, StreamExt};
+use tokio::sync::Notify;
+
+use crate::{
+    cancellation::{Cancellations, RunGroup},
+    config::RunnerConfig,
+    context::Context,
+    orchestrator::{process_pipeline, report_status},
+};
+
+/// Wakes the queue up once new pipelines were queued
+#[derive(Clone, Default)]
+pub struct JobQueue {
+    notify: Arc<Notify>,
+}
+
+impl JobQueue {
+    pub fn notify(&self) {
+        self.notify.notify_one();
+    }
+}
+
+/// Starts the queued pipelines without exceeding the concurrency limits.
+///
+/// Queued pipelines are stored in the database with the status `pending`, so the
+/// ones queued or running on this host when the backend stopped are picked up after
+/// a restart and can be cancelled again. Pipelines that were running on agents fail,
+/// as their results can no longer be received.
+pub async fn run(context: Context) {
+    let requeued = context
+        .repositories
+        .pipelines
+        .lock()
+        .unwrap()
+        .requeue_interrupted();
+
+    match requeued {
+        Ok(pipeline_ids) => {
+            for pipeline_id in pipeline_ids {
+                println!("Pipeline {pipeline_id} was interrupted by a restart, queued it again");
+            }
+        }
+        Err(err) => println!("Failed to queue interrupted pipelines again: {err}"),
+    }
+
+    // Agents do not know the restarted backend anymore, so the results of these never arrive
+    let remote = context
+        .repositories
+        .pipelines
+        .lock()
+        .unwrap()
+        .list_running();
+
+    match remote {
+        Ok(pipelines) => {
+            for pipeline in pipelines {
+                println!("Pipeline {} was interrupted by a restart", pipeline.id);
+                conclude(&context, pipeline, PipelineStatus::Failed);
+            }
+        }
+        Err(err) => println!("Failed to load interrupted pipelines: {err}"),
+    }
+
+    // Registered like newly queued pipelines, so they can be cancelled while they wait
+    match context.repositories.pipelines.lock().unwrap().list_queued() {
+        Ok(queued) => {
+            for pipeline in queued {
+                context.cancellations.register(
+                    pipeline.id,
+                    RunGroup::new(&pipeline.trigger, &pipeline.configuration.name),
+                );
+            }
+        }
+        Err(err) => println!("Failed to load queued pipelines: {err}"),
+    }
+
+    let mut running = HashMap::new();
+    let mut finished = FuturesUnordered::new();
+
+    loop {
+        let queued = context.repositories.pipelines.lock().unwrap().list_queued();
+
+        match queued {
+            Ok(queued) => {
+                let selection = select_startable(
+                    queued,
+                    &running,
+                    &context.cancellations,
+                    &context.config.runner,
+                );
+
+                // Concluded before the next lookup, so they are not picked up again
+                for pipeline in selection.cancelled {
+                    conclude(&context, pipeline, PipelineStatus::Cancelled);
+                }
+
+                for pipeline in selection.startable {
+                    let pipeline_id = pipeline.id;
+                    running.insert(pipeline_id, repository(&pipeline));
+
+                    let task = tokio::spawn(process_pipeline(pipeline.clone(), context.clone()));
+                    let context = context.clone();
+                    finished.push(async move {
+                        if let Err(err) = task.await {
+                            println!("Pipeline {pipeline_id} stopped unexpectedly: {err}");
+
+                            let stored = context
+                                .repositories
+                                .pipelines
+                                .lock()
+                                .unwrap()
+                                .find(pipeline_id);
+
+                            // It may have stopped while reporting a status it already stored
+                            match stored {
+                                Ok(Some(stored)) if stored.status.is_finished() => {
+                                    context.cancellations.unregister(pipeline_id);
+                                }
+                                _ => conclude(&context, pipeline, PipelineStatus::Failed),
+                            }
+                        }
+
+                        pipeline_id
+                    });
+                }
+            }
+            Err(err) => println!("Failed to load queued pipelines: {err}"),
+        }
+
+        tokio::select! {
+            _ = context.queue.notify.notified() => {}
+            Some(pipeline_id) = finished.next(), if !finished.is_empty() => {
+                running.remove(&pipeline_id);
+            }
+        }
+    }
+}
+
+/// Concludes a pipeline that will not run to completion, reporting the status in the background
+fn conclude(context: &Context, pipeline: Pipeline, status: PipelineStatus) {
+    context.cancellations.unregister(pipeline.id);
+
+    for step in &pipeline.steps {
+        context.log_streams.close(pipeline.id, step.id);
+    }
+
+    let concluded = context
+        .repositories
+        .pipelines
+        .lock()
+        .unwrap()
+        .update_status(pipeline.id, status);
+
+    match concluded {
+        Ok(()) => {
+            tokio::spawn(report_status(pipeline, status, context.clone()));
+        }
+        Err(err) => println!("Failed to conclude pipeline {}: {err}", pipeline.id),
+    }
+}
+
+fn repository(pipeline: &Pipeline) -> String {
+    format!(
+        "{}/{}",
+        pipeline.trigger.repository_owner, pipeline.trigger.repository_name
+    )
+}
+
+struct Selection {
+    startable: Vec<Pipeline>,
+    /// Cancelled while they were queued, these are concluded without being started
+    cancelled: Vec<Pipeline>,
+}
+
+/// Picks the queued pipelines to start, oldest first, so that neither the global
+/// nor the per repository limit is exceeded
+fn select_startable(
+    queued: Vec<Pipeline>,
+    running: &HashMap<PipelineId, String>,
+    cancellations: &Cancellations,
+    config: &RunnerConfig,
+) -> Selection {
+    let mut total = running.len();
+    let mut per_repository = HashMap::<String, usize>::new();
+
+    for repository in running.values() {
+        *per_repository.entry(repository.clone()).or_default() += 1;
+    }
+
+    let mut startable = vec![];
+    let mut cancelled = vec![];
+
+    for pipeline in queued {
+        // Started pipelines stay pending until they picked up their installation
+        if running.contains_key(&pipeline.id) {
+            continue;
+        }
+
+        if cancellations.is_cancelled(pipeline.id) {
+            cancelled.push(pipeline);
+            continue;
+        }
+
+        if total >= config.max_concurrent_pipelines {
+            continue;
+        }
+
+        let count = per_repository.entry(repository(&pipeline)).or_default();
+
+        if *count < config.max_concurrent_pipelines_per_repository {
+            *count += 1;
+            total += 1;
+            startable.push(pipeline);
+        }
+    }
+
+    Selection {
+        startable,
+        cancelled,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::DateTime;
+    use domain::{Branch, DockerImageReference, PipelineConfiguration, Trigger, TriggerEvent};
+
+    use super::*;
+
+    fn pipeline(id: i32, repository: &str) -> Pipeline {
+        let trigger = Trigger {
+            repository_owner: "Owner".to_owned(),
+            repository_name: repository.to_owned(),
+            installation_id: 1,
+            event: TriggerEvent::Push {
+                branch: Branch {
+                    name: "main".to_owned(),
+                    commit: "abc123".to_owned(),
+                },
+                before: None,
+            },
+        };
+        let configuration = PipelineConfiguration {
+            name: "Build".to_owned(),
+            trigger: vec![],
+            clone: None,
+            timeout: None,
+            cancel_previous: false,
+            runs_on: None,
+            matrix: None,
+            services: None,
+            environment: None,
+            steps: vec![],
+        };
+
+        Pipeline::new(
+            PipelineId(id),
+            trigger,
+            configuration,
+            DateTime::UNIX_EPOCH.naive_utc(),
+        )
+    }
+
+    fn config(total: usize, per_repository: usize) -> RunnerConfig {
+        RunnerConfig {
+            clone_image: DockerImageReference {
+                hostname: None,
+                repository: "cinnabar/clone".to_owned(),
+                tag: None,
+            },
+            max_concurrent_pipelines: total,
+            max_concurrent_pipelines_per_repository: per_repository,
+        }
+    }
+
+    fn ids(pipelines: &[Pipeline]) -> Vec<i32> {
+        pipelines.iter().map(|pipeline| pipeline.id.0).collect()
+    }
+
+    #[test]
+    fn select_startable_should_respect_global_limit() {
+        let queued = vec![pipeline(1, "a"), pipeline(2, "b"), pipeline(3, "c")];
+        let running = HashMap::from([(PipelineId(10), "Owner/d".to_owned())]);
+
+        let selection =
+            select_startable(queued, &running, &Cancellations::default(), &config(3, 5));
+
+        assert_eq!(ids(&selection.startable), vec![1, 2]);
+    }
+
+    #[test]
+    fn select_startable_should_respect_repository_limit() {
+        let queued = vec![pipeline(1, "a"), pipeline(2, "a"), pipeline(3, "b")];
+        let running = HashMap::from([(PipelineId(10), "Owner/a".to_owned())]);
+
+        let selection =
+            select_startable(queued, &running, &Cancellations::default(), &config(5, 2));
+
+        assert_eq!(ids(&selection.startable), vec![1, 3]);
+    }
+
+    #[test]
+    fn select_startable_should_skip_pipelines_already_started() {
+        let queued = vec![pipeline(1, "a"), pipeline(2, "a")];
+        let running = HashMap::from([(PipelineId(1), "Owner/a".to_owned())]);
+
+        let selection =
+            select_startable(queued, &running, &Cancellations::default(), &config(5, 5));
+
+        assert_eq!(ids(&selection.startable), vec![2]);
+    }
+
+    #[test]
+    fn select_startable_should_never_start_cancelled_pipelines() {
+        let queued = vec![pipeline(1, "a"), pipeline(2, "a"), pipeline(3, "b")];
+        let running = HashMap::from([(PipelineId(10), "Owner/c".to_owned())]);
+        let cancellations = Cancellations::default();
+
+        for pipeline in &queued {
+            cancellations.register(
+                pipeline.id,
+                RunGroup::new(&pipeline.trigger, &pipeline.configuration.name),
+            );
+        }
+
+        cancellations.cancel(PipelineId(1));
+        cancellations.cancel(PipelineId(3));
+
+        // Also concluded while the limit keeps the other pipelines waiting
+        let selection = select_startable(queued, &running, &cancellations, &config(1, 5));
+
+        assert_eq!(ids(&selection.startable), Vec::<i32>::new());
+        assert_eq!(ids(&selection.cancelled), vec![1, 3]);
+
+        let queued = vec![pipeline(1, "a"), pipeline(2, "a"), pipeline(3, "b")];
+        let selection = select_startable(queued, &HashMap::new(), &cancellations, &config(5, 5));
+
+        assert_eq!(ids(&selection.startable), vec![2]);
+        assert_eq!(ids(&selection.cancelled), vec![1, 3]);
+    }
+}
@@ -0,0 +1,202 @@
+use std::collections::BTreeMap;
+
+use base64::{engine::general_purpose::STANDARD, Engine};
+use bollard::auth::DockerCredentials;
+use domain::DockerImageReference;
+use secrecy::{ExposeSecret, SecretString};
+use serde::Deserialize;
+
+/// Name of the environment variable and of the secret holding registry credentials
+pub const DOCKER_AUTH_CONFIG: &str = "DOCKER_AUTH_CONFIG";
+
+const DOCKER_HUB: &str = "docker.io";
+const DOCKER_HUB_ADDRESS: &str = "https://index.docker.io/v1/";
+
+/// Credentials images are pulled with, by registry hostname
+#[derive(Clone, Default)]
+pub struct RegistryCredentials {
+    logins: BTreeMap<String, Login>,
+}
+
+#[derive(Clone)]
+struct Login {
+    username: String,
+    password: SecretString,
+}
+
+/// The `auths` of a Docker `config.json`, as written by `docker login`
+#[derive(Deserialize)]
+struct DockerConfig {
+    auths: BTreeMap<String, DockerAuth>,
+}
+
+#[derive(Deserialize)]
+struct DockerAuth {
+    /// `<username>:<password>` encoded as base64
+    auth: Option<String>,
+    username: Option<String>,
+    password: Option<String>,
+}
+
+impl RegistryCredentials {
+    /// Parses a Docker `config.json`, e.g. `{"auths": {"ghcr.io": {"auth": "<base64>"}}}`.
+    ///
+    /// Entries may also set `username` and `password` instead of `auth`. Docker Hub can be
+    /// written as `docker.io`, `index.docker.io` or `https://index.docker.io/v1/`.
+    pub fn parse(config: &str) -> Result<Self, String> {
+        let config: DockerConfig = serde_json::from_str(config)
+            .map_err(|err| format!("expected a Docker config.json: {err}"))?;
+
+        let logins = config
+            .auths
+            .into_iter()
+            .map(|(server, auth)| {
+                let login = match auth {
+                    DockerAuth {
+                        username: Some(username),
+                        password: Some(password),
+                        ..
+                    } => Login {
+                        username,
+                        password: SecretString::new(password),
+                    },
+                    DockerAuth {
+                        auth: Some(auth), ..
+                    } => {
+                        let auth = STANDARD
+                            .decode(auth.trim())
+                            .ok()
+                            .and_then(|auth| String::from_utf8(auth).ok())
+                            .ok_or_else(|| format!("auth of \"{server}\" is not valid base64"))?;
+                        let (username, password) = auth.split_once(':').ok_or_else(|| {
+                            format!("auth of \"{server}\" is not <username>:<password>")
+                        })?;
+
+                        Login {
+                            username: username.to_owned(),
+                            password: SecretString::new(password.to_owned()),
+                        }
+                    }
+                    _ => return Err(format!("\"{server}\" has neither auth nor a password")),
+                };
+
+                Ok((normalize_hostname(&server), login))
+            })
+            .collect::<Result<_, String>>()?;
+
+        Ok(Self { logins })
+    }
+
+    /// Adds the credentials of the other, replacing the ones of the same hostnames
+    pub fn merge(mut self, other: RegistryCredentials) -> Self {
+        self.logins.extend(other.logins);
+        self
+    }
+
+    /// Returns the credentials of the registry of the image, images without hostname
+    /// are pulled from Docker Hub
+    pub fn for_image(&self, image: &DockerImageReference) -> Option<DockerCredentials> {
+        let hostname = image
+            .hostname
+            .as_deref()
+            .map_or_else(|| DOCKER_HUB.to_owned(), normalize_hostname);
+        let login = self.logins.get(&hostname)?;
+        let serveraddress = if hostname == DOCKER_HUB {
+            DOCKER_HUB_ADDRESS.to_owned()
+        } else {
+            hostname
+        };
+
+        Some(DockerCredentials {
+            username: Some(login.username.clone()),
+            password: Some(login.password.expose_secret().clone()),
+            serveraddress: Some(serveraddress),
+            ..Default::default()
+        })
+    }
+}
+
+fn normalize_hostname(server: &str) -> String {
+    let server = server
+        .trim_start_matches("https://")
+        .trim_start_matches("http://");
+    let hostname = server.split('/').next().unwrap_or_default();
+
+    match hostname.to_ascii_lowercase().as_str() {
+        "index.docker.io" | "registry-1.docker.io" | DOCKER_HUB => DOCKER_HUB.to_owned(),
+        hostname => hostname.to_owned(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde::{de::IntoDeserializer, Deserialize};
+
+    use super::*;
+
+    fn image(reference: &str) -> DockerImageReference {
+        DockerImageReference::deserialize(reference.into_deserializer())
+            .map_err(|err: serde::de::value::Error| err)
+            .unwrap()
+    }
+
+    #[test]
+    fn for_image_should_return_credentials_of_registry() {
+        let credentials = RegistryCredentials::parse(
+            r#"{
+                "auths": {
+                    "https://index.docker.io/v1/": { "auth": "aHViOnNlY3JldA==" },
+                    "ghcr.io": { "username": "octocat", "password": "token" },
+                    "localhost:5000": { "auth": "dGVzdDp0ZXN0" }
+                }
+            }"#,
+        )
+        .unwrap();
+
+        let hub = credentials.for_image(&image("rust:1.80")).unwrap();
+        assert_eq!(hub.username.as_deref(), Some("hub"));
+        assert_eq!(hub.password.as_deref(), Some("secret"));
+        assert_eq!(hub.serveraddress.as_deref(), Some(DOCKER_HUB_ADDRESS));
+        assert!(credentials.for_image(&image("docker.io/rust")).is_some());
+
+        let ghcr = credentials
+            .for_image(&image("ghcr.io/octocat/app"))
+            .unwrap();
+        assert_eq!(ghcr.username.as_deref(), Some("octocat"));
+        assert_eq!(ghcr.serveraddress.as_deref(), Some("ghcr.io"));
+
+        let local = credentials
+            .for_image(&image("localhost:5000/app:v1"))
+            .unwrap();
+        assert_eq!(local.username.as_deref(), Some("test"));
+        assert_eq!(local.serveraddress.as_deref(), Some("localhost:5000"));
+
+        assert!(credentials.for_image(&image("quay.io/app")).is_none());
+    }
+
+    #[test]
+    fn merge_should_prefer_credentials_of_other() {
+        let server =
+            RegistryCredentials::parse(r#"{"auths": {"ghcr.io": {"auth": "YTpi"}}}"#).unwrap();
+        let repository =
+            RegistryCredentials::parse(r#"{"auths": {"ghcr.io": {"auth": "Yzpk"}}}"#).unwrap();
+
+        let credentials = server.merge(repository);
+
+        assert_eq!(
+            credentials
+                .for_image(&image("ghcr.io/app"))
+                .unwrap()
+                .username
+                .as_deref(),
+            Some("c")
+        );
+    }
+
+    #[test]
+    fn parse_should_reject_invalid_auth() {
+        assert!(RegistryCredentials::parse(r#"{"auths": {"ghcr.io": {"auth": "%%"}}}"#).is_err());
+        assert!(RegistryCredentials::parse(r#"{"auths": {"ghcr.io": {}}}"#).is_err());
+        assert!(RegistryCredentials::parse("not json").is_err());
+    }
+}
@@ -0,0 +1,161 @@
+use domain::{
+    CloneOptions, DockerImageReference, PipelineConfiguration, StepConfiguration, Trigger,
+    CLONE_STEP_NAME,
+};
+
+/// Adds the step checking out the triggering commit into the workspace volume,
+/// unless the pipeline disabled it with `clone: false`
+pub fn add_clone_step(
+    configuration: PipelineConfiguration,
+    trigger: &Trigger,
+    image: &DockerImageReference,
+) -> PipelineConfiguration {
+    let Some(options) = configuration.clone_options() else {
+        return configuration;
+    };
+
+    let step = StepConfiguration {
+        name: CLONE_STEP_NAME.to_owned(),
+        image: image.clone(),
+        commands: Some(clone_commands(trigger, &options)),
+        cache: None,
+        depends_on: None,
+        environment: None,
+        secrets: None,
+        timeout: None,
+        artifacts: None,
+        resources: None,
+    };
+
+    configuration.with_clone_step(step)
+}
+
+fn clone_commands(trigger: &Trigger, options: &CloneOptions) -> Vec<String> {
+    let depth = match options.depth.unwrap_or(1) {
+        0 => String::new(),
+        depth => format!(" --depth {depth}"),
+    };
+
+    let mut commands = vec![
+        "git init -q".to_owned(),
+        format!(
+            "git remote add origin https://github.com/{}/{}.git",
+            trigger.repository_owner, trigger.repository_name
+        ),
+        format!("git fetch{depth} origin {}", trigger.event.commit()),
+        "git checkout -q FETCH_HEAD".to_owned(),
+    ];
+
+    if options.submodules {
+        commands.push(format!("git submodule update --init --recursive{depth}"));
+    }
+
+    if options.lfs {
+        commands.push("git lfs install --local".to_owned());
+        commands.push("git lfs pull".to_owned());
+    }
+
+    commands
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use domain::{Branch, CloneConfiguration, TriggerEvent};
+
+    fn trigger() -> Trigger {
+        Trigger {
+            repository_owner: "Owner".to_owned(),
+            repository_name: "Repo".to_owned(),
+            installation_id: 1,
+            event: TriggerEvent::PullRequest {
+                number: 1,
+                source: Branch {
+                    name: "feature".to_owned(),
+                    commit: "abc123".to_owned(),
+                },
+                target: Branch {
+                    name: "main".to_owned(),
+                    commit: "def456".to_owned(),
+                },
+                from_fork: false,
+            },
+        }
+    }
+
+    fn configuration(clone: Option<CloneConfiguration>) -> PipelineConfiguration {
+        PipelineConfiguration {
+            name: "Build".to_owned(),
+            trigger: vec![],
+            clone,
+            timeout: None,
+            cancel_previous: false,
+            runs_on: None,
+            matrix: None,
+            services: None,
+            environment: None,
+            steps: vec![],
+        }
+    }
+
+    fn image() -> DockerImageReference {
+        DockerImageReference {
+            hostname: None,
+            repository: "cinnabar/clone".to_owned(),
+            tag: None,
+        }
+    }
+
+    #[test]
+    fn add_clone_step_should_fetch_pull_request_head_commit() {
+        let configuration = add_clone_step(configuration(None), &trigger(), &image());
+
+        assert_eq!(configuration.steps.len(), 1);
+        assert_eq!(configuration.steps[0].name, "clone");
+        assert_eq!(
+            configuration.steps[0].commands,
+            Some(vec![
+                "git init -q".to_owned(),
+                "git remote add origin https://github.com/Owner/Repo.git".to_owned(),
+                "git fetch --depth 1 origin abc123".to_owned(),
+                "git checkout -q FETCH_HEAD".to_owned(),
+            ])
+        );
+    }
+
+    #[test]
+    fn add_clone_step_should_apply_clone_options() {
+        let options = CloneOptions {
+            depth: Some(0),
+            submodules: true,
+            lfs: true,
+        };
+        let configuration = add_clone_step(
+            configuration(Some(CloneConfiguration::Options(options))),
+            &trigger(),
+            &image(),
+        );
+
+        assert_eq!(
+            configuration.steps[0].commands.as_deref().unwrap()[2..],
+            [
+                "git fetch origin abc123",
+                "git checkout -q FETCH_HEAD",
+                "git submodule update --init --recursive",
+                "git lfs install --local",
+                "git lfs pull",
+            ]
+        );
+    }
+
+    #[test]
+    fn add_clone_step_should_respect_opt_out() {
+        let configuration = add_clone_step(
+            configuration(Some(CloneConfiguration::Enabled(false))),
+            &trigger(),
+            &image(),
+        );
+
+        assert!(configuration.steps.is_empty());
+    }
+}
@@ -1,18 +1,32 @@
-use std::path::Path;
+use std::{
+    collections::{BTreeMap, HashMap},
+    io::Write,
+    path::Path,
+    time::{Duration, Instant},
+};
 
+use super::environment::step_environment;
 use super::error::RunnerError as Error;
+use super::labels::ResourceLabels;
+use super::logs::LogCollector;
+use super::network::Network;
 use super::volume::Volume;
-use domain::{Pipeline, Step};
+use domain::{LogLine, Pipeline, ResourcesConfiguration, ServiceConfiguration, Step};
 
 use bollard::{
-    container::{Config, CreateContainerOptions, LogOutput, LogsOptions},
+    container::{
+        Config, CreateContainerOptions, DownloadFromContainerOptions, LogsOptions,
+        NetworkingConfig, RemoveContainerOptions, StopContainerOptions, UploadToContainerOptions,
+    },
     errors::Error::DockerContainerWaitError,
-    secret::{ContainerWaitResponse, HostConfig},
+    secret::{ContainerWaitResponse, EndpointSettings, HealthConfig, HealthStatusEnum, HostConfig},
     Docker,
 };
 use futures::TryStreamExt;
 use secrecy::{ExposeSecret, SecretString};
 
+const WORKSPACE_DIRECTORY: &str = "/ci/src";
+
 pub struct Container<'a> {
     pub name: String,
     docker: &'a Docker,
@@ -31,12 +45,17 @@ impl ContainerExitCode {
 }
 
 impl<'a> Container<'a> {
+    #[allow(clippy::too_many_arguments)]
     pub async fn create(
         docker: &'a Docker,
         pipeline: &Pipeline,
         step: &Step,
         volume: &Volume<'a>,
+        network: Option<&Network<'a>>,
+        labels: &ResourceLabels,
+        resources: &ResourcesConfiguration,
         access_token: &SecretString,
+        secrets: &BTreeMap<String, SecretString>,
     ) -> Result<Self, Error> {
         let commands = step
             .configuration
@@ -45,32 +64,19 @@ impl<'a> Container<'a> {
             .map(|commands| commands.join("; "));
 
         let entrypoint = include_str!("./entrypoint.sh");
-        let workspace_directory = "/ci/src";
 
-        let workspace_bind = format!("{}:{}", volume.name, workspace_directory);
-        let cache_binds = step
-            .configuration
-            .cache
-            .as_ref()
-            .map(|cache| {
-                cache
-                    .iter()
-                    .map(|dir| {
-                        format!(
-                            "{}:{}",
-                            dir,
-                            Path::new(workspace_directory).join(dir).to_str().unwrap()
-                        )
-                    })
-                    .collect::<Vec<_>>()
-            })
-            .unwrap_or_default();
+        let binds = Some(vec![format!("{}:{}", volume.name, WORKSPACE_DIRECTORY)]);
+        let memory = resources.memory.map(|memory| memory.0 as i64);
 
-        let binds = [workspace_bind]
+        let environment = step_environment(pipeline, step)
             .into_iter()
-            .chain(cache_binds.into_iter())
+            .map(|(name, value)| format!("{name}={value}"))
+            .chain(
+                secrets
+                    .iter()
+                    .map(|(name, value)| format!("{name}={}", value.expose_secret())),
+            )
             .collect::<Vec<_>>();
-        let binds = Some(binds);
 
         let container = docker
             .create_container(
@@ -80,17 +86,25 @@ impl<'a> Container<'a> {
                 }),
                 Config {
                     image: Some(step.configuration.image.to_string().as_str()),
-                    working_dir: Some(workspace_directory),
-                    tty: Some(true),
-                    env: Some(vec![
-                        format!(
-                            "NETRC_CONTENT=machine github.com login x-oauth-token password {}",
-                            access_token.expose_secret()
-                        )
-                        .as_str(),
-                        format!("SCRIPT={}", entrypoint).as_str(),
-                        format!("COMMANDS={}", commands.unwrap_or_default()).as_str(),
-                    ]),
+                    working_dir: Some(WORKSPACE_DIRECTORY),
+                    tty: Some(false),
+                    labels: Some(labels.to_map()),
+                    // The variables the entrypoint relies on come last so they can not be overridden
+                    env: Some(
+                        environment
+                            .iter()
+                            .map(String::as_str)
+                            .chain([
+                                format!(
+                                    "NETRC_CONTENT=machine github.com login x-oauth-token password {}",
+                                    access_token.expose_secret()
+                                )
+                                .as_str(),
+                                format!("SCRIPT={}", entrypoint).as_str(),
+                                format!("COMMANDS={}", commands.unwrap_or_default()).as_str(),
+                            ])
+                            .collect(),
+                    ),
                     entrypoint: Some(vec![
                         "/bin/sh",
                         "-c",
@@ -98,6 +112,12 @@ impl<'a> Container<'a> {
                     ]),
                     host_config: Some(HostConfig {
                         binds,
+                        network_mode: network.map(|network| network.name.clone()),
+                        nano_cpus: resources.cpus.map(|cpus| (cpus * 1e9) as i64),
+                        memory,
+                        // Equal to the memory limit, so the container can not swap instead
+                        memory_swap: memory,
+                        pids_limit: resources.pids.map(i64::from),
                         ..Default::default()
                     }),
                     ..Default::default()
@@ -112,11 +132,154 @@ impl<'a> Container<'a> {
         Ok(container)
     }
 
-    pub async fn run(&self) -> Result<ContainerExitCode, Error> {
+    /// Creates and starts the container of a service, reachable by its name on the network
+    pub async fn start_service(
+        docker: &'a Docker,
+        pipeline: &Pipeline,
+        service: &ServiceConfiguration,
+        network: &Network<'a>,
+        labels: &ResourceLabels,
+    ) -> Result<Self, Error> {
+        let environment = service
+            .environment
+            .iter()
+            .flatten()
+            .map(|(name, value)| format!("{name}={value}"))
+            .collect::<Vec<_>>();
+
+        let healthcheck = service.health_check.as_ref().map(|health_check| {
+            let nanoseconds = |duration: Duration| duration.as_nanos() as i64;
+
+            HealthConfig {
+                test: Some(vec!["CMD-SHELL".to_owned(), health_check.command.clone()]),
+                interval: Some(nanoseconds(health_check.interval())),
+                timeout: Some(nanoseconds(health_check.timeout())),
+                retries: Some(health_check.retries().into()),
+                ..Default::default()
+            }
+        });
+
+        let endpoint = EndpointSettings {
+            aliases: Some(vec![service.name.clone()]),
+            ..Default::default()
+        };
+
+        let container = docker
+            .create_container(
+                Some(CreateContainerOptions {
+                    name: format!("pipeline-{}-service-{}", pipeline.id, service.name),
+                    platform: None,
+                }),
+                Config {
+                    image: Some(service.image.to_string()),
+                    env: Some(environment),
+                    cmd: service.command.clone(),
+                    healthcheck,
+                    labels: Some(
+                        labels
+                            .to_map()
+                            .into_iter()
+                            .map(|(name, value)| (name.to_owned(), value.to_owned()))
+                            .collect(),
+                    ),
+                    host_config: Some(HostConfig {
+                        network_mode: Some(network.name.clone()),
+                        ..Default::default()
+                    }),
+                    networking_config: Some(NetworkingConfig {
+                        endpoints_config: HashMap::from([(network.name.clone(), endpoint)]),
+                    }),
+                    ..Default::default()
+                },
+            )
+            .await
+            .map(|result| Self {
+                name: result.id,
+                docker,
+            })?;
+
+        docker
+            .start_container::<String>(&container.name, None)
+            .await?;
+
+        Ok(container)
+    }
+
+    /// Waits until the health check of the container passed, or until it is running
+    /// if it has none. Fails if the check is still starting once the limit passed.
+    pub async fn wait_until_healthy(&self, service: &str, limit: Duration) -> Result<(), Error> {
+        let deadline = Instant::now() + limit;
+
+        loop {
+            let state = self
+                .docker
+                .inspect_container(&self.name, None)
+                .await?
+                .state
+                .unwrap_or_default();
+
+            if !state.running.unwrap_or(false) {
+                return Err(Error::Generic(format!(
+                    "Service \"{service}\" exited with code {}",
+                    state.exit_code.unwrap_or_default()
+                )));
+            }
+
+            match state.health.and_then(|health| health.status) {
+                Some(HealthStatusEnum::STARTING) if Instant::now() >= deadline => {
+                    return Err(Error::Generic(format!(
+                        "Service \"{service}\" did not become healthy within {} seconds",
+                        limit.as_secs()
+                    )));
+                }
+                Some(HealthStatusEnum::STARTING) => {}
+                Some(HealthStatusEnum::UNHEALTHY) => {
+                    return Err(Error::Generic(format!(
+                        "Service \"{service}\" is unhealthy"
+                    )));
+                }
+                _ => return Ok(()),
+            }
+
+            tokio::time::sleep(Duration::from_secs(1)).await;
+        }
+    }
+
+    pub async fn run(
+        &self,
+        mut on_logs: impl FnMut(Vec<LogLine>) -> Result<(), Error>,
+    ) -> Result<ContainerExitCode, Error> {
         self.docker
             .start_container::<String>(&self.name, None)
             .await?;
 
+        let mut logs = self.docker.logs(
+            &self.name,
+            Some(LogsOptions::<&str> {
+                follow: true,
+                timestamps: true,
+                stdout: true,
+                stderr: true,
+                ..Default::default()
+            }),
+        );
+
+        let mut collector = LogCollector::new();
+
+        while let Some(output) = logs.try_next().await? {
+            let lines = collector.push(output);
+
+            if !lines.is_empty() {
+                on_logs(lines)?;
+            }
+        }
+
+        let lines = collector.finish();
+
+        if !lines.is_empty() {
+            on_logs(lines)?;
+        }
+
         let result = self
             .docker
             .wait_container::<String>(&self.name, None)
@@ -133,48 +296,117 @@ impl<'a> Container<'a> {
             }
         };
 
-        let logs = self
+        Ok(exit_code)
+    }
+
+    /// Returns the output the container has written so far
+    pub async fn logs(&self) -> Result<Vec<LogLine>, Error> {
+        let mut logs = self.docker.logs(
+            &self.name,
+            Some(LogsOptions::<&str> {
+                timestamps: true,
+                stdout: true,
+                stderr: true,
+                ..Default::default()
+            }),
+        );
+
+        let mut collector = LogCollector::new();
+        let mut lines = vec![];
+
+        while let Some(output) = logs.try_next().await? {
+            lines.extend(collector.push(output));
+        }
+
+        lines.extend(collector.finish());
+
+        Ok(lines)
+    }
+
+    /// Whether the kernel killed a process of the container for exceeding its memory limit
+    pub async fn was_oom_killed(&self) -> Result<bool, Error> {
+        let state = self.docker.inspect_container(&self.name, None).await?.state;
+
+        Ok(state.and_then(|state| state.oom_killed).unwrap_or_default())
+    }
+
+    /// Stops the container, killing it if it does not exit within a few seconds
+    pub async fn stop(&self) -> Result<(), Error> {
+        Ok(self
             .docker
-            .logs(
-                &self.name,
-                Some(LogsOptions::<&str> {
-                    timestamps: true,
-                    stdout: true,
-                    stderr: true,
-                    ..Default::default()
-                }),
-            )
-            .try_collect::<Vec<_>>()
-            .await?;
+            .stop_container(&self.name, Some(StopContainerOptions { t: 10 }))
+            .await?)
+    }
 
-        let mut logs: Vec<_> = logs
-            .into_iter()
-            .map(|log| match log {
-                LogOutput::Console { message } => message,
-                LogOutput::StdOut { message } => message,
-                LogOutput::StdErr { message } => message,
-                LogOutput::StdIn { message } => message,
-            })
-            .map(|message| {
-                let mut iter = message.splitn(2, |char| *char == b' ');
-                let timestamp = iter.next().unwrap();
-                let message = iter.next().unwrap();
-                (
-                    String::from_utf8_lossy(timestamp).to_string(),
-                    String::from_utf8_lossy(message).to_string(),
-                )
-            })
-            .collect();
-        logs.sort_by_key(|(t, _m)| t.to_string());
+    /// Writes a tar archive of the path, relative to the workspace, to the file
+    pub async fn download_to(&self, path: &str, file: &Path) -> Result<(), Error> {
+        let options = DownloadFromContainerOptions {
+            path: Path::new(WORKSPACE_DIRECTORY)
+                .join(path)
+                .to_string_lossy()
+                .into_owned(),
+        };
+
+        let mut file = std::fs::File::create(file).map_err(Error::Artifact)?;
+        let mut archive = self
+            .docker
+            .download_from_container(&self.name, Some(options));
 
-        for log in logs {
-            print!("{}", log.1)
+        while let Some(chunk) = archive.try_next().await? {
+            file.write_all(&chunk).map_err(Error::Artifact)?;
         }
 
-        Ok(exit_code)
+        Ok(())
+    }
+
+    /// Returns a tar archive of the path, relative to the workspace
+    pub async fn download(&self, path: &str) -> Result<Vec<u8>, Error> {
+        let options = DownloadFromContainerOptions {
+            path: Path::new(WORKSPACE_DIRECTORY)
+                .join(path)
+                .to_string_lossy()
+                .into_owned(),
+        };
+
+        let archive = self
+            .docker
+            .download_from_container(&self.name, Some(options))
+            .try_fold(Vec::new(), |mut archive, chunk| async move {
+                archive.extend_from_slice(&chunk);
+                Ok(archive)
+            })
+            .await?;
+
+        Ok(archive)
+    }
+
+    /// Extracts the tar archive into the workspace
+    pub async fn upload(&self, archive: Vec<u8>) -> Result<(), Error> {
+        let options = UploadToContainerOptions {
+            path: WORKSPACE_DIRECTORY,
+            ..Default::default()
+        };
+
+        Ok(self
+            .docker
+            .upload_to_container(&self.name, Some(options), archive.into())
+            .await?)
     }
 
     pub async fn remove(&self) -> Result<(), Error> {
         Ok(self.docker.remove_container(&self.name, None).await?)
     }
+
+    /// Removes the container, killing it first if it is still running
+    pub async fn remove_forced(&self) -> Result<(), Error> {
+        let options = RemoveContainerOptions {
+            force: true,
+            ..Default::default()
+        };
+
+        Ok(self
+            .docker
+            .remove_container(&self.name, Some(options))
+            .await?)
+    }
 }
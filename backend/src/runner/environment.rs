@@ -0,0 +1,154 @@
+use std::collections::BTreeMap;
+
+use domain::{Pipeline, Step, TriggerEvent};
+
+/// Builds the environment variables of a step container.
+///
+/// Built-in `CI`/`CINNABAR_*` variables and `INPUT_*` variables of manual runs come first,
+/// then the pipeline's `environment`, which is overridden by the step's `environment`.
+pub fn step_environment(pipeline: &Pipeline, step: &Step) -> BTreeMap<String, String> {
+    let trigger = &pipeline.trigger;
+    let mut environment = BTreeMap::from([
+        ("CI".to_owned(), "true".to_owned()),
+        ("CINNABAR".to_owned(), "true".to_owned()),
+        ("CINNABAR_PIPELINE_ID".to_owned(), pipeline.id.to_string()),
+        (
+            "CINNABAR_PIPELINE_NAME".to_owned(),
+            pipeline.configuration.name.clone(),
+        ),
+        (
+            "CINNABAR_STEP_NAME".to_owned(),
+            step.configuration.name.clone(),
+        ),
+        ("CINNABAR_EVENT".to_owned(), trigger.event.name().to_owned()),
+        (
+            "CINNABAR_REPO".to_owned(),
+            format!("{}/{}", trigger.repository_owner, trigger.repository_name),
+        ),
+        (
+            "CINNABAR_COMMIT_SHA".to_owned(),
+            trigger.event.commit().to_owned(),
+        ),
+    ]);
+
+    match &trigger.event {
+        TriggerEvent::PullRequest { source, target, .. } => {
+            environment.insert("CINNABAR_BRANCH".to_owned(), source.name.clone());
+            environment.insert("CINNABAR_PR_SOURCE".to_owned(), source.name.clone());
+            environment.insert("CINNABAR_PR_TARGET".to_owned(), target.name.clone());
+        }
+        TriggerEvent::Tag { tag } => {
+            environment.insert("CINNABAR_TAG".to_owned(), tag.name.clone());
+        }
+        event => {
+            environment.insert("CINNABAR_BRANCH".to_owned(), event.branch().name.clone());
+        }
+    }
+
+    for (name, value) in trigger.event.inputs().into_iter().flatten() {
+        environment.insert(format!("INPUT_{}", name.to_uppercase()), value.clone());
+    }
+
+    let configured = [
+        &pipeline.configuration.environment,
+        &step.configuration.environment,
+    ];
+
+    for (name, value) in configured.into_iter().flatten().flatten() {
+        environment.insert(name.clone(), value.clone());
+    }
+
+    environment
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::NaiveDateTime;
+    use domain::{Branch, PipelineConfiguration, PipelineId, Trigger};
+
+    use super::*;
+
+    fn pipeline(event: TriggerEvent) -> Pipeline {
+        let configuration: PipelineConfiguration = serde_json::from_str(
+            r#"{
+                "name": "Build",
+                "trigger": [],
+                "environment": { "RUST_LOG": "info", "PROFILE": "debug" },
+                "steps": [
+                    { "name": "test", "image": "rust", "environment": { "PROFILE": "release" } }
+                ]
+            }"#,
+        )
+        .unwrap();
+
+        let trigger = Trigger {
+            repository_owner: "Owner".to_owned(),
+            repository_name: "Repo".to_owned(),
+            installation_id: 789,
+            event,
+        };
+
+        Pipeline::new(
+            PipelineId::new(42),
+            trigger,
+            configuration,
+            NaiveDateTime::default(),
+        )
+    }
+
+    fn branch(name: &str) -> Branch {
+        Branch {
+            name: name.to_owned(),
+            commit: "123".to_owned(),
+        }
+    }
+
+    #[test]
+    fn step_environment_should_contain_built_in_variables_for_push() {
+        let pipeline = pipeline(TriggerEvent::Push {
+            branch: branch("main"),
+            before: None,
+        });
+
+        let environment = step_environment(&pipeline, &pipeline.steps[0]);
+
+        assert_eq!(environment["CI"], "true");
+        assert_eq!(environment["CINNABAR_PIPELINE_ID"], "42");
+        assert_eq!(environment["CINNABAR_STEP_NAME"], "test");
+        assert_eq!(environment["CINNABAR_COMMIT_SHA"], "123");
+        assert_eq!(environment["CINNABAR_BRANCH"], "main");
+        assert_eq!(environment["CINNABAR_REPO"], "Owner/Repo");
+        assert!(!environment.contains_key("CINNABAR_PR_SOURCE"));
+    }
+
+    #[test]
+    fn step_environment_should_contain_pull_request_branches() {
+        let pipeline = pipeline(TriggerEvent::PullRequest {
+            number: 1,
+            source: branch("feature"),
+            target: branch("main"),
+            from_fork: false,
+        });
+
+        let environment = step_environment(&pipeline, &pipeline.steps[0]);
+
+        assert_eq!(environment["CINNABAR_BRANCH"], "feature");
+        assert_eq!(environment["CINNABAR_PR_SOURCE"], "feature");
+        assert_eq!(environment["CINNABAR_PR_TARGET"], "main");
+    }
+
+    #[test]
+    fn step_environment_should_override_pipeline_with_step_values() {
+        let pipeline = pipeline(TriggerEvent::Manual {
+            branch: branch("main"),
+            pipeline: "Build".to_owned(),
+            inputs: BTreeMap::from([("dry_run".to_owned(), "true".to_owned())]),
+        });
+
+        let environment = step_environment(&pipeline, &pipeline.steps[0]);
+
+        assert_eq!(environment["RUST_LOG"], "info");
+        assert_eq!(environment["PROFILE"], "release");
+        assert_eq!(environment["INPUT_DRY_RUN"], "true");
+    }
+}
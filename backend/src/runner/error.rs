@@ -1,10 +1,21 @@
 use bollard::errors::Error as DockerError;
+use domain::repositories::RepositoryError;
 use thiserror::Error;
 
+use crate::secrets::error::SecretError;
+
 #[derive(Debug, Error)]
 pub enum RunnerError {
     #[error(transparent)]
     Docker(#[from] DockerError),
+    #[error(transparent)]
+    Repository(#[from] RepositoryError),
+    #[error(transparent)]
+    Secret(#[from] SecretError),
+    #[error("Could not store artifact: {0}")]
+    Artifact(#[from] std::io::Error),
+    #[error("Could not access cache: {0}")]
+    Cache(std::io::Error),
     #[error("{0}")]
     Generic(String),
 }
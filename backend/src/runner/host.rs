@@ -0,0 +1,205 @@
+use std::{collections::BTreeMap, fs, path::PathBuf};
+
+use chrono::Utc;
+use domain::{repositories::Repositories, LogLine, Pipeline, PipelineStatus, Step, Trigger};
+use secrecy::{ExposeSecret, SecretString};
+
+use super::error::RunnerError as Error;
+use crate::{
+    artifacts::ArtifactStore,
+    log_streams::LogStreams,
+    registry::{RegistryCredentials, DOCKER_AUTH_CONFIG},
+    secrets::{resolve_runner_secret, resolve_step_secrets, SecretCipher},
+};
+
+/// Where the runner gets the secrets of steps from and reports their progress to.
+///
+/// The backend runs pipelines against the database directly, remote agents send
+/// everything back to the backend.
+pub trait RunnerHost: Send + Sync {
+    fn step_secrets(
+        &self,
+        pipeline: &Pipeline,
+        step: &Step,
+    ) -> Result<BTreeMap<String, SecretString>, Error>;
+    /// Credentials images of the pipeline are pulled with
+    fn registry_credentials(&self, pipeline: &Pipeline) -> Result<RegistryCredentials, Error>;
+    fn update_step_status(
+        &self,
+        pipeline: &Pipeline,
+        step: &Step,
+        status: PipelineStatus,
+        exit_code: Option<i64>,
+        failure_reason: Option<&str>,
+    ) -> Result<(), Error>;
+    fn store_logs(&self, pipeline: &Pipeline, step: &Step, lines: &[LogLine]) -> Result<(), Error>;
+    /// Keeps the tar archive of an artifact path of the step, the archive is a temporary file
+    /// the host removes once it is done with it
+    fn store_artifact(
+        &self,
+        pipeline: &Pipeline,
+        step: &Step,
+        path: &str,
+        archive: PathBuf,
+    ) -> Result<(), Error>;
+}
+
+/// Runs pipelines on the machine of the backend
+pub struct LocalHost<'a> {
+    pub repositories: &'a Repositories,
+    pub log_streams: &'a LogStreams,
+    pub secret_cipher: Option<&'a SecretCipher>,
+    pub artifact_store: &'a ArtifactStore,
+    pub registries: &'a RegistryCredentials,
+}
+
+impl RunnerHost for LocalHost<'_> {
+    fn step_secrets(
+        &self,
+        pipeline: &Pipeline,
+        step: &Step,
+    ) -> Result<BTreeMap<String, SecretString>, Error> {
+        Ok(resolve_step_secrets(
+            self.repositories,
+            self.secret_cipher,
+            &pipeline.trigger,
+            &step.configuration,
+        )?)
+    }
+
+    fn registry_credentials(&self, pipeline: &Pipeline) -> Result<RegistryCredentials, Error> {
+        let secret = resolve_runner_secret(
+            self.repositories,
+            self.secret_cipher,
+            &pipeline.trigger,
+            DOCKER_AUTH_CONFIG,
+        )?;
+
+        combine_registry_credentials(&pipeline.trigger, self.registries, secret.as_ref())
+    }
+
+    fn update_step_status(
+        &self,
+        pipeline: &Pipeline,
+        step: &Step,
+        status: PipelineStatus,
+        exit_code: Option<i64>,
+        failure_reason: Option<&str>,
+    ) -> Result<(), Error> {
+        self.repositories
+            .pipelines
+            .lock()
+            .unwrap()
+            .update_step_status(pipeline.id, step.id, status, exit_code, failure_reason)?;
+
+        // All logs of the step are stored before its final status
+        if status.is_finished() {
+            self.log_streams.close(pipeline.id, step.id);
+        }
+
+        Ok(())
+    }
+
+    fn store_logs(&self, pipeline: &Pipeline, step: &Step, lines: &[LogLine]) -> Result<(), Error> {
+        self.repositories
+            .logs
+            .lock()
+            .unwrap()
+            .append(pipeline.id, step.id, lines)?;
+
+        self.log_streams.publish(pipeline.id, step.id, lines);
+
+        Ok(())
+    }
+
+    fn store_artifact(
+        &self,
+        pipeline: &Pipeline,
+        step: &Step,
+        path: &str,
+        archive: PathBuf,
+    ) -> Result<(), Error> {
+        let stored =
+            fs::File::open(&archive).and_then(|mut file| self.artifact_store.store(&mut file));
+        let _ = fs::remove_file(&archive);
+        let (digest, size) = stored?;
+
+        self.repositories.artifacts.lock().unwrap().create(
+            pipeline.id,
+            step.id,
+            path,
+            &digest,
+            size as i64,
+            Utc::now().naive_utc(),
+        )?;
+
+        Ok(())
+    }
+}
+
+/// Adds the registry credentials stored as secret of a repository or installation to the ones
+/// of the host.
+///
+/// Pull requests from forks get neither, like they get no secrets, so they can not pull
+/// private images with the credentials of the host.
+pub fn combine_registry_credentials(
+    trigger: &Trigger,
+    registries: &RegistryCredentials,
+    secret: Option<&SecretString>,
+) -> Result<RegistryCredentials, Error> {
+    if !trigger.event.allows_secrets() {
+        return Ok(RegistryCredentials::default());
+    }
+
+    match secret {
+        Some(secret) => {
+            let secret = RegistryCredentials::parse(secret.expose_secret()).map_err(|err| {
+                Error::Generic(format!("Secret \"{DOCKER_AUTH_CONFIG}\" is invalid: {err}"))
+            })?;
+
+            Ok(registries.clone().merge(secret))
+        }
+        None => Ok(registries.clone()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use domain::{Branch, DockerImageReference, TriggerEvent};
+
+    use super::*;
+
+    #[test]
+    fn combine_registry_credentials_should_withhold_credentials_from_forks() {
+        let trigger = |from_fork| Trigger {
+            repository_owner: "Owner".to_owned(),
+            repository_name: "Repo".to_owned(),
+            installation_id: 1,
+            event: TriggerEvent::PullRequest {
+                number: 1,
+                source: Branch {
+                    name: "feature".to_owned(),
+                    commit: "abc123".to_owned(),
+                },
+                target: Branch {
+                    name: "main".to_owned(),
+                    commit: "def456".to_owned(),
+                },
+                from_fork,
+            },
+        };
+        let registries =
+            RegistryCredentials::parse(r#"{"auths": {"ghcr.io": {"auth": "YTpi"}}}"#).unwrap();
+        let image = DockerImageReference {
+            hostname: Some("ghcr.io".to_owned()),
+            repository: "owner/private".to_owned(),
+            tag: None,
+        };
+
+        let credentials = combine_registry_credentials(&trigger(false), &registries, None);
+        assert!(credentials.unwrap().for_image(&image).is_some());
+
+        let credentials = combine_registry_credentials(&trigger(true), &registries, None);
+        assert!(credentials.unwrap().for_image(&image).is_none());
+    }
+}
@@ -0,0 +1,33 @@
+use std::collections::HashMap;
+
+use domain::PipelineId;
+
+/// Who runs the pipeline a container, volume or network belongs to, so the janitor only
+/// removes resources it is responsible for
+pub const OWNER_LABEL: &str = "cinnabar.owner";
+pub const PIPELINE_LABEL: &str = "cinnabar.pipeline";
+
+/// Owner of the resources of pipelines the backend runs itself
+pub const BACKEND_OWNER: &str = "backend";
+
+/// Labels every resource created for a pipeline carries
+pub struct ResourceLabels {
+    owner: String,
+    pipeline_id: String,
+}
+
+impl ResourceLabels {
+    pub fn new(owner: &str, pipeline_id: PipelineId) -> Self {
+        Self {
+            owner: owner.to_owned(),
+            pipeline_id: pipeline_id.to_string(),
+        }
+    }
+
+    pub fn to_map(&self) -> HashMap<&str, &str> {
+        HashMap::from([
+            (OWNER_LABEL, self.owner.as_str()),
+            (PIPELINE_LABEL, self.pipeline_id.as_str()),
+        ])
+    }
+}
@@ -0,0 +1,215 @@
+use bollard::container::LogOutput;
+use chrono::{DateTime, NaiveDateTime, Utc};
+use domain::{LogLine, LogStream};
+
+/// Splits the timestamped output frames of a container into numbered lines.
+///
+/// Docker does not guarantee that a frame ends at a line break, so incomplete
+/// lines are kept per stream until the rest of the line arrives.
+pub struct LogCollector {
+    next_number: i32,
+    stdout: Option<PartialLine>,
+    stderr: Option<PartialLine>,
+}
+
+struct PartialLine {
+    timestamp: NaiveDateTime,
+    content: Vec<u8>,
+}
+
+impl LogCollector {
+    pub fn new() -> Self {
+        Self {
+            next_number: 1,
+            stdout: None,
+            stderr: None,
+        }
+    }
+
+    pub fn push(&mut self, output: LogOutput) -> Vec<LogLine> {
+        let (stream, message) = match output {
+            LogOutput::StdOut { message } | LogOutput::Console { message } => {
+                (LogStream::Stdout, message)
+            }
+            LogOutput::StdErr { message } => (LogStream::Stderr, message),
+            LogOutput::StdIn { .. } => return vec![],
+        };
+
+        let (timestamp, content) = split_timestamp(&message);
+
+        let partial = self
+            .partial_line(stream)
+            .get_or_insert_with(|| PartialLine {
+                timestamp,
+                content: vec![],
+            });
+        partial.content.extend_from_slice(content);
+
+        let mut lines = vec![];
+
+        while let Some(partial) = self.partial_line(stream).take() {
+            let Some(position) = partial.content.iter().position(|byte| *byte == b'\n') else {
+                *self.partial_line(stream) = Some(partial);
+                break;
+            };
+
+            let PartialLine {
+                timestamp: line_timestamp,
+                mut content,
+            } = partial;
+            let rest = content.split_off(position + 1);
+
+            lines.push(self.create_line(stream, line_timestamp, &content));
+
+            if !rest.is_empty() {
+                *self.partial_line(stream) = Some(PartialLine {
+                    timestamp,
+                    content: rest,
+                });
+            }
+        }
+
+        lines
+    }
+
+    /// Flushes the lines that were not terminated by a line break
+    pub fn finish(&mut self) -> Vec<LogLine> {
+        [LogStream::Stdout, LogStream::Stderr]
+            .into_iter()
+            .filter_map(|stream| {
+                self.partial_line(stream)
+                    .take()
+                    .map(|partial| self.create_line(stream, partial.timestamp, &partial.content))
+            })
+            .collect()
+    }
+
+    fn partial_line(&mut self, stream: LogStream) -> &mut Option<PartialLine> {
+        match stream {
+            LogStream::Stdout => &mut self.stdout,
+            LogStream::Stderr => &mut self.stderr,
+        }
+    }
+
+    fn create_line(
+        &mut self,
+        stream: LogStream,
+        timestamp: NaiveDateTime,
+        content: &[u8],
+    ) -> LogLine {
+        let number = self.next_number;
+        self.next_number += 1;
+
+        let content = String::from_utf8_lossy(content);
+        let content = content.trim_end_matches(['\n', '\r']).to_owned();
+
+        LogLine {
+            number,
+            timestamp,
+            stream,
+            content,
+        }
+    }
+}
+
+fn split_timestamp(message: &[u8]) -> (NaiveDateTime, &[u8]) {
+    let mut parts = message.splitn(2, |byte| *byte == b' ');
+    let timestamp = parts
+        .next()
+        .and_then(|timestamp| std::str::from_utf8(timestamp).ok())
+        .and_then(|timestamp| DateTime::parse_from_rfc3339(timestamp).ok());
+
+    match (timestamp, parts.next()) {
+        (Some(timestamp), Some(content)) => (timestamp.naive_utc(), content),
+        _ => (Utc::now().naive_utc(), message),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn stdout(message: &'static str) -> LogOutput {
+        LogOutput::StdOut {
+            message: message.into(),
+        }
+    }
+
+    fn stderr(message: &'static str) -> LogOutput {
+        LogOutput::StdErr {
+            message: message.into(),
+        }
+    }
+
+    fn timestamp(value: &str) -> NaiveDateTime {
+        DateTime::parse_from_rfc3339(value).unwrap().naive_utc()
+    }
+
+    #[test]
+    fn push_should_split_frames_into_lines() {
+        let mut collector = LogCollector::new();
+
+        let lines = collector.push(stdout("2024-01-01T00:00:00.5Z first\nsecond\n"));
+
+        assert_eq!(
+            lines,
+            vec![
+                LogLine {
+                    number: 1,
+                    timestamp: timestamp("2024-01-01T00:00:00.5Z"),
+                    stream: LogStream::Stdout,
+                    content: "first".to_owned()
+                },
+                LogLine {
+                    number: 2,
+                    timestamp: timestamp("2024-01-01T00:00:00.5Z"),
+                    stream: LogStream::Stdout,
+                    content: "second".to_owned()
+                }
+            ]
+        );
+    }
+
+    #[test]
+    fn push_should_join_partial_lines_per_stream() {
+        let mut collector = LogCollector::new();
+
+        assert!(collector
+            .push(stdout("2024-01-01T00:00:00Z hello "))
+            .is_empty());
+        assert_eq!(
+            collector.push(stderr("2024-01-01T00:00:01Z error\n"))[0].content,
+            "error"
+        );
+
+        let lines = collector.push(stdout("2024-01-01T00:00:02Z world\r\n"));
+
+        assert_eq!(
+            lines,
+            vec![LogLine {
+                number: 2,
+                timestamp: timestamp("2024-01-01T00:00:00Z"),
+                stream: LogStream::Stdout,
+                content: "hello world".to_owned()
+            }]
+        );
+    }
+
+    #[test]
+    fn finish_should_flush_unterminated_lines() {
+        let mut collector = LogCollector::new();
+
+        collector.push(stdout("2024-01-01T00:00:00Z no line break"));
+
+        assert_eq!(
+            collector.finish(),
+            vec![LogLine {
+                number: 1,
+                timestamp: timestamp("2024-01-01T00:00:00Z"),
+                stream: LogStream::Stdout,
+                content: "no line break".to_owned()
+            }]
+        );
+        assert!(collector.finish().is_empty());
+    }
+}
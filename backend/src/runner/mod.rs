@@ -1,102 +1,1350 @@
-use bollard::Docker;
-use domain::{Pipeline, PipelineStatus, Step};
-use futures::TryStreamExt;
+use bollard::{errors::Error as DockerError, Docker};
+use sha2::{Digest, Sha256};
+use std::time::Duration;
+
+use domain::{
+    parse_cache_key, CacheConfiguration, CacheKeySegment, DockerImageReference,
+    HealthCheckConfiguration, LogLine, LogStream, Pipeline, PipelineStatus, Step, Timeout,
+};
+use futures::{stream::FuturesUnordered, StreamExt, TryStreamExt};
+use tokio::time::Instant;
 
-use self::container::ContainerExitCode;
 use self::error::RunnerError as Error;
-use self::{container::Container, volume::Volume};
+use self::{
+    container::Container, labels::ResourceLabels, network::Network, redact::Redactor,
+    volume::Volume,
+};
+use crate::{
+    cache::{combine_archives, hash_archive_files, CacheScope, CacheStore},
+    cancellation::Cancellation,
+    config::ResourcesConfig,
+};
 use secrecy::SecretString;
 
+mod clone;
 mod container;
+mod environment;
 pub mod error;
+pub mod host;
+pub mod labels;
+mod logs;
+mod network;
+mod redact;
 mod volume;
 
+/// Failure reason of steps whose container exceeded its memory limit
+const OOM_KILLED: &str = "OOMKilled";
+/// Time for a service container to start on top of the time its health check may take
+const SERVICE_START_MARGIN: Duration = Duration::from_secs(30);
+
+pub use clone::add_clone_step;
+pub use host::{LocalHost, RunnerHost};
+
 pub struct PipelineRunner<'a> {
     pub docker: &'a Docker,
     pub access_token: &'a SecretString,
+    pub host: &'a dyn RunnerHost,
+    pub cancellation: &'a Cancellation,
+    /// Value of the owner label of all created resources, see [`labels::OWNER_LABEL`]
+    pub owner: &'a str,
+    pub caches: &'a CacheStore,
+    /// Server-wide defaults and maximums of the resources of step containers
+    pub resources: &'a ResourcesConfig,
+    /// Caches of the default branch are restored on branches that have none yet
+    pub default_branch: Option<&'a str>,
     pub pipeline: &'a mut Pipeline,
 }
 
 impl<'a> PipelineRunner<'a> {
     pub async fn run(&mut self) -> Result<(), Error> {
-        self.create_cache_volumes().await?;
-
         let workspace_volume = format!("workspace-pipeline-{}", self.pipeline.id);
-        let workspace_volume = Volume::create(self.docker, workspace_volume).await?;
+        let workspace_volume =
+            Volume::create(self.docker, workspace_volume, &self.labels()).await?;
 
-        let pipeline_status = self.run_pipeline(&workspace_volume).await;
+        let network = match &self.pipeline.configuration.services {
+            Some(services) if !services.is_empty() => {
+                let network = format!("network-pipeline-{}", self.pipeline.id);
+                Some(Network::create(self.docker, network, &self.labels()).await?)
+            }
+            _ => None,
+        };
+
+        let deadline = self
+            .pipeline
+            .configuration
+            .timeout
+            .map(|timeout| Instant::now() + timeout.0);
 
+        let mut services = vec![];
+        let step_statuses = match self
+            .start_services(network.as_ref(), &mut services, deadline)
+            .await
+        {
+            Ok(()) => self
+                .run_pipeline(&workspace_volume, network.as_ref(), deadline)
+                .await
+                .map(Some),
+            Err(failure) => self
+                .skip_steps_of_failed_service(failure, &services)
+                .await
+                .map(|()| None),
+        };
+
+        let removed = remove_services(&services, network.as_ref()).await;
         workspace_volume.remove().await?;
+        removed?;
+
+        let Some((step_statuses, deadline_hit)) = step_statuses? else {
+            self.pipeline.status = PipelineStatus::Failed;
 
-        self.pipeline.status = pipeline_status?;
+            for step in &mut self.pipeline.steps {
+                step.status = PipelineStatus::Skipped;
+            }
+
+            return Ok(());
+        };
+
+        self.pipeline.status = pipeline_status(
+            self.cancellation.is_cancelled(),
+            deadline_hit,
+            &step_statuses,
+        );
+
+        for (step, status) in self.pipeline.steps.iter_mut().zip(step_statuses) {
+            step.status = status;
+        }
 
         Ok(())
     }
 
-    async fn create_cache_volumes(&self) -> Result<(), Error> {
-        let cache_volumes =
-            self.pipeline
-                .configuration
-                .steps
-                .iter()
-                .flat_map(|step| match &step.cache {
-                    Some(vec) => vec.clone(),
-                    None => vec![],
-                });
+    /// Starts the services of the pipeline and waits until all of them are healthy,
+    /// the started containers are added to `started` even if starting another one failed.
+    /// Waiting stops once the pipeline is cancelled or its deadline passed, no steps are
+    /// started then.
+    async fn start_services(
+        &self,
+        network: Option<&Network<'a>>,
+        started: &mut Vec<Container<'a>>,
+        deadline: Option<Instant>,
+    ) -> Result<(), ServiceFailure> {
+        let (Some(network), Some(services)) = (network, &self.pipeline.configuration.services)
+        else {
+            return Ok(());
+        };
+
+        for (index, service) in services.iter().enumerate() {
+            let failure = |error| ServiceFailure { index, error };
 
-        for cache in cache_volumes {
-            Volume::create(self.docker, cache.clone()).await?;
+            self.pull_image(&service.image).await.map_err(failure)?;
+            started.push(
+                Container::start_service(
+                    self.docker,
+                    self.pipeline,
+                    service,
+                    network,
+                    &self.labels(),
+                )
+                .await
+                .map_err(failure)?,
+            );
+        }
+
+        for (index, (container, service)) in started.iter().zip(services).enumerate() {
+            let limit = service
+                .health_check
+                .as_ref()
+                .map_or(Duration::ZERO, HealthCheckConfiguration::startup_time)
+                + SERVICE_START_MARGIN;
+
+            tokio::select! {
+                result = container.wait_until_healthy(&service.name, limit) => {
+                    result.map_err(|error| ServiceFailure { index, error })?
+                }
+                _ = self.cancellation.cancelled() => return Ok(()),
+                _ = sleep(remaining_time(None, deadline)) => return Ok(()),
+            }
         }
 
         Ok(())
     }
 
-    async fn run_pipeline(&self, workspace_volume: &Volume<'a>) -> Result<PipelineStatus, Error> {
+    /// Stores the logs of the service that failed as the logs of the first step and skips
+    /// all steps, recording the error of the service as the reason
+    async fn skip_steps_of_failed_service(
+        &self,
+        failure: ServiceFailure,
+        started: &[Container<'a>],
+    ) -> Result<(), Error> {
+        let ServiceFailure { index, error } = failure;
+        let reason = error.to_string();
+
+        println!("Service of pipeline {} failed: {reason}", self.pipeline.id);
+
+        if let (Some(container), Some(step)) = (started.get(index), self.pipeline.steps.first()) {
+            match container.logs().await {
+                Ok(lines) => self.store_logs(step, &lines)?,
+                Err(err) => println!("Failed to get logs of service: {err}"),
+            }
+        }
+
         for step in &self.pipeline.steps {
-            let exit_code = self.run_step(step, workspace_volume).await?;
+            self.update_step_status(step, PipelineStatus::Skipped, None, Some(&reason))?;
+        }
+
+        Ok(())
+    }
+
+    /// Returns the statuses of the steps and whether the deadline of the pipeline passed
+    /// before all of them were started
+    async fn run_pipeline(
+        &self,
+        workspace_volume: &Volume<'a>,
+        network: Option<&Network<'a>>,
+        deadline: Option<Instant>,
+    ) -> Result<(Vec<PipelineStatus>, bool), Error> {
+        let steps = &self.pipeline.steps;
+        let dependencies = self.pipeline.configuration.step_dependencies();
+
+        let (mut statuses, deadline_hit) = run_steps(
+            &dependencies,
+            deadline,
+            || self.cancellation.is_cancelled(),
+            |index| self.run_step(&steps[index], workspace_volume, network, deadline),
+        )
+        .await?;
+
+        // Steps that were not started before the pipeline timed out or was cancelled
+        for (step, status) in steps.iter().zip(&mut statuses) {
+            if *status == PipelineStatus::Pending {
+                *status = PipelineStatus::Skipped;
+            }
+
+            if *status == PipelineStatus::Skipped {
+                self.update_step_status(step, *status, None, None)?;
+            }
+        }
+
+        Ok((statuses, deadline_hit))
+    }
+
+    async fn run_step(
+        &self,
+        step: &Step,
+        volume: &Volume<'a>,
+        network: Option<&Network<'a>>,
+        deadline: Option<Instant>,
+    ) -> Result<PipelineStatus, Error> {
+        self.update_step_status(step, PipelineStatus::Running, None, None)?;
+        self.pull_step_image(step).await?;
+
+        let secrets = self.host.step_secrets(self.pipeline, step)?;
+        let resources = step
+            .configuration
+            .resources
+            .unwrap_or_default()
+            .resolve(&self.resources.defaults, &self.resources.maximums);
 
-            if exit_code.is_err() {
+        let container = Container::create(
+            self.docker,
+            self.pipeline,
+            step,
+            volume,
+            network,
+            &self.labels(),
+            &resources,
+            self.access_token,
+            &secrets,
+        )
+        .await?;
+        let caches = self.restore_caches(step, &container).await;
+        let redactor = Redactor::new(std::iter::once(self.access_token).chain(secrets.values()));
+        let timeout = remaining_time(step.configuration.timeout, deadline);
+        let interruption = async {
+            tokio::select! {
+                _ = sleep(timeout) => PipelineStatus::TimedOut,
+                _ = self.cancellation.cancelled() => PipelineStatus::Cancelled,
+            }
+        };
+
+        let result = tokio::select! {
+            result = container.run(|lines| self.store_logs(step, &redactor.redact(lines))) => {
+                result.map(|exit_code| {
+                    let status = if exit_code.is_err() {
+                        PipelineStatus::Failed
+                    } else {
+                        PipelineStatus::Passed
+                    };
+
+                    (status, Some(exit_code.0))
+                })
+            }
+            status = interruption => container.stop().await.map(|_| (status, None)),
+        };
+        let result = match result {
+            Ok((PipelineStatus::Passed, exit_code)) => {
+                let status = self.collect_artifacts(step, &container).await;
+
+                if let Ok(PipelineStatus::Passed) = status {
+                    self.save_caches(step, &container, &caches).await;
+                }
+
+                status.map(|status| (status, exit_code))
+            }
+            result => result,
+        };
+        let failure_reason = match result {
+            Ok((PipelineStatus::Failed, _)) => match container.was_oom_killed().await {
+                Ok(true) => {
+                    println!("Step {} of {} ran out of memory", step.id, self.pipeline.id);
+                    Some(OOM_KILLED)
+                }
+                Ok(false) => None,
+                Err(err) => {
+                    println!("Failed to inspect container of step {}: {err}", step.id);
+                    None
+                }
+            },
+            _ => None,
+        };
+        container.remove().await?;
+
+        let (status, exit_code) = result?;
+        self.update_step_status(step, status, exit_code, failure_reason)?;
+
+        Ok(status)
+    }
+
+    /// Stores the artifacts of a passed step, failing it if one of them is missing
+    async fn collect_artifacts(
+        &self,
+        step: &Step,
+        container: &Container<'_>,
+    ) -> Result<PipelineStatus, Error> {
+        for (index, path) in step.configuration.artifacts.iter().flatten().enumerate() {
+            let archive = std::env::temp_dir().join(format!(
+                "artifact-{}-{}-{}-{index}.tar",
+                std::process::id(),
+                self.pipeline.id,
+                step.id
+            ));
+
+            if let Err(err) = container.download_to(path, &archive).await {
+                let _ = std::fs::remove_file(&archive);
+                println!(
+                    "Failed to collect artifact {path} of step {}: {err}",
+                    step.id
+                );
                 return Ok(PipelineStatus::Failed);
             }
+
+            self.host
+                .store_artifact(self.pipeline, step, path, archive)?;
         }
 
         Ok(PipelineStatus::Passed)
     }
 
-    async fn run_step(&self, step: &Step, volume: &Volume<'a>) -> Result<ContainerExitCode, Error> {
-        self.pull_image_for_step(step).await?;
+    /// Restores the caches of the step into the workspace, returning the ones that have to
+    /// be saved once it passed. Caches never fail the step.
+    async fn restore_caches<'s>(
+        &self,
+        step: &'s Step,
+        container: &Container<'_>,
+    ) -> Vec<(&'s CacheConfiguration, String)> {
+        let mut unsaved = vec![];
 
-        let container =
-            Container::create(self.docker, self.pipeline, step, volume, self.access_token).await?;
-        let exit_code = container.run().await;
-        container.remove().await?;
+        for cache in step.configuration.cache.iter().flatten() {
+            match self.restore_cache(cache, container).await {
+                Ok(Some(key)) => unsaved.push((cache, key)),
+                Ok(None) => {}
+                Err(err) => println!(
+                    "Failed to restore cache {} of step {}: {err}",
+                    cache.key, step.id
+                ),
+            }
+        }
+
+        unsaved
+    }
+
+    /// Returns the key the cache has to be saved under, `None` if it was restored from
+    /// exactly that key
+    async fn restore_cache(
+        &self,
+        cache: &CacheConfiguration,
+        container: &Container<'_>,
+    ) -> Result<Option<String>, Error> {
+        let key = self.render_cache_key(&cache.key, container).await?;
+        let mut restore_keys = vec![];
+
+        for restore_key in cache.restore_keys.iter().flatten() {
+            restore_keys.push(self.render_cache_key(restore_key, container).await?);
+        }
+
+        let hit = self
+            .caches
+            .restore(&self.cache_scope(), &key, &restore_keys)
+            .map_err(Error::Cache)?;
 
-        exit_code
+        let Some(hit) = hit else {
+            return Ok(Some(key));
+        };
+
+        container.upload(hit.archive).await?;
+
+        Ok((!hit.exact).then_some(key))
+    }
+
+    async fn save_caches(
+        &self,
+        step: &Step,
+        container: &Container<'_>,
+        caches: &[(&CacheConfiguration, String)],
+    ) {
+        for (cache, key) in caches {
+            if let Err(err) = self.save_cache(cache, key, container).await {
+                println!("Failed to save cache {key} of step {}: {err}", step.id);
+            }
+        }
+    }
+
+    async fn save_cache(
+        &self,
+        cache: &CacheConfiguration,
+        key: &str,
+        container: &Container<'_>,
+    ) -> Result<(), Error> {
+        let mut archives = vec![];
+
+        for path in &cache.paths {
+            match container.download(path).await {
+                Ok(archive) => archives.push((path.as_str(), archive)),
+                Err(err) if is_not_found(&err) => {}
+                Err(err) => return Err(err),
+            }
+        }
+
+        if archives.is_empty() {
+            return Ok(());
+        }
+
+        let archive = combine_archives(&archives).map_err(Error::Cache)?;
+        let saved = self
+            .caches
+            .save(&self.cache_scope(), key, &archive)
+            .map_err(Error::Cache)?;
+
+        if !saved {
+            println!("Cache {key} is larger than the maximum cache size and was not saved");
+        }
+
+        Ok(())
+    }
+
+    /// Replaces the `hashFiles` expressions of the key with the hash of the files in the
+    /// workspace, files that do not exist are left out
+    async fn render_cache_key(
+        &self,
+        template: &str,
+        container: &Container<'_>,
+    ) -> Result<String, Error> {
+        let mut key = String::new();
+
+        for segment in parse_cache_key(template).map_err(Error::Generic)? {
+            let paths = match segment {
+                CacheKeySegment::Text(text) => {
+                    key.push_str(&text);
+                    continue;
+                }
+                CacheKeySegment::HashFiles(paths) => paths,
+            };
+
+            let mut hasher = Sha256::new();
+            let mut found = false;
+
+            for path in paths {
+                match container.download(&path).await {
+                    Ok(archive) => {
+                        hasher.update(path.as_bytes());
+                        hash_archive_files(&mut hasher, &archive).map_err(Error::Cache)?;
+                        found = true;
+                    }
+                    Err(err) if is_not_found(&err) => {}
+                    Err(err) => return Err(err),
+                }
+            }
+
+            if found {
+                key.push_str(&hex::encode(hasher.finalize()));
+            }
+        }
+
+        Ok(key)
+    }
+
+    fn labels(&self) -> ResourceLabels {
+        ResourceLabels::new(self.owner, self.pipeline.id)
+    }
+
+    fn cache_scope(&self) -> CacheScope<'_> {
+        CacheScope::new(&self.pipeline.trigger, self.default_branch)
+    }
+
+    fn update_step_status(
+        &self,
+        step: &Step,
+        status: PipelineStatus,
+        exit_code: Option<i64>,
+        failure_reason: Option<&str>,
+    ) -> Result<(), Error> {
+        self.host
+            .update_step_status(self.pipeline, step, status, exit_code, failure_reason)
+    }
+
+    fn store_logs(&self, step: &Step, lines: &[LogLine]) -> Result<(), Error> {
+        self.host.store_logs(self.pipeline, step, lines)
+    }
+
+    /// Pulls the image of the step, failing the step with the error as its only log line
+    /// if that is not possible
+    async fn pull_step_image(&self, step: &Step) -> Result<(), Error> {
+        let image = &step.configuration.image;
+        let Err(err) = self.pull_image(image).await else {
+            return Ok(());
+        };
+
+        let line = LogLine {
+            number: 1,
+            timestamp: chrono::Utc::now().naive_utc(),
+            stream: LogStream::Stderr,
+            content: format!("Failed to pull image {image}: {err}"),
+        };
+        self.store_logs(step, &[line])?;
+        self.update_step_status(step, PipelineStatus::Failed, None, None)?;
+
+        Err(err)
     }
 
-    async fn pull_image_for_step(&self, step: &Step) -> Result<(), Error> {
-        let image =
-            self.docker
-                .create_image(
-                    Some(bollard::image::CreateImageOptions {
-                        from_image: step.configuration.image.to_string().as_str(),
-                        tag: step.configuration.image.tag.as_deref().unwrap_or("latest"),
+    async fn pull_image(&self, image: &DockerImageReference) -> Result<(), Error> {
+        let credentials = self
+            .host
+            .registry_credentials(self.pipeline)?
+            .for_image(image);
+        let image_name = image.to_string();
+        let image = self
+            .docker
+            .create_image(
+                Some(bollard::image::CreateImageOptions {
+                    from_image: image_name.as_str(),
+                    tag: image.tag.as_deref().unwrap_or("latest"),
+                    ..Default::default()
+                }),
+                None,
+                credentials,
+            )
+            .try_collect::<Vec<_>>()
+            .await;
+
+        let image = match image {
+            Ok(image) => image,
+            // Images built locally, like the clone image, can not be pulled
+            Err(err) => {
+                return match self.docker.inspect_image(&image_name).await {
+                    Ok(_) => Ok(()),
+                    Err(_) => Err(err.into()),
+                };
+            }
+        };
+
+        let image_status = image.last().unwrap().status.as_ref().unwrap();
+
+        println!("{image_status}");
+
+        Ok(())
+    }
+}
+
+/// A service that could not be started or did not become healthy
+struct ServiceFailure {
+    /// Position of the service in the configuration, its container was started if the
+    /// started containers reach that far
+    index: usize,
+    error: Error,
+}
+
+/// Removes the service containers and their network, trying all of them before
+/// returning the first error
+async fn remove_services(
+    services: &[Container<'_>],
+    network: Option<&Network<'_>>,
+) -> Result<(), Error> {
+    let mut result = Ok(());
+
+    for service in services {
+        result = result.and(service.remove_forced().await);
+    }
+
+    if let Some(network) = network {
+        result = result.and(network.remove().await);
+    }
+
+    result
+}
+
+/// The time a step may run for, limited by its own timeout and the deadline of the pipeline
+fn remaining_time(timeout: Option<Timeout>, deadline: Option<Instant>) -> Option<Duration> {
+    let remaining = deadline.map(|deadline| deadline.saturating_duration_since(Instant::now()));
+
+    [timeout.map(|timeout| timeout.0), remaining]
+        .into_iter()
+        .flatten()
+        .min()
+}
+
+fn is_not_found(err: &Error) -> bool {
+    matches!(
+        err,
+        Error::Docker(DockerError::DockerResponseServerError {
+            status_code: 404,
+            ..
+        })
+    )
+}
+
+/// Waits for the duration, forever if there is none
+async fn sleep(duration: Option<Duration>) {
+    match duration {
+        Some(duration) => tokio::time::sleep(duration).await,
+        None => std::future::pending().await,
+    }
+}
+
+/// Runs the steps in the order of their dependencies until all of them finished, the deadline
+/// passed or the pipeline was cancelled. Steps that were never started stay pending, the
+/// returned flag tells whether the deadline kept any of them from starting.
+async fn run_steps<F>(
+    dependencies: &[Vec<usize>],
+    deadline: Option<Instant>,
+    is_cancelled: impl Fn() -> bool,
+    run_step: impl Fn(usize) -> F,
+) -> Result<(Vec<PipelineStatus>, bool), Error>
+where
+    F: Future<Output = Result<PipelineStatus, Error>>,
+{
+    let mut statuses = vec![PipelineStatus::Pending; dependencies.len()];
+    let mut running = FuturesUnordered::new();
+    let mut error = None;
+    let mut deadline_hit = false;
+
+    loop {
+        let timed_out = deadline.is_some_and(|deadline| deadline <= Instant::now());
+
+        if timed_out && statuses.contains(&PipelineStatus::Pending) {
+            deadline_hit = true;
+        }
+
+        if error.is_none() && !timed_out && !is_cancelled() {
+            schedule_steps(dependencies, &mut statuses, |index| {
+                let step = run_step(index);
+                running.push(async move { (index, step.await) });
+            });
+        }
+
+        let Some((index, result)) = running.next().await else {
+            break;
+        };
+
+        statuses[index] = match result {
+            Ok(status) => status,
+            Err(err) => {
+                error.get_or_insert(err);
+                PipelineStatus::Failed
+            }
+        };
+    }
+
+    match error {
+        Some(err) => Err(err),
+        None => Ok((statuses, deadline_hit)),
+    }
+}
+
+fn pipeline_status(
+    cancelled: bool,
+    deadline_hit: bool,
+    step_statuses: &[PipelineStatus],
+) -> PipelineStatus {
+    let has_status = |status| step_statuses.contains(&status);
+
+    if cancelled {
+        PipelineStatus::Cancelled
+    } else if deadline_hit || has_status(PipelineStatus::TimedOut) {
+        PipelineStatus::TimedOut
+    } else if has_status(PipelineStatus::Failed) {
+        PipelineStatus::Failed
+    } else {
+        PipelineStatus::Passed
+    }
+}
+
+/// Starts every pending step whose dependencies have all passed and skips every
+/// pending step that depends on a failed or skipped step.
+fn schedule_steps(
+    dependencies: &[Vec<usize>],
+    statuses: &mut [PipelineStatus],
+    mut start: impl FnMut(usize),
+) {
+    loop {
+        let mut changed = false;
+
+        for (index, step_dependencies) in dependencies.iter().enumerate() {
+            if statuses[index] != PipelineStatus::Pending {
+                continue;
+            }
+
+            let dependency_statuses = step_dependencies.iter().map(|index| statuses[*index]);
+
+            if dependency_statuses.clone().any(|status| {
+                matches!(
+                    status,
+                    PipelineStatus::Failed
+                        | PipelineStatus::Skipped
+                        | PipelineStatus::TimedOut
+                        | PipelineStatus::Cancelled
+                )
+            }) {
+                statuses[index] = PipelineStatus::Skipped;
+                changed = true;
+            } else if dependency_statuses
+                .clone()
+                .all(|status| status == PipelineStatus::Passed)
+            {
+                statuses[index] = PipelineStatus::Running;
+                start(index);
+            }
+        }
+
+        if !changed {
+            break;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn schedule_steps_should_start_steps_without_dependencies() {
+        let dependencies = vec![vec![], vec![], vec![0, 1]];
+        let mut statuses = vec![PipelineStatus::Pending; 3];
+        let mut started = vec![];
+
+        schedule_steps(&dependencies, &mut statuses, |index| started.push(index));
+
+        assert_eq!(started, vec![0, 1]);
+        assert_eq!(
+            statuses,
+            vec![
+                PipelineStatus::Running,
+                PipelineStatus::Running,
+                PipelineStatus::Pending
+            ]
+        );
+    }
+
+    #[test]
+    fn schedule_steps_should_start_steps_once_dependencies_passed() {
+        let dependencies = vec![vec![], vec![], vec![0, 1]];
+        let mut statuses = vec![
+            PipelineStatus::Passed,
+            PipelineStatus::Passed,
+            PipelineStatus::Pending,
+        ];
+        let mut started = vec![];
+
+        schedule_steps(&dependencies, &mut statuses, |index| started.push(index));
+
+        assert_eq!(started, vec![2]);
+    }
+
+    #[test]
+    fn remaining_time_should_be_limited_by_pipeline_deadline() {
+        let step_timeout = Some(Timeout(Duration::from_secs(600)));
+        let deadline = Instant::now() + Duration::from_secs(60);
+
+        assert_eq!(remaining_time(None, None), None);
+        assert_eq!(
+            remaining_time(step_timeout, None),
+            Some(Duration::from_secs(600))
+        );
+        assert!(remaining_time(step_timeout, Some(deadline)).unwrap() <= Duration::from_secs(60));
+    }
+
+    #[tokio::test]
+    async fn pipeline_should_time_out_when_deadline_passes_between_steps() {
+        let dependencies = vec![vec![], vec![0]];
+        let deadline = Instant::now() + Duration::from_millis(20);
+
+        let (statuses, deadline_hit) = run_steps(
+            &dependencies,
+            Some(deadline),
+            || false,
+            |_| async {
+                tokio::time::sleep(Duration::from_millis(40)).await;
+                Ok(PipelineStatus::Passed)
+            },
+        )
+        .await
+        .unwrap();
+
+        assert_eq!(statuses, [PipelineStatus::Passed, PipelineStatus::Pending]);
+        assert!(deadline_hit);
+        assert_eq!(
+            pipeline_status(
+                false,
+                deadline_hit,
+                &[PipelineStatus::Passed, PipelineStatus::Skipped]
+            ),
+            PipelineStatus::TimedOut
+        );
+    }
+
+    #[test]
+    fn schedule_steps_should_skip_dependents_of_timed_out_step() {
+        let dependencies = vec![vec![], vec![0]];
+        let mut statuses = vec![PipelineStatus::TimedOut, PipelineStatus::Pending];
+        let mut started = vec![];
+
+        schedule_steps(&dependencies, &mut statuses, |index| started.push(index));
+
+        assert!(started.is_empty());
+        assert_eq!(statuses[1], PipelineStatus::Skipped);
+    }
+
+    #[test]
+    fn schedule_steps_should_skip_transitive_dependents_of_failed_step() {
+        let dependencies = vec![vec![], vec![], vec![3], vec![0], vec![1]];
+        let mut statuses = vec![
+            PipelineStatus::Failed,
+            PipelineStatus::Running,
+            PipelineStatus::Pending,
+            PipelineStatus::Pending,
+            PipelineStatus::Pending,
+        ];
+        let mut started = vec![];
+
+        schedule_steps(&dependencies, &mut statuses, |index| started.push(index));
+
+        assert!(started.is_empty());
+        assert_eq!(
+            statuses,
+            vec![
+                PipelineStatus::Failed,
+                PipelineStatus::Running,
+                PipelineStatus::Skipped,
+                PipelineStatus::Skipped,
+                PipelineStatus::Pending
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn pull_step_image_should_fail_step_with_error_in_logs() {
+        use std::sync::Mutex;
+
+        use domain::{Branch, PipelineConfiguration, PipelineId, Trigger, TriggerEvent};
+
+        use crate::{
+            cancellation::{Cancellations, RunGroup},
+            registry::RegistryCredentials,
+        };
+
+        /// Records the statuses and logs of the steps
+        #[derive(Default)]
+        struct RecordingHost {
+            statuses: Mutex<Vec<PipelineStatus>>,
+            logs: Mutex<Vec<LogLine>>,
+        }
+
+        impl RunnerHost for RecordingHost {
+            fn step_secrets(
+                &self,
+                _: &Pipeline,
+                _: &Step,
+            ) -> Result<std::collections::BTreeMap<String, SecretString>, Error> {
+                Ok(Default::default())
+            }
+
+            fn registry_credentials(&self, _: &Pipeline) -> Result<RegistryCredentials, Error> {
+                Ok(Default::default())
+            }
+
+            fn update_step_status(
+                &self,
+                _: &Pipeline,
+                _: &Step,
+                status: PipelineStatus,
+                _: Option<i64>,
+                _: Option<&str>,
+            ) -> Result<(), Error> {
+                self.statuses.lock().unwrap().push(status);
+                Ok(())
+            }
+
+            fn store_logs(&self, _: &Pipeline, _: &Step, lines: &[LogLine]) -> Result<(), Error> {
+                self.logs.lock().unwrap().extend_from_slice(lines);
+                Ok(())
+            }
+
+            fn store_artifact(
+                &self,
+                _: &Pipeline,
+                _: &Step,
+                _: &str,
+                _: std::path::PathBuf,
+            ) -> Result<(), Error> {
+                Ok(())
+            }
+        }
+
+        // Nothing listens on the socket, so pulling fails without a Docker daemon
+        let docker = Docker::connect_with_socket(
+            "/nonexistent/docker.sock",
+            5,
+            bollard::API_DEFAULT_VERSION,
+        )
+        .unwrap();
+        let trigger = Trigger {
+            repository_owner: "Owner".to_owned(),
+            repository_name: "Repo".to_owned(),
+            installation_id: 1,
+            event: TriggerEvent::Push {
+                branch: Branch {
+                    name: "main".to_owned(),
+                    commit: "abc123".to_owned(),
+                },
+                before: None,
+            },
+        };
+        let configuration: PipelineConfiguration = serde_json::from_str(
+            r#"{
+                "name": "Build",
+                "trigger": [{ "event": "push" }],
+                "steps": [{ "name": "test", "image": "alpine" }]
+            }"#,
+        )
+        .unwrap();
+        let mut pipeline = Pipeline::new(
+            PipelineId(1),
+            trigger,
+            configuration,
+            chrono::Utc::now().naive_utc(),
+        );
+        let step = pipeline.steps[0].clone();
+        let cancellation = Cancellations::default().register(
+            pipeline.id,
+            RunGroup::new(&pipeline.trigger, &pipeline.configuration.name),
+        );
+        let caches = CacheStore::new(std::env::temp_dir().join("cinnabar-test-pull"), 0);
+        let resources = ResourcesConfig {
+            defaults: Default::default(),
+            maximums: Default::default(),
+        };
+        let access_token = SecretString::new("token".to_owned());
+        let host = RecordingHost::default();
+        let runner = PipelineRunner {
+            docker: &docker,
+            access_token: &access_token,
+            host: &host,
+            cancellation: &cancellation,
+            owner: labels::BACKEND_OWNER,
+            caches: &caches,
+            resources: &resources,
+            default_branch: None,
+            pipeline: &mut pipeline,
+        };
+
+        assert!(runner.pull_step_image(&step).await.is_err());
+        assert_eq!(*host.statuses.lock().unwrap(), [PipelineStatus::Failed]);
+
+        let logs = host.logs.lock().unwrap();
+        assert_eq!(logs.len(), 1);
+        assert!(logs[0].content.starts_with("Failed to pull image alpine"));
+    }
+
+    /// Starts `registry:2` with htpasswd authentication on the port, the user `test` has
+    /// the password `test`
+    async fn start_registry(docker: &Docker, port: u16) -> String {
+        use bollard::{
+            container::{Config, CreateContainerOptions},
+            image::CreateImageOptions,
+            service::{HostConfig, PortBinding},
+        };
+        use std::collections::HashMap;
+
+        const HTPASSWD: &str = "test:$2b$12$77S6uVX2diPf9.oXzM3jZuNwne.CQwqY377tVIbKAWP2GXe1TAnoO";
+
+        docker
+            .create_image(
+                Some(CreateImageOptions {
+                    from_image: "registry",
+                    tag: "2",
+                    ..Default::default()
+                }),
+                None,
+                None,
+            )
+            .try_collect::<Vec<_>>()
+            .await
+            .unwrap();
+
+        let port_bindings = HashMap::from([(
+            "5000/tcp".to_owned(),
+            Some(vec![PortBinding {
+                host_ip: Some("127.0.0.1".to_owned()),
+                host_port: Some(port.to_string()),
+            }]),
+        )]);
+        let container = docker
+            .create_container(
+                Some(CreateContainerOptions {
+                    name: format!("cinnabar-test-registry-{}", std::process::id()),
+                    platform: None,
+                }),
+                Config {
+                    image: Some("registry:2"),
+                    env: Some(vec![
+                        format!("HTPASSWD={HTPASSWD}").as_str(),
+                        "REGISTRY_AUTH=htpasswd",
+                        "REGISTRY_AUTH_HTPASSWD_REALM=Registry",
+                        "REGISTRY_AUTH_HTPASSWD_PATH=/auth/htpasswd",
+                    ]),
+                    entrypoint: Some(vec!["/bin/sh", "-c"]),
+                    cmd: Some(vec![
+                        "mkdir -p /auth && echo \"$HTPASSWD\" > /auth/htpasswd \
+                         && exec /entrypoint.sh /etc/docker/registry/config.yml",
+                    ]),
+                    exposed_ports: Some(HashMap::from([("5000/tcp", HashMap::new())])),
+                    host_config: Some(HostConfig {
+                        port_bindings: Some(port_bindings),
                         ..Default::default()
                     }),
-                    None,
-                    step.configuration.image.hostname.as_deref().and_then(
-                        |hostname| match hostname {
-                            _ => None,
-                        },
-                    ),
+                    ..Default::default()
+                },
+            )
+            .await
+            .unwrap()
+            .id;
+        docker
+            .start_container::<String>(&container, None)
+            .await
+            .unwrap();
+
+        container
+    }
+
+    /// Pushes `alpine` into the registry as `cinnabar-test` and removes it locally again,
+    /// so it can only be pulled from the registry
+    async fn push_test_image(
+        docker: &Docker,
+        image: &str,
+        credentials: bollard::auth::DockerCredentials,
+    ) {
+        use bollard::image::{
+            CreateImageOptions, PushImageOptions, RemoveImageOptions, TagImageOptions,
+        };
+
+        docker
+            .create_image(
+                Some(CreateImageOptions {
+                    from_image: "alpine",
+                    tag: "latest",
+                    ..Default::default()
+                }),
+                None,
+                None,
+            )
+            .try_collect::<Vec<_>>()
+            .await
+            .unwrap();
+        docker
+            .tag_image(
+                "alpine:latest",
+                Some(TagImageOptions {
+                    repo: image,
+                    tag: "latest",
+                }),
+            )
+            .await
+            .unwrap();
+
+        // The registry takes a moment to start listening
+        let mut attempts = 0;
+        loop {
+            let pushed = docker
+                .push_image(
+                    image,
+                    Some(PushImageOptions { tag: "latest" }),
+                    Some(credentials.clone()),
                 )
                 .try_collect::<Vec<_>>()
-                .await?;
+                .await;
 
-        let image_status = image.last().unwrap().status.as_ref().unwrap();
+            match pushed {
+                Ok(output) if output.iter().all(|info| info.error.is_none()) => break,
+                result if attempts >= 20 => panic!("Failed to push test image: {result:?}"),
+                _ => {
+                    attempts += 1;
+                    tokio::time::sleep(Duration::from_millis(500)).await;
+                }
+            }
+        }
 
-        println!("{image_status}");
+        docker
+            .remove_image(
+                &format!("{image}:latest"),
+                Some(RemoveImageOptions {
+                    force: true,
+                    ..Default::default()
+                }),
+                None,
+            )
+            .await
+            .unwrap();
+    }
 
-        Ok(())
+    #[tokio::test]
+    #[ignore = "needs a Docker daemon, run with `cargo test -- --ignored`"]
+    async fn pull_image_should_authenticate_against_private_registry() {
+        use bollard::container::RemoveContainerOptions;
+        use domain::{Branch, PipelineConfiguration, PipelineId, Trigger, TriggerEvent};
+        use serde::{de::IntoDeserializer, Deserialize};
+
+        use crate::{
+            cancellation::{Cancellations, RunGroup},
+            registry::RegistryCredentials,
+        };
+
+        /// Only provides registry credentials, everything else is ignored
+        struct RegistryHost(RegistryCredentials);
+
+        impl RunnerHost for RegistryHost {
+            fn step_secrets(
+                &self,
+                _: &Pipeline,
+                _: &Step,
+            ) -> Result<std::collections::BTreeMap<String, SecretString>, Error> {
+                Ok(Default::default())
+            }
+
+            fn registry_credentials(&self, _: &Pipeline) -> Result<RegistryCredentials, Error> {
+                Ok(self.0.clone())
+            }
+
+            fn update_step_status(
+                &self,
+                _: &Pipeline,
+                _: &Step,
+                _: PipelineStatus,
+                _: Option<i64>,
+                _: Option<&str>,
+            ) -> Result<(), Error> {
+                Ok(())
+            }
+
+            fn store_logs(&self, _: &Pipeline, _: &Step, _: &[LogLine]) -> Result<(), Error> {
+                Ok(())
+            }
+
+            fn store_artifact(
+                &self,
+                _: &Pipeline,
+                _: &Step,
+                _: &str,
+                _: std::path::PathBuf,
+            ) -> Result<(), Error> {
+                Ok(())
+            }
+        }
+
+        const PORT: u16 = 5055;
+
+        let docker = Docker::connect_with_socket_defaults().unwrap();
+        let registry = start_registry(&docker, PORT).await;
+        let image_name = format!("localhost:{PORT}/cinnabar-test");
+        let credentials = RegistryCredentials::parse(&format!(
+            r#"{{"auths": {{"localhost:{PORT}": {{"username": "test", "password": "test"}}}}}}"#
+        ))
+        .unwrap();
+        let image = DockerImageReference::deserialize(
+            format!("{image_name}:latest").as_str().into_deserializer(),
+        )
+        .map_err(|err: serde::de::value::Error| err)
+        .unwrap();
+
+        push_test_image(&docker, &image_name, credentials.for_image(&image).unwrap()).await;
+
+        let trigger = Trigger {
+            repository_owner: "Owner".to_owned(),
+            repository_name: "Repo".to_owned(),
+            installation_id: 1,
+            event: TriggerEvent::Push {
+                branch: Branch {
+                    name: "main".to_owned(),
+                    commit: "abc123".to_owned(),
+                },
+                before: None,
+            },
+        };
+        let configuration = PipelineConfiguration {
+            name: "Build".to_owned(),
+            trigger: vec![],
+            clone: None,
+            timeout: None,
+            cancel_previous: false,
+            runs_on: None,
+            matrix: None,
+            services: None,
+            environment: None,
+            steps: vec![],
+        };
+        let mut pipeline = Pipeline::new(
+            PipelineId(1),
+            trigger,
+            configuration,
+            chrono::Utc::now().naive_utc(),
+        );
+        let cancellation = Cancellations::default().register(
+            pipeline.id,
+            RunGroup::new(&pipeline.trigger, &pipeline.configuration.name),
+        );
+        let caches = CacheStore::new(std::env::temp_dir().join("cinnabar-test-registry"), 0);
+        let resources = ResourcesConfig {
+            defaults: Default::default(),
+            maximums: Default::default(),
+        };
+        let access_token = SecretString::new("token".to_owned());
+
+        let mut pull = async |registries: RegistryCredentials| {
+            let host = RegistryHost(registries);
+            let runner = PipelineRunner {
+                docker: &docker,
+                access_token: &access_token,
+                host: &host,
+                cancellation: &cancellation,
+                owner: labels::BACKEND_OWNER,
+                caches: &caches,
+                resources: &resources,
+                default_branch: None,
+                pipeline: &mut pipeline,
+            };
+
+            runner.pull_image(&image).await
+        };
+
+        let anonymous = pull(RegistryCredentials::default()).await;
+        let authenticated = pull(credentials).await;
+
+        let options = RemoveContainerOptions {
+            force: true,
+            ..Default::default()
+        };
+        docker
+            .remove_container(&registry, Some(options))
+            .await
+            .unwrap();
+
+        assert!(anonymous.is_err());
+        authenticated.unwrap();
+    }
+
+    #[tokio::test]
+    #[ignore = "needs a Docker daemon, run with `cargo test -- --ignored`"]
+    async fn wait_until_healthy_should_give_up_on_service_that_never_becomes_healthy() {
+        use bollard::image::CreateImageOptions;
+        use domain::{
+            Branch, PipelineConfiguration, PipelineId, ServiceConfiguration, Trigger, TriggerEvent,
+        };
+        use serde::{de::IntoDeserializer, Deserialize};
+
+        let docker = Docker::connect_with_socket_defaults().unwrap();
+
+        docker
+            .create_image(
+                Some(CreateImageOptions {
+                    from_image: "alpine",
+                    tag: "3",
+                    ..Default::default()
+                }),
+                None,
+                None,
+            )
+            .try_collect::<Vec<_>>()
+            .await
+            .unwrap();
+
+        let trigger = Trigger {
+            repository_owner: "Owner".to_owned(),
+            repository_name: "Repo".to_owned(),
+            installation_id: 1,
+            event: TriggerEvent::Push {
+                branch: Branch {
+                    name: "main".to_owned(),
+                    commit: "abc123".to_owned(),
+                },
+                before: None,
+            },
+        };
+        let configuration = PipelineConfiguration {
+            name: "Build".to_owned(),
+            trigger: vec![],
+            clone: None,
+            timeout: None,
+            cancel_previous: false,
+            runs_on: None,
+            matrix: None,
+            services: None,
+            environment: None,
+            steps: vec![],
+        };
+        let pipeline = Pipeline::new(
+            PipelineId(2),
+            trigger,
+            configuration,
+            chrono::Utc::now().naive_utc(),
+        );
+        // Docker keeps the check starting for a minute and a half before giving up on it
+        let service = ServiceConfiguration {
+            name: "never-healthy".to_owned(),
+            image: DockerImageReference::deserialize("alpine:3".into_deserializer())
+                .map_err(|err: serde::de::value::Error| err)
+                .unwrap(),
+            environment: None,
+            command: Some(vec!["sleep".to_owned(), "300".to_owned()]),
+            health_check: Some(HealthCheckConfiguration {
+                command: "false".to_owned(),
+                interval: Some(Timeout(Duration::from_secs(1))),
+                timeout: None,
+                retries: Some(90),
+            }),
+        };
+        let labels = ResourceLabels::new(labels::BACKEND_OWNER, pipeline.id);
+        let network = Network::create(&docker, "cinnabar-test-health".to_owned(), &labels)
+            .await
+            .unwrap();
+        let container = Container::start_service(&docker, &pipeline, &service, &network, &labels)
+            .await
+            .unwrap();
+
+        let result = container
+            .wait_until_healthy(&service.name, Duration::from_secs(3))
+            .await;
+
+        container.remove_forced().await.unwrap();
+        network.remove().await.unwrap();
+
+        let error = result.unwrap_err().to_string();
+        assert!(error.contains("did not become healthy within 3 seconds"));
     }
 }
@@ -0,0 +1,32 @@
+use super::error::RunnerError as Error;
+use super::labels::ResourceLabels;
+use bollard::{network::CreateNetworkOptions, Docker};
+
+pub struct Network<'a> {
+    pub name: String,
+    docker: &'a Docker,
+}
+
+impl<'a> Network<'a> {
+    pub async fn create(
+        docker: &'a Docker,
+        name: String,
+        labels: &ResourceLabels,
+    ) -> Result<Self, Error> {
+        let network = docker
+            .create_network(CreateNetworkOptions {
+                name: name.as_str(),
+                check_duplicate: true,
+                labels: labels.to_map(),
+                ..Default::default()
+            })
+            .await
+            .map(|_| Self { docker, name })?;
+
+        Ok(network)
+    }
+
+    pub async fn remove(&self) -> Result<(), Error> {
+        Ok(self.docker.remove_network(&self.name).await?)
+    }
+}
@@ -1,4 +1,5 @@
 use super::error::RunnerError as Error;
+use super::labels::ResourceLabels;
 use bollard::{volume::CreateVolumeOptions, Docker};
 
 pub struct Volume<'a> {
@@ -7,10 +8,15 @@ pub struct Volume<'a> {
 }
 
 impl<'a> Volume<'a> {
-    pub async fn create(docker: &'a Docker, name: String) -> Result<Self, Error> {
+    pub async fn create(
+        docker: &'a Docker,
+        name: String,
+        labels: &ResourceLabels,
+    ) -> Result<Self, Error> {
         let volume = docker
             .create_volume(CreateVolumeOptions {
                 name: name.as_str(),
+                labels: labels.to_map(),
                 ..Default::default()
             })
             .await
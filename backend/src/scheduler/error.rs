@@ -0,0 +1,11 @@
+use domain::repositories::RepositoryError;
+use source_control::github::error::GitHubError;
+use thiserror::Error;
+
+#[derive(Debug, Error)]
+pub enum SchedulerError {
+    #[error(transparent)]
+    GitHub(#[from] GitHubError),
+    #[error(transparent)]
+    Repository(#[from] RepositoryError),
+}
@@ -0,0 +1,432 @@
+use std::{
+    collections::{HashMap, HashSet},
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use chrono::{DateTime, Utc};
+use domain::{
+    repositories::{Repositories, ScheduleKey},
+    Branch, CronSchedule, Trigger, TriggerConfiguration, TriggerEvent,
+};
+use source_control::{
+    github::{GitHub, GitHubInstallation},
+    Repository, SourceControl, SourceControlInstallation,
+};
+use tokio::time::{Instant, MissedTickBehavior};
+
+use self::error::SchedulerError;
+use crate::{
+    context::Context,
+    orchestrator::{find_pipeline_files, handle_trigger},
+    parser::parse_pipeline,
+};
+
+pub mod error;
+
+const INTERVAL: Duration = Duration::from_secs(60);
+
+/// All repositories are listed again this often, to pick up new installations and
+/// pushes whose webhook never arrived
+const LIST_INTERVAL: Duration = Duration::from_secs(60 * 60);
+
+/// Repositories whose schedules have to be read again, e.g. after a push
+#[derive(Clone, Default)]
+pub struct ScheduleRefresh {
+    repositories: Arc<Mutex<HashSet<(String, String)>>>,
+}
+
+impl ScheduleRefresh {
+    pub fn request(&self, owner: &str, name: &str) {
+        self.repositories
+            .lock()
+            .unwrap()
+            .insert((owner.to_owned(), name.to_owned()));
+    }
+
+    fn take(&self) -> HashSet<(String, String)> {
+        std::mem::take(&mut self.repositories.lock().unwrap())
+    }
+}
+
+/// The schedules of every repository, by owner and name
+#[derive(Default)]
+struct Schedules {
+    repositories: HashMap<(String, String), RepositorySchedules>,
+    listed_at: Option<Instant>,
+}
+
+struct RepositorySchedules {
+    installation_id: u64,
+    repository: Repository,
+    /// Commit of the default branch the schedules were read at
+    commit: String,
+    schedules: Vec<Schedule>,
+}
+
+/// A schedule trigger of a pipeline
+struct Schedule {
+    key: ScheduleKey,
+    timezone: Option<String>,
+    schedule: CronSchedule,
+}
+
+/// Pipelines whose schedules are due together are started by a single trigger
+#[derive(PartialEq, Eq, Hash)]
+struct DueSchedule {
+    branch: String,
+    cron: String,
+    timezone: Option<String>,
+}
+
+/// Periodically starts the pipelines with a `schedule` trigger that are due.
+///
+/// Schedules are read from the pipeline files on the default branch of every
+/// repository the GitHub app is installed in. They are kept until a push to the
+/// repository or the next listing of all repositories finds a new commit on the
+/// default branch. The time a schedule last fired is stored in the database, so
+/// restarts neither skip nor repeat a run.
+pub async fn run(context: Context) {
+    let mut interval = tokio::time::interval(INTERVAL);
+    interval.set_missed_tick_behavior(MissedTickBehavior::Skip);
+    let mut schedules = Schedules::default();
+
+    loop {
+        interval.tick().await;
+
+        if let Err(err) = refresh_schedules(&context, &mut schedules).await {
+            println!("Failed to read scheduled pipelines: {err}");
+        }
+
+        if let Err(err) = trigger_due_schedules(&context, &schedules).await {
+            println!("Failed to check scheduled pipelines: {err}");
+        }
+    }
+}
+
+async fn refresh_schedules(
+    context: &Context,
+    schedules: &mut Schedules,
+) -> Result<(), SchedulerError> {
+    let changed = context.schedule_refresh.take();
+    let list = schedules
+        .listed_at
+        .is_none_or(|listed_at| listed_at.elapsed() >= LIST_INTERVAL);
+
+    if !list && changed.is_empty() {
+        return Ok(());
+    }
+
+    let github = GitHub::build(
+        context.config.github.app_id,
+        &context.config.github.private_key,
+    )?;
+
+    let repositories = if list {
+        let mut repositories = vec![];
+
+        for installation_id in github.list_installations().await? {
+            for repository in github.list_repositories(installation_id).await? {
+                repositories.push((installation_id, repository));
+            }
+        }
+
+        let listed: HashSet<_> = repositories
+            .iter()
+            .map(|(_, repository)| (repository.owner.clone(), repository.name.clone()))
+            .collect();
+        schedules
+            .repositories
+            .retain(|repository, _| listed.contains(repository));
+        schedules.listed_at = Some(Instant::now());
+
+        repositories
+    } else {
+        // Repositories that are not known yet are picked up by the next listing
+        changed
+            .iter()
+            .filter_map(|repository| schedules.repositories.get(repository))
+            .map(|cached| (cached.installation_id, cached.repository.clone()))
+            .collect()
+    };
+
+    for (installation_id, repository) in repositories {
+        if let Err(err) =
+            refresh_repository_schedules(&github, installation_id, &repository, schedules).await
+        {
+            println!(
+                "Failed to read scheduled pipelines of {}/{}: {err}",
+                repository.owner, repository.name
+            );
+        }
+    }
+
+    Ok(())
+}
+
+/// Reads the schedules of the default branch of the repository, unless they were
+/// already read at its current commit
+async fn refresh_repository_schedules(
+    github: &GitHub,
+    installation_id: u64,
+    repository: &Repository,
+    schedules: &mut Schedules,
+) -> Result<(), SchedulerError> {
+    let installation = github
+        .get_installation(&repository.owner, &repository.name, installation_id)
+        .await?;
+    let commit = installation
+        .resolve_branch(&repository.default_branch)
+        .await?;
+    let key = (repository.owner.clone(), repository.name.clone());
+
+    if schedules
+        .repositories
+        .get(&key)
+        .is_some_and(|cached| cached.commit == commit)
+    {
+        return Ok(());
+    }
+
+    let mut repository_schedules = vec![];
+
+    for file in find_pipeline_files(&commit, &installation).await? {
+        let Ok(configuration) = parse_pipeline(&file, &installation).await else {
+            continue;
+        };
+
+        for trigger in &configuration.trigger {
+            let TriggerConfiguration::Schedule {
+                cron,
+                branch,
+                timezone,
+            } = trigger
+            else {
+                continue;
+            };
+
+            let Ok(schedule) = CronSchedule::parse(cron, timezone.as_deref()) else {
+                continue;
+            };
+
+            let key = ScheduleKey {
+                repository_owner: repository.owner.clone(),
+                repository_name: repository.name.clone(),
+                pipeline: configuration.name.clone(),
+                branch: branch.clone(),
+                cron: cron.clone(),
+            };
+
+            repository_schedules.push(Schedule {
+                key,
+                timezone: timezone.clone(),
+                schedule,
+            });
+        }
+    }
+
+    schedules.repositories.insert(
+        key,
+        RepositorySchedules {
+            installation_id,
+            repository: repository.clone(),
+            commit,
+            schedules: repository_schedules,
+        },
+    );
+
+    Ok(())
+}
+
+/// Only talks to GitHub for repositories with a schedule that is due
+async fn trigger_due_schedules(
+    context: &Context,
+    schedules: &Schedules,
+) -> Result<(), SchedulerError> {
+    let now = Utc::now();
+    let mut github = None;
+
+    for cached in schedules.repositories.values() {
+        let mut due_schedules = HashSet::new();
+
+        for Schedule {
+            key,
+            timezone,
+            schedule,
+        } in &cached.schedules
+        {
+            if claim_schedule(&context.repositories, key, schedule, now)? {
+                due_schedules.insert(DueSchedule {
+                    branch: key.branch.clone(),
+                    cron: key.cron.clone(),
+                    timezone: timezone.clone(),
+                });
+            }
+        }
+
+        if due_schedules.is_empty() {
+            continue;
+        }
+
+        let github = match &mut github {
+            Some(github) => github,
+            None => github.insert(GitHub::build(
+                context.config.github.app_id,
+                &context.config.github.private_key,
+            )?),
+        };
+
+        if let Err(err) = trigger_repository_schedules(context, github, cached, due_schedules).await
+        {
+            println!(
+                "Failed to start scheduled pipelines of {}/{}: {err}",
+                cached.repository.owner, cached.repository.name
+            );
+        }
+    }
+
+    Ok(())
+}
+
+async fn trigger_repository_schedules(
+    context: &Context,
+    github: &GitHub,
+    cached: &RepositorySchedules,
+    due_schedules: HashSet<DueSchedule>,
+) -> Result<(), SchedulerError> {
+    let repository = &cached.repository;
+    let installation = github
+        .get_installation(&repository.owner, &repository.name, cached.installation_id)
+        .await?;
+
+    for due_schedule in due_schedules {
+        trigger_schedule(
+            context,
+            &installation,
+            cached.installation_id,
+            repository,
+            due_schedule,
+        )
+        .await?;
+    }
+
+    Ok(())
+}
+
+async fn trigger_schedule(
+    context: &Context,
+    installation: &GitHubInstallation,
+    installation_id: u64,
+    repository: &Repository,
+    DueSchedule {
+        branch,
+        cron,
+        timezone,
+    }: DueSchedule,
+) -> Result<(), SchedulerError> {
+    let commit = installation.resolve_branch(&branch).await?;
+
+    let trigger = Trigger {
+        repository_owner: repository.owner.clone(),
+        repository_name: repository.name.clone(),
+        installation_id,
+        event: TriggerEvent::Schedule {
+            branch: Branch {
+                name: branch,
+                commit,
+            },
+            cron,
+            timezone,
+        },
+    };
+
+    if let Err(err) = handle_trigger(trigger, context.clone()).await {
+        println!(
+            "Failed to start scheduled pipelines of {}/{}: {err}",
+            repository.owner, repository.name
+        );
+    }
+
+    Ok(())
+}
+
+/// Returns true if the schedule is due and this process is the one to trigger it
+fn claim_schedule(
+    repositories: &Repositories,
+    key: &ScheduleKey,
+    schedule: &CronSchedule,
+    now: DateTime<Utc>,
+) -> Result<bool, SchedulerError> {
+    let mut schedules = repositories.schedules.lock().unwrap();
+
+    let Some(last_triggered) = schedules.find_last_triggered(key)? else {
+        schedules.insert(key, now.naive_utc())?;
+        return Ok(false);
+    };
+
+    if !is_due(schedule, last_triggered.and_utc(), now) {
+        return Ok(false);
+    }
+
+    Ok(schedules.advance(key, last_triggered, now.naive_utc())?)
+}
+
+/// A schedule is due if it should have fired at least once since it was last triggered.
+/// Runs missed while the backend was down are caught up by a single run.
+fn is_due(schedule: &CronSchedule, last_triggered: DateTime<Utc>, now: DateTime<Utc>) -> bool {
+    schedule
+        .next_after(last_triggered)
+        .is_some_and(|next| next <= now)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn utc(value: &str) -> DateTime<Utc> {
+        DateTime::parse_from_rfc3339(value)
+            .unwrap()
+            .with_timezone(&Utc)
+    }
+
+    #[test]
+    fn is_due_should_return_true_once_schedule_time_passed() {
+        let schedule = CronSchedule::parse("0 2 * * *", None).unwrap();
+
+        assert!(is_due(
+            &schedule,
+            utc("2024-01-01T01:59:00Z"),
+            utc("2024-01-01T02:00:00Z")
+        ));
+        assert!(is_due(
+            &schedule,
+            utc("2024-01-01T01:59:00Z"),
+            utc("2024-01-03T05:00:00Z")
+        ));
+    }
+
+    #[test]
+    fn schedule_refresh_should_return_requested_repositories_once() {
+        let refresh = ScheduleRefresh::default();
+
+        refresh.request("Owner", "Repo");
+        refresh.request("Owner", "Repo");
+
+        assert_eq!(
+            refresh.take(),
+            HashSet::from([("Owner".to_owned(), "Repo".to_owned())])
+        );
+        assert!(refresh.take().is_empty());
+    }
+
+    #[test]
+    fn is_due_should_return_false_before_schedule_time() {
+        let schedule = CronSchedule::parse("0 2 * * *", None).unwrap();
+
+        assert!(!is_due(
+            &schedule,
+            utc("2024-01-01T02:00:00Z"),
+            utc("2024-01-02T01:59:59Z")
+        ));
+    }
+}
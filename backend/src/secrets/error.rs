@@ -0,0 +1,16 @@
+use domain::repositories::RepositoryError;
+use thiserror::Error;
+
+#[derive(Debug, Error)]
+pub enum SecretError {
+    #[error("SECRETS_MASTER_KEY needs to be 32 bytes encoded as hex")]
+    InvalidMasterKey,
+    #[error("Secrets are not configured, please provide SECRETS_MASTER_KEY")]
+    NotConfigured,
+    #[error("Secret \"{0}\" does not exist")]
+    NotFound(String),
+    #[error("Secret \"{0}\" could not be encrypted or decrypted")]
+    Cipher(String),
+    #[error(transparent)]
+    Repository(#[from] RepositoryError),
+}
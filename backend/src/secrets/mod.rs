@@ -0,0 +1,220 @@
+use std::collections::BTreeMap;
+
+use aes_gcm::{
+    aead::{Aead, AeadCore, KeyInit, OsRng, Payload},
+    Aes256Gcm, Key, Nonce,
+};
+use domain::{
+    repositories::Repositories, EncryptedSecret, SecretScope, StepConfiguration, Trigger,
+};
+use secrecy::{ExposeSecret, SecretString};
+
+use self::error::SecretError;
+
+pub mod error;
+
+/// Encrypts secret values with AES-256-GCM before they are stored.
+///
+/// The scope and name of a secret are authenticated along with its value,
+/// so a ciphertext can not be moved to another secret in the database.
+#[derive(Clone)]
+pub struct SecretCipher {
+    cipher: Aes256Gcm,
+}
+
+impl SecretCipher {
+    pub fn new(master_key: &SecretString) -> Result<Self, SecretError> {
+        let key = hex::decode(master_key.expose_secret().trim())
+            .ok()
+            .filter(|key| key.len() == 32)
+            .ok_or(SecretError::InvalidMasterKey)?;
+
+        Ok(Self {
+            cipher: Aes256Gcm::new(Key::<Aes256Gcm>::from_slice(&key)),
+        })
+    }
+
+    pub fn encrypt(
+        &self,
+        scope: &SecretScope,
+        name: &str,
+        value: &SecretString,
+    ) -> Result<EncryptedSecret, SecretError> {
+        let nonce = Aes256Gcm::generate_nonce(&mut OsRng);
+        let associated_data = associated_data(scope, name);
+
+        let ciphertext = self
+            .cipher
+            .encrypt(
+                &nonce,
+                Payload {
+                    msg: value.expose_secret().as_bytes(),
+                    aad: associated_data.as_bytes(),
+                },
+            )
+            .map_err(|_| SecretError::Cipher(name.to_owned()))?;
+
+        Ok(EncryptedSecret {
+            nonce: nonce.to_vec(),
+            ciphertext,
+        })
+    }
+
+    pub fn decrypt(
+        &self,
+        scope: &SecretScope,
+        name: &str,
+        secret: &EncryptedSecret,
+    ) -> Result<SecretString, SecretError> {
+        if secret.nonce.len() != 12 {
+            return Err(SecretError::Cipher(name.to_owned()));
+        }
+
+        let associated_data = associated_data(scope, name);
+
+        let value = self
+            .cipher
+            .decrypt(
+                Nonce::from_slice(&secret.nonce),
+                Payload {
+                    msg: &secret.ciphertext,
+                    aad: associated_data.as_bytes(),
+                },
+            )
+            .map_err(|_| SecretError::Cipher(name.to_owned()))?;
+
+        let value = String::from_utf8(value).map_err(|_| SecretError::Cipher(name.to_owned()))?;
+
+        Ok(SecretString::new(value))
+    }
+}
+
+fn associated_data(scope: &SecretScope, name: &str) -> String {
+    format!("{scope}/{name}")
+}
+
+/// Decrypts the secrets a step references, keyed by the environment variable they are exposed as.
+///
+/// Repository secrets take precedence over secrets of the installation. Pull requests from
+/// forks get no secrets at all, their steps run without the variables.
+pub fn resolve_step_secrets(
+    repositories: &Repositories,
+    cipher: Option<&SecretCipher>,
+    trigger: &Trigger,
+    step: &StepConfiguration,
+) -> Result<BTreeMap<String, SecretString>, SecretError> {
+    let Some(references) = step.secrets.as_ref().filter(|secrets| !secrets.is_empty()) else {
+        return Ok(BTreeMap::new());
+    };
+
+    if !trigger.event.allows_secrets() {
+        return Ok(BTreeMap::new());
+    }
+
+    let cipher = cipher.ok_or(SecretError::NotConfigured)?;
+    let mut resolved = BTreeMap::new();
+
+    for (variable, name) in references {
+        let value = find_secret(repositories, cipher, trigger, name)?
+            .ok_or_else(|| SecretError::NotFound(name.clone()))?;
+
+        resolved.insert(variable.clone(), value);
+    }
+
+    Ok(resolved)
+}
+
+/// Decrypts a secret the runner uses itself, like the credentials of registries.
+///
+/// Returns nothing if secrets are not configured, the secret does not exist or
+/// the trigger gets no secrets.
+pub fn resolve_runner_secret(
+    repositories: &Repositories,
+    cipher: Option<&SecretCipher>,
+    trigger: &Trigger,
+    name: &str,
+) -> Result<Option<SecretString>, SecretError> {
+    match cipher {
+        Some(cipher) if trigger.event.allows_secrets() => {
+            find_secret(repositories, cipher, trigger, name)
+        }
+        _ => Ok(None),
+    }
+}
+
+fn find_secret(
+    repositories: &Repositories,
+    cipher: &SecretCipher,
+    trigger: &Trigger,
+    name: &str,
+) -> Result<Option<SecretString>, SecretError> {
+    let scopes = [
+        SecretScope::Repository {
+            owner: trigger.repository_owner.clone(),
+            name: trigger.repository_name.clone(),
+        },
+        SecretScope::Installation(trigger.installation_id),
+    ];
+
+    let mut secrets = repositories.secrets.lock().unwrap();
+
+    for scope in &scopes {
+        if let Some(secret) = secrets.find(scope, name)? {
+            return Ok(Some(cipher.decrypt(scope, name, &secret)?));
+        }
+    }
+
+    Ok(None)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn cipher() -> SecretCipher {
+        SecretCipher::new(&SecretString::new("ab".repeat(32))).unwrap()
+    }
+
+    fn scope() -> SecretScope {
+        SecretScope::Repository {
+            owner: "Owner".to_owned(),
+            name: "Repo".to_owned(),
+        }
+    }
+
+    #[test]
+    fn decrypt_should_return_encrypted_value() {
+        let cipher = cipher();
+        let value = SecretString::new("hunter2".to_owned());
+
+        let encrypted = cipher.encrypt(&scope(), "password", &value).unwrap();
+
+        assert_ne!(encrypted.ciphertext, b"hunter2");
+        assert_eq!(
+            cipher
+                .decrypt(&scope(), "password", &encrypted)
+                .unwrap()
+                .expose_secret(),
+            "hunter2"
+        );
+    }
+
+    #[test]
+    fn decrypt_should_fail_for_other_secret_name_or_scope() {
+        let cipher = cipher();
+        let value = SecretString::new("hunter2".to_owned());
+
+        let encrypted = cipher.encrypt(&scope(), "password", &value).unwrap();
+
+        assert!(cipher.decrypt(&scope(), "token", &encrypted).is_err());
+        assert!(cipher
+            .decrypt(&SecretScope::Installation(789), "password", &encrypted)
+            .is_err());
+    }
+
+    #[test]
+    fn new_should_reject_master_key_of_wrong_length() {
+        assert!(SecretCipher::new(&SecretString::new("abcd".to_owned())).is_err());
+        assert!(SecretCipher::new(&SecretString::new("not hex".to_owned())).is_err());
+    }
+}
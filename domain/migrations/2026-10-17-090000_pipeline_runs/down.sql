@@ -0,0 +1,6 @@
+DROP TABLE steps;
+DROP TABLE pipelines;
+CREATE TABLE pipelines (
+  id INTEGER NOT NULL PRIMARY KEY AUTOINCREMENT,
+  status VARCHAR(16) NOT NULL
+)
@@ -0,0 +1,30 @@
+-- The initial table only stored the status of each pipeline. Without a trigger or
+-- configuration those rows can not fill the new columns, so existing pipelines are
+-- dropped rather than migrated.
+DROP TABLE pipelines;
+CREATE TABLE pipelines (
+  id INTEGER NOT NULL PRIMARY KEY AUTOINCREMENT,
+  status VARCHAR(16) NOT NULL,
+  repository_owner TEXT NOT NULL,
+  repository_name TEXT NOT NULL,
+  event VARCHAR(32) NOT NULL,
+  branch TEXT NOT NULL,
+  commit_sha VARCHAR(64) NOT NULL,
+  trigger TEXT NOT NULL,
+  configuration_name TEXT NOT NULL,
+  configuration TEXT NOT NULL,
+  created_at TIMESTAMP NOT NULL,
+  started_at TIMESTAMP,
+  finished_at TIMESTAMP
+);
+CREATE INDEX pipelines_repository ON pipelines (repository_owner, repository_name);
+CREATE TABLE steps (
+  pipeline_id INTEGER NOT NULL REFERENCES pipelines (id) ON DELETE CASCADE,
+  id INTEGER NOT NULL,
+  name TEXT NOT NULL,
+  status VARCHAR(16) NOT NULL,
+  exit_code BIGINT,
+  started_at TIMESTAMP,
+  finished_at TIMESTAMP,
+  PRIMARY KEY (pipeline_id, id)
+)
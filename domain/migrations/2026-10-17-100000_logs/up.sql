@@ -0,0 +1,10 @@
+CREATE TABLE logs (
+  pipeline_id INTEGER NOT NULL,
+  step_id INTEGER NOT NULL,
+  number INTEGER NOT NULL,
+  timestamp TIMESTAMP NOT NULL,
+  stream VARCHAR(16) NOT NULL,
+  content TEXT NOT NULL,
+  PRIMARY KEY (pipeline_id, step_id, number),
+  FOREIGN KEY (pipeline_id, step_id) REFERENCES steps (pipeline_id, id) ON DELETE CASCADE
+)
@@ -0,0 +1,9 @@
+CREATE TABLE schedules (
+  repository_owner TEXT NOT NULL,
+  repository_name TEXT NOT NULL,
+  pipeline TEXT NOT NULL,
+  branch TEXT NOT NULL,
+  cron TEXT NOT NULL,
+  last_triggered_at TIMESTAMP NOT NULL,
+  PRIMARY KEY (repository_owner, repository_name, pipeline, branch, cron)
+)
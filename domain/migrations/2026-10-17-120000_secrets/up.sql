@@ -0,0 +1,9 @@
+CREATE TABLE secrets (
+  scope TEXT NOT NULL,
+  name TEXT NOT NULL,
+  nonce BLOB NOT NULL,
+  ciphertext BLOB NOT NULL,
+  created_at TIMESTAMP NOT NULL,
+  updated_at TIMESTAMP NOT NULL,
+  PRIMARY KEY (scope, name)
+)
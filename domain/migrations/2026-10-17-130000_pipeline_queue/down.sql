@@ -0,0 +1 @@
+DROP INDEX pipelines_status;
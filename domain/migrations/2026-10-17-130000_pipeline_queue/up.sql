@@ -0,0 +1 @@
+CREATE INDEX pipelines_status ON pipelines (status);
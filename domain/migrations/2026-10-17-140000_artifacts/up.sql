@@ -0,0 +1,12 @@
+CREATE TABLE artifacts (
+  id INTEGER NOT NULL PRIMARY KEY AUTOINCREMENT,
+  pipeline_id INTEGER NOT NULL,
+  step_id INTEGER NOT NULL,
+  path TEXT NOT NULL,
+  digest VARCHAR(64) NOT NULL,
+  size BIGINT NOT NULL,
+  created_at TIMESTAMP NOT NULL,
+  FOREIGN KEY (pipeline_id, step_id) REFERENCES steps (pipeline_id, id) ON DELETE CASCADE
+);
+CREATE INDEX artifacts_pipeline ON artifacts (pipeline_id);
+CREATE INDEX artifacts_created_at ON artifacts (created_at);
@@ -0,0 +1 @@
+ALTER TABLE steps DROP COLUMN failure_reason
@@ -0,0 +1 @@
+ALTER TABLE steps ADD COLUMN failure_reason TEXT
@@ -0,0 +1,28 @@
+use std::path::{Component, Path};
+
+use chrono::NaiveDateTime;
+use serde::Serialize;
+
+use super::pipeline::{PipelineId, StepId};
+
+/// Files a step collected after it passed, stored as a tar archive
+#[derive(Serialize, Clone, Debug, PartialEq, Eq)]
+pub struct Artifact {
+    pub id: i32,
+    pub pipeline_id: PipelineId,
+    pub step_id: StepId,
+    /// Path as declared by the step, relative to the workspace
+    pub path: String,
+    /// SHA-256 of the archive, which it is stored under
+    pub digest: String,
+    pub size: i64,
+    pub created_at: NaiveDateTime,
+}
+
+/// Artifact paths have to stay inside of the workspace
+pub fn is_valid_artifact_path(path: &str) -> bool {
+    !path.is_empty()
+        && Path::new(path)
+            .components()
+            .all(|component| matches!(component, Component::Normal(_) | Component::CurDir))
+}
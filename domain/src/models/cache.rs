@@ -0,0 +1,153 @@
+use serde::{Deserialize, Serialize};
+
+/// Paths restored before a step runs and saved once it passed, shared between runs
+/// of the same repository.
+///
+/// A plain path is shorthand for a cache of that path keyed by the path itself.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+#[serde(from = "RawCacheConfiguration")]
+pub struct CacheConfiguration {
+    /// Template of the key the cache is saved under, e.g. `cargo-${{ hashFiles('Cargo.lock') }}`
+    pub key: String,
+    /// Templates of key prefixes tried in order if there is no cache with the exact key,
+    /// the most recently used cache starting with a prefix is restored
+    pub restore_keys: Option<Vec<String>>,
+    /// Paths relative to the workspace
+    pub paths: Vec<String>,
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum RawCacheConfiguration {
+    Path(String),
+    Keyed {
+        key: String,
+        restore_keys: Option<Vec<String>>,
+        paths: Vec<String>,
+    },
+}
+
+impl From<RawCacheConfiguration> for CacheConfiguration {
+    fn from(raw: RawCacheConfiguration) -> Self {
+        match raw {
+            RawCacheConfiguration::Path(path) => CacheConfiguration {
+                key: path.clone(),
+                restore_keys: None,
+                paths: vec![path],
+            },
+            RawCacheConfiguration::Keyed {
+                key,
+                restore_keys,
+                paths,
+            } => CacheConfiguration {
+                key,
+                restore_keys,
+                paths,
+            },
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum CacheKeySegment {
+    Text(String),
+    /// A hash over the contents of the files, which are relative to the workspace
+    HashFiles(Vec<String>),
+}
+
+/// Splits a key template into text and `${{ hashFiles('<path>', ...) }}` expressions
+pub fn parse_cache_key(template: &str) -> Result<Vec<CacheKeySegment>, String> {
+    let mut segments = vec![];
+    let mut rest = template;
+
+    while let Some(start) = rest.find("${{") {
+        let end = rest[start..]
+            .find("}}")
+            .ok_or_else(|| "unterminated expression".to_owned())?;
+        let expression = rest[start + 3..start + end].trim();
+
+        if start > 0 {
+            segments.push(CacheKeySegment::Text(rest[..start].to_owned()));
+        }
+
+        segments.push(CacheKeySegment::HashFiles(parse_hash_files(expression)?));
+        rest = &rest[start + end + 2..];
+    }
+
+    if !rest.is_empty() {
+        segments.push(CacheKeySegment::Text(rest.to_owned()));
+    }
+
+    Ok(segments)
+}
+
+fn parse_hash_files(expression: &str) -> Result<Vec<String>, String> {
+    let unknown = || format!("unknown expression \"{expression}\"");
+    let arguments = expression
+        .strip_prefix("hashFiles(")
+        .and_then(|expression| expression.strip_suffix(')'))
+        .ok_or_else(unknown)?;
+
+    let paths = arguments
+        .split(',')
+        .map(|argument| {
+            let argument = argument.trim();
+            argument
+                .strip_prefix('\'')
+                .and_then(|argument| argument.strip_suffix('\''))
+                .filter(|path| !path.is_empty())
+                .map(str::to_owned)
+                .ok_or_else(|| format!("invalid argument {argument} of hashFiles"))
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+
+    Ok(paths)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn plain_path_should_be_keyed_by_itself() {
+        let caches: Vec<CacheConfiguration> = serde_json::from_str(
+            r#"["target", { "key": "cargo", "restore_keys": ["car"], "paths": ["a", "b"] }]"#,
+        )
+        .unwrap();
+
+        assert_eq!(
+            caches,
+            vec![
+                CacheConfiguration {
+                    key: "target".to_owned(),
+                    restore_keys: None,
+                    paths: vec!["target".to_owned()],
+                },
+                CacheConfiguration {
+                    key: "cargo".to_owned(),
+                    restore_keys: Some(vec!["car".to_owned()]),
+                    paths: vec!["a".to_owned(), "b".to_owned()],
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn parse_cache_key_should_split_hash_files_expressions() {
+        assert_eq!(
+            parse_cache_key("cargo-${{ hashFiles('Cargo.lock', 'a/Cargo.lock') }}-v1"),
+            Ok(vec![
+                CacheKeySegment::Text("cargo-".to_owned()),
+                CacheKeySegment::HashFiles(vec![
+                    "Cargo.lock".to_owned(),
+                    "a/Cargo.lock".to_owned()
+                ]),
+                CacheKeySegment::Text("-v1".to_owned()),
+            ])
+        );
+
+        assert!(parse_cache_key("cargo-${{ branch }}").is_err());
+        assert!(parse_cache_key("cargo-${{ hashFiles(Cargo.lock) }}").is_err());
+        assert!(parse_cache_key("cargo-${{ hashFiles('Cargo.lock')").is_err());
+    }
+}
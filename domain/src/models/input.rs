@@ -0,0 +1,180 @@
+use std::collections::BTreeMap;
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use thiserror::Error;
+
+/// An input of a manually started pipeline, declared on its `manual` trigger
+#[derive(Serialize, Deserialize, Clone, PartialEq, Eq, Debug)]
+pub struct InputConfiguration {
+    #[serde(rename = "type", default)]
+    pub input_type: InputType,
+    pub description: Option<String>,
+    #[serde(default)]
+    pub required: bool,
+    pub default: Option<Value>,
+    /// The allowed values of a `choice` input
+    pub options: Option<Vec<String>>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Debug, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum InputType {
+    #[default]
+    String,
+    Boolean,
+    Number,
+    Choice,
+}
+
+#[derive(Debug, Error, PartialEq, Eq)]
+pub enum InputError {
+    #[error("Unknown input \"{0}\"")]
+    Unknown(String),
+    #[error("Missing required input \"{0}\"")]
+    Missing(String),
+    #[error("Input \"{input}\" must be a {expected}")]
+    InvalidType {
+        input: String,
+        expected: &'static str,
+    },
+    #[error("Input \"{input}\" must be one of {options:?}")]
+    InvalidChoice { input: String, options: Vec<String> },
+}
+
+impl InputConfiguration {
+    /// Checks the value against the declared type and converts it to the string
+    /// steps receive in their environment
+    pub fn convert(&self, name: &str, value: &Value) -> Result<String, InputError> {
+        let invalid_type = |expected| InputError::InvalidType {
+            input: name.to_owned(),
+            expected,
+        };
+
+        match (self.input_type, value) {
+            (InputType::String, Value::String(value)) => Ok(value.clone()),
+            (InputType::String, _) => Err(invalid_type("string")),
+            (InputType::Boolean, Value::Bool(value)) => Ok(value.to_string()),
+            (InputType::Boolean, _) => Err(invalid_type("boolean")),
+            (InputType::Number, Value::Number(value)) => Ok(value.to_string()),
+            (InputType::Number, _) => Err(invalid_type("number")),
+            (InputType::Choice, Value::String(value)) => {
+                let options = self.options.as_deref().unwrap_or_default();
+
+                if options.contains(value) {
+                    Ok(value.clone())
+                } else {
+                    Err(InputError::InvalidChoice {
+                        input: name.to_owned(),
+                        options: options.to_vec(),
+                    })
+                }
+            }
+            (InputType::Choice, _) => Err(invalid_type("string")),
+        }
+    }
+}
+
+/// Validates the provided inputs against the declared ones and fills in defaults.
+/// Optional inputs without a value or default are left out.
+pub fn resolve_inputs(
+    declared: &BTreeMap<String, InputConfiguration>,
+    provided: &BTreeMap<String, Value>,
+) -> Result<BTreeMap<String, String>, InputError> {
+    if let Some(name) = provided.keys().find(|name| !declared.contains_key(*name)) {
+        return Err(InputError::Unknown(name.clone()));
+    }
+
+    let mut inputs = BTreeMap::new();
+
+    for (name, input) in declared {
+        match provided.get(name).or(input.default.as_ref()) {
+            Some(value) => {
+                inputs.insert(name.clone(), input.convert(name, value)?);
+            }
+            None if input.required => return Err(InputError::Missing(name.clone())),
+            None => {}
+        }
+    }
+
+    Ok(inputs)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn declared() -> BTreeMap<String, InputConfiguration> {
+        serde_json::from_str(
+            r#"{
+                "environment": { "type": "choice", "options": ["staging", "production"], "required": true },
+                "dry_run": { "type": "boolean", "default": true },
+                "replicas": { "type": "number" },
+                "message": {}
+            }"#,
+        )
+        .unwrap()
+    }
+
+    fn provided(json: &str) -> BTreeMap<String, Value> {
+        serde_json::from_str(json).unwrap()
+    }
+
+    #[test]
+    fn resolve_inputs_should_convert_values_and_apply_defaults() {
+        let inputs = resolve_inputs(
+            &declared(),
+            &provided(r#"{ "environment": "staging", "replicas": 3 }"#),
+        )
+        .unwrap();
+
+        assert_eq!(
+            inputs,
+            BTreeMap::from([
+                ("dry_run".to_owned(), "true".to_owned()),
+                ("environment".to_owned(), "staging".to_owned()),
+                ("replicas".to_owned(), "3".to_owned()),
+            ])
+        );
+    }
+
+    #[test]
+    fn resolve_inputs_should_reject_missing_required_input() {
+        let result = resolve_inputs(&declared(), &provided("{}"));
+
+        assert_eq!(result, Err(InputError::Missing("environment".to_owned())));
+    }
+
+    #[test]
+    fn resolve_inputs_should_reject_unknown_input() {
+        let result = resolve_inputs(
+            &declared(),
+            &provided(r#"{ "environment": "staging", "force": true }"#),
+        );
+
+        assert_eq!(result, Err(InputError::Unknown("force".to_owned())));
+    }
+
+    #[test]
+    fn resolve_inputs_should_reject_wrong_type() {
+        let result = resolve_inputs(
+            &declared(),
+            &provided(r#"{ "environment": "staging", "dry_run": "yes" }"#),
+        );
+
+        assert_eq!(
+            result,
+            Err(InputError::InvalidType {
+                input: "dry_run".to_owned(),
+                expected: "boolean"
+            })
+        );
+    }
+
+    #[test]
+    fn resolve_inputs_should_reject_unknown_choice() {
+        let result = resolve_inputs(&declared(), &provided(r#"{ "environment": "local" }"#));
+
+        assert!(matches!(result, Err(InputError::InvalidChoice { .. })));
+    }
+}
@@ -0,0 +1,69 @@
+use std::str::FromStr;
+
+use chrono::NaiveDateTime;
+use diesel::{
+    backend::Backend,
+    deserialize::{self, FromSql, FromSqlRow},
+    serialize::ToSql,
+    sql_types::VarChar,
+    AsExpression,
+};
+use serde::{Deserialize, Serialize};
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct LogLine {
+    pub number: i32,
+    pub timestamp: NaiveDateTime,
+    pub stream: LogStream,
+    pub content: String,
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Debug, AsExpression, FromSqlRow)]
+#[diesel(sql_type = VarChar)]
+pub enum LogStream {
+    #[serde(rename = "stdout")]
+    Stdout,
+    #[serde(rename = "stderr")]
+    Stderr,
+}
+
+impl<DB> ToSql<VarChar, DB> for LogStream
+where
+    DB: Backend,
+    str: ToSql<VarChar, DB>,
+{
+    fn to_sql<'b>(
+        &'b self,
+        out: &mut diesel::serialize::Output<'b, '_, DB>,
+    ) -> diesel::serialize::Result {
+        match self {
+            LogStream::Stdout => "stdout".to_sql(out),
+            LogStream::Stderr => "stderr".to_sql(out),
+        }
+    }
+}
+
+impl<DB> FromSql<VarChar, DB> for LogStream
+where
+    DB: Backend,
+    *const str: FromSql<diesel::sql_types::VarChar, DB>,
+{
+    fn from_sql(bytes: <DB as Backend>::RawValue<'_>) -> diesel::deserialize::Result<Self> {
+        let string = <String as deserialize::FromSql<VarChar, DB>>::from_sql(bytes)?;
+        string
+            .parse()
+            .map_err(|_| format!("Could not parse log stream {string}").into())
+    }
+}
+
+impl FromStr for LogStream {
+    type Err = ();
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value {
+            "stdout" => Ok(LogStream::Stdout),
+            "stderr" => Ok(LogStream::Stderr),
+            _ => Err(()),
+        }
+    }
+}
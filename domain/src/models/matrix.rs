@@ -0,0 +1,284 @@
+use std::collections::BTreeMap;
+
+use serde::{de::IntoDeserializer, Deserialize, Serialize};
+
+use super::{
+    docker_image_reference::DockerImageReference, pipeline::PipelineConfiguration,
+    validation::ConfigurationError,
+};
+
+/// Upper bound of combinations, so a typo can not flood the queue
+const MAX_COMBINATIONS: usize = 256;
+
+/// Runs a pipeline once per combination of values, e.g. `{ "rust": ["stable", "beta"] }`.
+///
+/// Values are substituted into the `image`, `commands` and `environment` of steps
+/// wherever `${{ matrix.<name> }}` appears.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct MatrixConfiguration {
+    #[serde(flatten)]
+    pub axes: BTreeMap<String, Vec<String>>,
+    /// Combinations added on top of the generated ones
+    #[serde(default)]
+    pub include: Vec<BTreeMap<String, String>>,
+    /// Removes generated combinations matching all values of an entry
+    #[serde(default)]
+    pub exclude: Vec<BTreeMap<String, String>>,
+}
+
+pub type MatrixCombination = BTreeMap<String, String>;
+
+impl MatrixConfiguration {
+    pub fn combinations(&self) -> Vec<MatrixCombination> {
+        let mut combinations = vec![MatrixCombination::new()];
+
+        for (name, values) in &self.axes {
+            combinations = combinations
+                .into_iter()
+                .flat_map(|combination| {
+                    values.iter().map(move |value| {
+                        let mut combination = combination.clone();
+                        combination.insert(name.clone(), value.clone());
+                        combination
+                    })
+                })
+                .collect();
+        }
+
+        combinations.retain(|combination| {
+            !self.exclude.iter().any(|exclude| {
+                exclude
+                    .iter()
+                    .all(|(name, value)| combination.get(name) == Some(value))
+            })
+        });
+
+        for include in &self.include {
+            if !combinations.contains(include) {
+                combinations.push(include.clone());
+            }
+        }
+
+        combinations
+    }
+
+    fn validate(&self) -> Result<(), ConfigurationError> {
+        let invalid = |reason: String| ConfigurationError::InvalidMatrix(reason);
+
+        if let Some((name, _)) = self.axes.iter().find(|(_, values)| values.is_empty()) {
+            return Err(invalid(format!("\"{name}\" has no values")));
+        }
+
+        for exclude in &self.exclude {
+            if let Some(name) = exclude.keys().find(|name| !self.axes.contains_key(*name)) {
+                return Err(invalid(format!(
+                    "exclude refers to unknown value \"{name}\""
+                )));
+            }
+        }
+
+        let combinations = self.combinations();
+
+        if combinations.is_empty() || combinations == [MatrixCombination::new()] {
+            return Err(invalid("no combinations to run".to_owned()));
+        }
+
+        if combinations.len() > MAX_COMBINATIONS {
+            return Err(invalid(format!(
+                "{} combinations exceed the maximum of {MAX_COMBINATIONS}",
+                combinations.len()
+            )));
+        }
+
+        Ok(())
+    }
+}
+
+impl PipelineConfiguration {
+    /// One configuration per matrix combination named like `build (rust=stable)`,
+    /// or the configuration itself if it has no matrix
+    pub fn expand_matrix(self) -> Result<Vec<PipelineConfiguration>, ConfigurationError> {
+        let Some(matrix) = &self.matrix else {
+            return Ok(vec![self]);
+        };
+
+        matrix.validate()?;
+
+        matrix
+            .combinations()
+            .iter()
+            .map(|combination| self.with_matrix_values(combination))
+            .collect()
+    }
+
+    fn with_matrix_values(
+        &self,
+        combination: &MatrixCombination,
+    ) -> Result<PipelineConfiguration, ConfigurationError> {
+        let values = combination
+            .iter()
+            .map(|(name, value)| format!("{name}={value}"))
+            .collect::<Vec<_>>()
+            .join(", ");
+
+        let mut configuration = self.clone();
+        configuration.name = format!("{} ({values})", self.name);
+        configuration.matrix = None;
+
+        for step in &mut configuration.steps {
+            let image = substitute(&step.image.to_string(), combination)?;
+            step.image = DockerImageReference::deserialize(image.as_str().into_deserializer())
+                .map_err(|err: serde::de::value::Error| {
+                    ConfigurationError::InvalidMatrix(format!("invalid image \"{image}\": {err}"))
+                })?;
+
+            for command in step.commands.iter_mut().flatten() {
+                *command = substitute(command, combination)?;
+            }
+
+            for value in step.environment.iter_mut().flat_map(|env| env.values_mut()) {
+                *value = substitute(value, combination)?;
+            }
+
+            for cache in step.cache.iter_mut().flatten() {
+                cache.key = substitute(&cache.key, combination)?;
+
+                for restore_key in cache.restore_keys.iter_mut().flatten() {
+                    *restore_key = substitute(restore_key, combination)?;
+                }
+            }
+        }
+
+        Ok(configuration)
+    }
+}
+
+/// Replaces all `${{ matrix.<name> }}` expressions with the values of the combination
+fn substitute(text: &str, combination: &MatrixCombination) -> Result<String, ConfigurationError> {
+    let mut result = String::with_capacity(text.len());
+    let mut rest = text;
+
+    while let Some(start) = rest.find("${{") {
+        let Some(end) = rest[start..].find("}}") else {
+            break;
+        };
+
+        let expression = rest[start + 3..start + end].trim();
+        result.push_str(&rest[..start]);
+
+        match expression.strip_prefix("matrix.") {
+            Some(name) => {
+                let value = combination.get(name).ok_or_else(|| {
+                    ConfigurationError::InvalidMatrix(format!("unknown value \"{name}\""))
+                })?;
+                result.push_str(value);
+            }
+            // Other expressions are left for the commands to interpret
+            None => result.push_str(&rest[start..start + end + 2]),
+        }
+
+        rest = &rest[start + end + 2..];
+    }
+
+    result.push_str(rest);
+
+    Ok(result)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(json: &str) -> PipelineConfiguration {
+        serde_json::from_str(json).unwrap()
+    }
+
+    #[test]
+    fn combinations_should_apply_exclude_and_include() {
+        let matrix: MatrixConfiguration = serde_json::from_str(
+            r#"{
+                "rust": ["stable", "beta"],
+                "os": ["alpine", "debian"],
+                "exclude": [{ "rust": "beta", "os": "debian" }],
+                "include": [{ "rust": "nightly", "os": "alpine" }]
+            }"#,
+        )
+        .unwrap();
+
+        let combinations: Vec<String> = matrix
+            .combinations()
+            .iter()
+            .map(|combination| format!("{}-{}", combination["rust"], combination["os"]))
+            .collect();
+
+        assert_eq!(
+            combinations,
+            vec![
+                "stable-alpine",
+                "beta-alpine",
+                "stable-debian",
+                "nightly-alpine"
+            ]
+        );
+    }
+
+    #[test]
+    fn expand_matrix_should_substitute_values_into_steps() {
+        let configuration = parse(
+            r#"{
+                "name": "build",
+                "trigger": [],
+                "matrix": { "rust": ["stable", "beta"] },
+                "steps": [{
+                    "name": "test",
+                    "image": "rust:${{ matrix.rust }}-alpine",
+                    "commands": ["echo ${{matrix.rust}} ${{ env.KEEP }}"],
+                    "environment": { "TOOLCHAIN": "${{ matrix.rust }}" }
+                }]
+            }"#,
+        );
+
+        let expanded = configuration.expand_matrix().unwrap();
+
+        assert_eq!(expanded.len(), 2);
+        assert_eq!(expanded[1].name, "build (rust=beta)");
+        assert!(expanded[1].matrix.is_none());
+
+        let step = &expanded[1].steps[0];
+        assert_eq!(step.image.to_string(), "rust:beta-alpine");
+        assert_eq!(
+            step.commands,
+            Some(vec!["echo beta ${{ env.KEEP }}".to_owned()])
+        );
+        assert_eq!(step.environment.as_ref().unwrap()["TOOLCHAIN"], "beta");
+    }
+
+    #[test]
+    fn expand_matrix_should_reject_unknown_values() {
+        let configuration = parse(
+            r#"{
+                "name": "build",
+                "trigger": [],
+                "matrix": { "rust": ["stable"] },
+                "steps": [{ "name": "test", "image": "rust:${{ matrix.version }}" }]
+            }"#,
+        );
+
+        assert_eq!(
+            configuration.expand_matrix().err(),
+            Some(ConfigurationError::InvalidMatrix(
+                "unknown value \"version\"".to_owned()
+            ))
+        );
+    }
+
+    #[test]
+    fn expand_matrix_should_keep_configuration_without_matrix() {
+        let configuration = parse(r#"{ "name": "build", "trigger": [], "steps": [] }"#);
+
+        let expanded = configuration.expand_matrix().unwrap();
+
+        assert_eq!(expanded.len(), 1);
+        assert_eq!(expanded[0].name, "build");
+    }
+}
@@ -1,7 +1,33 @@
+pub mod artifact;
+pub mod cache;
 pub mod docker_image_reference;
+pub mod input;
+pub mod log;
+pub mod matrix;
+pub mod path_filter;
 pub mod pipeline;
+pub mod ref_pattern;
+pub mod resources;
+pub mod schedule;
+pub mod secret;
+pub mod service;
+pub mod timeout;
 pub mod trigger;
+pub mod validation;
 
+pub use artifact::*;
+pub use cache::*;
 pub use docker_image_reference::*;
+pub use input::*;
+pub use log::*;
+pub use matrix::*;
+pub use path_filter::*;
 pub use pipeline::*;
+pub use ref_pattern::*;
+pub use resources::*;
+pub use schedule::*;
+pub use secret::*;
+pub use service::*;
+pub use timeout::*;
 pub use trigger::*;
+pub use validation::*;
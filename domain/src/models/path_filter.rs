@@ -0,0 +1,93 @@
+use super::ref_pattern::{compile_glob, glob_matches};
+
+/// The `paths` and `paths_ignore` globs of a trigger, matched against the files changed by
+/// a push or pull request.
+///
+/// A file is relevant if it matches any of `paths` (or `paths` is not set) and none of
+/// `paths_ignore`. The filter matches if at least one changed file is relevant.
+#[derive(Clone, Copy, Debug)]
+pub struct PathFilter<'a> {
+    pub paths: Option<&'a [String]>,
+    pub paths_ignore: Option<&'a [String]>,
+}
+
+impl<'a> PathFilter<'a> {
+    pub fn matches(&self, changed_files: &[String]) -> bool {
+        changed_files.iter().any(|file| self.is_relevant(file))
+    }
+
+    fn is_relevant(&self, file: &str) -> bool {
+        let is_included = self
+            .paths
+            .is_none_or(|paths| paths.iter().any(|pattern| glob_matches(pattern, file)));
+
+        let is_ignored = self
+            .paths_ignore
+            .is_some_and(|paths| paths.iter().any(|pattern| glob_matches(pattern, file)));
+
+        is_included && !is_ignored
+    }
+
+    /// Returns the first pattern that is not a valid glob along with the reason
+    pub fn find_invalid(&self) -> Option<(&'a str, String)> {
+        self.paths
+            .into_iter()
+            .chain(self.paths_ignore)
+            .flatten()
+            .find_map(|pattern| {
+                compile_glob(pattern)
+                    .err()
+                    .map(|err| (pattern.as_str(), err.kind().to_string()))
+            })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn strings(values: &[&str]) -> Vec<String> {
+        values.iter().map(|&value| value.to_owned()).collect()
+    }
+
+    #[test]
+    fn paths_should_match_if_any_changed_file_matches() {
+        let paths = strings(&["services/api/**", "Cargo.lock"]);
+        let filter = PathFilter {
+            paths: Some(&paths),
+            paths_ignore: None,
+        };
+
+        assert!(filter.matches(&strings(&["README.md", "services/api/src/main.rs"])));
+        assert!(filter.matches(&strings(&["Cargo.lock"])));
+        assert!(!filter.matches(&strings(&["services/web/index.html"])));
+        assert!(!filter.matches(&[]));
+    }
+
+    #[test]
+    fn paths_ignore_should_not_match_if_all_changed_files_are_ignored() {
+        let paths_ignore = strings(&["docs/**", "*.md"]);
+        let filter = PathFilter {
+            paths: None,
+            paths_ignore: Some(&paths_ignore),
+        };
+
+        assert!(!filter.matches(&strings(&["README.md", "docs/setup/index.html"])));
+        assert!(filter.matches(&strings(&["README.md", "src/main.rs"])));
+        // `*` does not cross directories
+        assert!(filter.matches(&strings(&["docs2/guide.md"])));
+    }
+
+    #[test]
+    fn paths_and_paths_ignore_should_combine() {
+        let paths = strings(&["services/api/**"]);
+        let paths_ignore = strings(&["services/api/**/*.md"]);
+        let filter = PathFilter {
+            paths: Some(&paths),
+            paths_ignore: Some(&paths_ignore),
+        };
+
+        assert!(!filter.matches(&strings(&["services/api/docs/README.md"])));
+        assert!(filter.matches(&strings(&["services/api/src/lib.rs"])));
+    }
+}
@@ -1,5 +1,6 @@
-use std::{fmt::Display, str::FromStr};
+use std::{collections::BTreeMap, fmt::Display, str::FromStr};
 
+use chrono::NaiveDateTime;
 use diesel::{
     backend::Backend,
     deserialize::{self, FromSql, FromSqlRow},
@@ -9,12 +10,39 @@ use diesel::{
 };
 use serde::{Deserialize, Serialize};
 
-use super::{docker_image_reference::DockerImageReference, trigger::TriggerConfiguration};
+use super::{
+    cache::CacheConfiguration,
+    docker_image_reference::DockerImageReference,
+    input::InputConfiguration,
+    matrix::MatrixConfiguration,
+    resources::ResourcesConfiguration,
+    service::ServiceConfiguration,
+    timeout::Timeout,
+    trigger::{Trigger, TriggerConfiguration, TriggerEvent},
+};
+
+/// Name of the step that checks out the repository, reserved unless `clone` is disabled
+pub const CLONE_STEP_NAME: &str = "clone";
 
-#[derive(Serialize, Deserialize)]
+#[derive(Serialize, Deserialize, Clone)]
 pub struct PipelineConfiguration {
     pub name: String,
     pub trigger: Vec<TriggerConfiguration>,
+    /// Options of the implicit clone step, `false` disables it
+    pub clone: Option<CloneConfiguration>,
+    /// Maximum duration of the whole pipeline, steps still running are stopped when it is exceeded
+    pub timeout: Option<Timeout>,
+    /// Cancel runs of this pipeline still in progress for the same branch or pull request
+    #[serde(default)]
+    pub cancel_previous: bool,
+    /// Labels an agent needs to advertise to run the pipeline, it runs on the backend if unset
+    pub runs_on: Option<Vec<String>>,
+    /// Runs the pipeline once per combination of values, expanded before it is queued
+    pub matrix: Option<MatrixConfiguration>,
+    /// Containers started before the first step and reachable from all steps by their name
+    pub services: Option<Vec<ServiceConfiguration>>,
+    /// Environment variables of all steps, overridden by the steps' own `environment`
+    pub environment: Option<BTreeMap<String, String>>,
     pub steps: Vec<StepConfiguration>,
 }
 
@@ -23,18 +51,50 @@ pub struct StepConfiguration {
     pub name: String,
     pub image: DockerImageReference,
     pub commands: Option<Vec<String>>,
-    pub cache: Option<Vec<String>>,
+    pub cache: Option<Vec<CacheConfiguration>>,
+    pub depends_on: Option<Vec<String>>,
+    pub environment: Option<BTreeMap<String, String>>,
+    /// Secrets exposed to the step, mapping environment variable names to secret names
+    pub secrets: Option<BTreeMap<String, String>>,
+    /// Maximum duration of the step, the container is stopped when it is exceeded
+    pub timeout: Option<Timeout>,
+    /// Paths relative to the workspace that are kept as artifacts once the step passed
+    pub artifacts: Option<Vec<String>>,
+    pub resources: Option<ResourcesConfiguration>,
 }
 
-#[derive(Serialize, Deserialize)]
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+#[serde(untagged)]
+pub enum CloneConfiguration {
+    Enabled(bool),
+    Options(CloneOptions),
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct CloneOptions {
+    /// Number of commits to fetch, 0 fetches the full history. Defaults to 1
+    pub depth: Option<u32>,
+    #[serde(default)]
+    pub submodules: bool,
+    #[serde(default)]
+    pub lfs: bool,
+}
+
+#[derive(Serialize, Deserialize, Clone)]
 pub struct Pipeline {
     pub id: PipelineId,
+    pub trigger: Trigger,
     pub configuration: PipelineConfiguration,
     pub steps: Vec<Step>,
     pub status: PipelineStatus,
+    pub created_at: NaiveDateTime,
+    pub started_at: Option<NaiveDateTime>,
+    pub finished_at: Option<NaiveDateTime>,
 }
 
-#[derive(Serialize, Deserialize, Debug, AsExpression, FromSqlRow)]
+#[derive(
+    Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Hash, Debug, AsExpression, FromSqlRow,
+)]
 #[diesel(sql_type = Integer)]
 #[diesel(check_for_backend(diesel::sqlite::Sqlite))]
 pub struct PipelineId(pub i32);
@@ -53,6 +113,9 @@ where
             PipelineStatus::Running => "running".to_sql(out),
             PipelineStatus::Failed => "failed".to_sql(out),
             PipelineStatus::Passed => "passed".to_sql(out),
+            PipelineStatus::Skipped => "skipped".to_sql(out),
+            PipelineStatus::TimedOut => "timed_out".to_sql(out),
+            PipelineStatus::Cancelled => "cancelled".to_sql(out),
         }
     }
 }
@@ -94,24 +157,63 @@ where
     }
 }
 
-#[derive(Serialize, Deserialize)]
+#[derive(Serialize, Deserialize, Clone)]
 pub struct Step {
     pub id: StepId,
     pub configuration: StepConfiguration,
     pub status: PipelineStatus,
+    pub exit_code: Option<i64>,
+    /// Why the step failed if the exit code does not tell, e.g. `OOMKilled`, or why it was
+    /// skipped if a service of the pipeline failed to start
+    pub failure_reason: Option<String>,
+    pub started_at: Option<NaiveDateTime>,
+    pub finished_at: Option<NaiveDateTime>,
+}
+
+#[derive(
+    Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Hash, Debug, AsExpression, FromSqlRow,
+)]
+#[diesel(sql_type = Integer)]
+#[diesel(check_for_backend(diesel::sqlite::Sqlite))]
+pub struct StepId(pub i32);
+
+impl<DB> ToSql<Integer, DB> for StepId
+where
+    DB: Backend,
+    i32: serialize::ToSql<Integer, DB>,
+{
+    fn to_sql<'b>(
+        &'b self,
+        out: &mut diesel::serialize::Output<'b, '_, DB>,
+    ) -> diesel::serialize::Result {
+        self.0.to_sql(out)
+    }
 }
 
-#[derive(Serialize, Deserialize)]
-pub struct StepId(usize);
+impl<DB> FromSql<Integer, DB> for StepId
+where
+    DB: Backend,
+    i32: deserialize::FromSql<Integer, DB>,
+{
+    fn from_sql(bytes: <DB as Backend>::RawValue<'_>) -> diesel::deserialize::Result<Self> {
+        let result = <i32 as deserialize::FromSql<Integer, DB>>::from_sql(bytes);
+        result.map(StepId)
+    }
+}
 
 #[repr(i32)]
-#[derive(Serialize, Deserialize, PartialEq, Debug, AsExpression, FromSqlRow)]
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Debug, AsExpression, FromSqlRow)]
 #[diesel(sql_type = VarChar)]
+#[serde(rename_all = "lowercase")]
 pub enum PipelineStatus {
     Pending,
     Running,
     Passed,
     Failed,
+    Skipped,
+    #[serde(rename = "timed_out")]
+    TimedOut,
+    Cancelled,
 }
 
 impl FromStr for PipelineStatus {
@@ -123,27 +225,134 @@ impl FromStr for PipelineStatus {
             "running" => Ok(PipelineStatus::Running),
             "failed" => Ok(PipelineStatus::Failed),
             "passed" => Ok(PipelineStatus::Passed),
+            "skipped" => Ok(PipelineStatus::Skipped),
+            "timed_out" => Ok(PipelineStatus::TimedOut),
+            "cancelled" => Ok(PipelineStatus::Cancelled),
             _ => Err(()),
         }
     }
 }
 
+impl PipelineStatus {
+    pub fn is_finished(&self) -> bool {
+        match self {
+            PipelineStatus::Pending | PipelineStatus::Running => false,
+            PipelineStatus::Passed
+            | PipelineStatus::Failed
+            | PipelineStatus::Skipped
+            | PipelineStatus::TimedOut
+            | PipelineStatus::Cancelled => true,
+        }
+    }
+}
+
+impl PipelineConfiguration {
+    /// Whether any of the triggers matches, manual triggers only start the pipeline they name
+    pub fn is_triggered_by(&self, trigger: &Trigger) -> bool {
+        let is_target = match &trigger.event {
+            TriggerEvent::Manual { pipeline, .. } => pipeline == &self.name,
+            _ => true,
+        };
+
+        is_target
+            && self
+                .trigger
+                .iter()
+                .any(|trigger_configuration| trigger_configuration.matches(trigger))
+    }
+
+    /// The inputs declared by the first `manual` trigger, `None` if the pipeline
+    /// can not be started manually
+    pub fn manual_inputs(&self) -> Option<BTreeMap<String, InputConfiguration>> {
+        self.trigger
+            .iter()
+            .find_map(|trigger_configuration| match trigger_configuration {
+                TriggerConfiguration::Manual { inputs } => Some(inputs.clone().unwrap_or_default()),
+                _ => None,
+            })
+    }
+
+    /// The options of the implicit clone step, `None` if it is disabled
+    pub fn clone_options(&self) -> Option<CloneOptions> {
+        match &self.clone {
+            None | Some(CloneConfiguration::Enabled(true)) => Some(CloneOptions {
+                depth: None,
+                submodules: false,
+                lfs: false,
+            }),
+            Some(CloneConfiguration::Enabled(false)) => None,
+            Some(CloneConfiguration::Options(options)) => Some(options.clone()),
+        }
+    }
+
+    /// Adds the clone step in front of all other steps, which all depend on it
+    pub fn with_clone_step(mut self, clone_step: StepConfiguration) -> Self {
+        if self.steps.iter().any(|step| step.depends_on.is_some()) {
+            for step in &mut self.steps {
+                let depends_on = step.depends_on.get_or_insert_with(Vec::new);
+
+                if !depends_on.iter().any(|name| name == &clone_step.name) {
+                    depends_on.push(clone_step.name.clone());
+                }
+            }
+        }
+
+        self.steps.insert(0, clone_step);
+        self
+    }
+
+    /// Resolves the `depends_on` names of every step to step indices.
+    ///
+    /// If no step declares any dependencies, the steps run sequentially in the
+    /// order they are defined, so every step implicitly depends on the previous one.
+    /// Unknown step names are ignored here, they are reported by `validate`.
+    pub fn step_dependencies(&self) -> Vec<Vec<usize>> {
+        let uses_dependencies = self.steps.iter().any(|step| step.depends_on.is_some());
+
+        if !uses_dependencies {
+            return (0..self.steps.len())
+                .map(|index| index.checked_sub(1).into_iter().collect())
+                .collect();
+        }
+
+        self.steps
+            .iter()
+            .map(|step| {
+                step.depends_on
+                    .iter()
+                    .flatten()
+                    .filter_map(|name| self.steps.iter().position(|step| &step.name == name))
+                    .collect()
+            })
+            .collect()
+    }
+}
+
 impl Pipeline {
-    pub fn new(id: PipelineId, configuration: PipelineConfiguration) -> Self {
+    pub fn new(
+        id: PipelineId,
+        trigger: Trigger,
+        configuration: PipelineConfiguration,
+        created_at: NaiveDateTime,
+    ) -> Self {
         let steps = configuration
             .steps
             .iter()
             .enumerate()
             .map(|(id, step_configuration)| {
-                Step::new(StepId::new(id + 1), step_configuration.clone())
+                Step::new(StepId::new(id as i32 + 1), step_configuration.clone())
             })
             .collect();
 
         Self {
             id,
+            trigger,
             configuration,
             steps,
             status: PipelineStatus::Pending,
+            created_at,
+            started_at: None,
+            finished_at: None,
         }
     }
 }
@@ -166,12 +375,16 @@ impl Step {
             id,
             configuration,
             status: PipelineStatus::Pending,
+            exit_code: None,
+            failure_reason: None,
+            started_at: None,
+            finished_at: None,
         }
     }
 }
 
 impl StepId {
-    pub fn new(i: usize) -> Self {
+    pub fn new(i: i32) -> Self {
         Self(i)
     }
 }
@@ -181,3 +394,107 @@ impl Display for StepId {
         write!(f, "{}", self.0)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::trigger::Branch;
+
+    fn step(name: &str, depends_on: Option<&[&str]>) -> StepConfiguration {
+        serde_json::from_value(serde_json::json!({
+            "name": name,
+            "image": "alpine",
+            "depends_on": depends_on,
+        }))
+        .unwrap()
+    }
+
+    fn configuration(steps: Vec<StepConfiguration>) -> PipelineConfiguration {
+        PipelineConfiguration {
+            name: "Build".to_owned(),
+            trigger: vec![],
+            clone: None,
+            timeout: None,
+            cancel_previous: false,
+            runs_on: None,
+            matrix: None,
+            services: None,
+            environment: None,
+            steps,
+        }
+    }
+
+    #[test]
+    fn with_clone_step_should_run_clone_step_first_for_sequential_steps() {
+        let configuration = configuration(vec![step("a", None), step("b", None)])
+            .with_clone_step(step("clone", None));
+
+        assert_eq!(
+            configuration.step_dependencies(),
+            vec![vec![], vec![0], vec![1]]
+        );
+    }
+
+    #[test]
+    fn with_clone_step_should_make_all_steps_depend_on_clone_step() {
+        let configuration = configuration(vec![
+            step("a", None),
+            step("b", Some(&["a"])),
+            step("c", Some(&["clone"])),
+        ])
+        .with_clone_step(step("clone", None));
+
+        assert_eq!(
+            configuration.step_dependencies(),
+            vec![vec![], vec![0], vec![1, 0], vec![0]]
+        );
+    }
+
+    #[test]
+    fn clone_options_should_default_to_enabled() {
+        let mut configuration = configuration(vec![]);
+        assert!(configuration.clone_options().is_some());
+
+        configuration.clone = serde_json::from_str("false").unwrap();
+        assert!(configuration.clone_options().is_none());
+
+        configuration.clone = serde_json::from_str(r#"{ "depth": 0, "lfs": true }"#).unwrap();
+        assert_eq!(
+            configuration.clone_options(),
+            Some(CloneOptions {
+                depth: Some(0),
+                submodules: false,
+                lfs: true
+            })
+        );
+    }
+
+    #[test]
+    fn is_triggered_by_should_only_match_pipeline_named_by_manual_trigger() {
+        let configuration: PipelineConfiguration = serde_json::from_str(
+            r#"{
+                "name": "Deploy",
+                "trigger": [{ "event": "manual" }],
+                "steps": []
+            }"#,
+        )
+        .unwrap();
+        let trigger = |pipeline: &str| Trigger {
+            repository_owner: "Owner".to_owned(),
+            repository_name: "Repo".to_owned(),
+            installation_id: 789,
+            event: TriggerEvent::Manual {
+                branch: Branch {
+                    name: "main".to_owned(),
+                    commit: "123".to_owned(),
+                },
+                pipeline: pipeline.to_owned(),
+                inputs: BTreeMap::new(),
+            },
+        };
+
+        assert!(configuration.is_triggered_by(&trigger("Deploy")));
+        assert!(!configuration.is_triggered_by(&trigger("Test")));
+        assert_eq!(configuration.manual_inputs(), Some(BTreeMap::new()));
+    }
+}
@@ -0,0 +1,122 @@
+use globset::{GlobBuilder, GlobMatcher};
+use serde::{Deserialize, Serialize};
+
+/// One or more glob patterns a branch or tag name is matched against.
+///
+/// `*` matches within a single path segment, `**` across segments, so `release/*` matches
+/// `release/1.0` but not `release/1.0/hotfix`. Patterns prefixed with `!` exclude names.
+/// A list consisting only of exclusions matches every other name.
+#[derive(Serialize, Deserialize, Clone, PartialEq, Eq, Debug)]
+#[serde(untagged)]
+pub enum RefPatterns {
+    Single(String),
+    List(Vec<String>),
+}
+
+impl RefPatterns {
+    pub fn patterns(&self) -> &[String] {
+        match self {
+            Self::Single(pattern) => std::slice::from_ref(pattern),
+            Self::List(patterns) => patterns,
+        }
+    }
+
+    pub fn matches(&self, name: &str) -> bool {
+        let (excluded, included): (Vec<_>, Vec<_>) = self
+            .patterns()
+            .iter()
+            .partition(|pattern| pattern.starts_with('!'));
+
+        let is_included =
+            included.is_empty() || included.iter().any(|pattern| glob_matches(pattern, name));
+
+        let is_excluded = excluded
+            .iter()
+            .any(|pattern| glob_matches(&pattern[1..], name));
+
+        is_included && !is_excluded
+    }
+
+    /// Returns the first pattern that is not a valid glob along with the reason
+    pub fn find_invalid(&self) -> Option<(&str, String)> {
+        self.patterns().iter().find_map(|pattern| {
+            compile_glob(pattern.strip_prefix('!').unwrap_or(pattern))
+                .err()
+                .map(|err| (pattern.as_str(), err.kind().to_string()))
+        })
+    }
+}
+
+pub(super) fn compile_glob(pattern: &str) -> Result<GlobMatcher, globset::Error> {
+    GlobBuilder::new(pattern)
+        .literal_separator(true)
+        .build()
+        .map(|glob| glob.compile_matcher())
+}
+
+pub(super) fn glob_matches(pattern: &str, value: &str) -> bool {
+    compile_glob(pattern).is_ok_and(|matcher| matcher.is_match(value))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn list(patterns: &[&str]) -> RefPatterns {
+        RefPatterns::List(patterns.iter().map(|&pattern| pattern.to_owned()).collect())
+    }
+
+    #[test]
+    fn exact_pattern_should_only_match_same_branch() {
+        let patterns = RefPatterns::Single("main".to_owned());
+
+        assert!(patterns.matches("main"));
+        assert!(!patterns.matches("main2"));
+        assert!(!patterns.matches("feature/main"));
+    }
+
+    #[test]
+    fn single_star_should_not_match_across_segments() {
+        let patterns = RefPatterns::Single("release/*".to_owned());
+
+        assert!(patterns.matches("release/1.0"));
+        assert!(!patterns.matches("release/1.0/hotfix"));
+        assert!(!patterns.matches("release"));
+    }
+
+    #[test]
+    fn double_star_should_match_across_segments() {
+        let patterns = RefPatterns::Single("feature/**".to_owned());
+
+        assert!(patterns.matches("feature/login"));
+        assert!(patterns.matches("feature/login/form"));
+        assert!(!patterns.matches("bugfix/login"));
+    }
+
+    #[test]
+    fn list_should_match_any_pattern_unless_excluded() {
+        let patterns = list(&["main", "release/*", "!release/old"]);
+
+        assert!(patterns.matches("main"));
+        assert!(patterns.matches("release/1.0"));
+        assert!(!patterns.matches("release/old"));
+        assert!(!patterns.matches("develop"));
+    }
+
+    #[test]
+    fn list_of_exclusions_should_match_all_other_branches() {
+        let patterns = list(&["!wip/*"]);
+
+        assert!(patterns.matches("main"));
+        assert!(!patterns.matches("wip/experiment"));
+    }
+
+    #[test]
+    fn find_invalid_should_return_malformed_pattern() {
+        assert_eq!(list(&["main", "!feature/**"]).find_invalid(), None);
+        assert!(matches!(
+            list(&["main", "release/[1.0"]).find_invalid(),
+            Some(("release/[1.0", _))
+        ));
+    }
+}
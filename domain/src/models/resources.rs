@@ -0,0 +1,210 @@
+use std::{fmt::Display, str::FromStr};
+
+use serde::{Deserialize, Serialize};
+
+/// Docker does not start containers with less memory than this
+pub const MIN_MEMORY: u64 = 6 * 1024 * 1024;
+
+/// Limits of the container of a step, unset limits fall back to the defaults of the server
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq)]
+pub struct ResourcesConfiguration {
+    /// Number of CPUs, fractions like `0.5` are allowed
+    pub cpus: Option<f64>,
+    pub memory: Option<MemorySize>,
+    /// Maximum number of processes
+    pub pids: Option<u32>,
+}
+
+impl ResourcesConfiguration {
+    /// Fills in unset limits from the defaults and caps all limits at the maximums
+    pub fn resolve(&self, defaults: &Self, maximums: &Self) -> Self {
+        fn cap<T: PartialOrd>(
+            value: Option<T>,
+            default: Option<T>,
+            maximum: Option<T>,
+        ) -> Option<T> {
+            match (value.or(default), maximum) {
+                (Some(value), Some(maximum)) if value > maximum => Some(maximum),
+                (None, maximum) => maximum,
+                (value, _) => value,
+            }
+        }
+
+        Self {
+            cpus: cap(self.cpus, defaults.cpus, maximums.cpus),
+            memory: cap(self.memory, defaults.memory, maximums.memory),
+            pids: cap(self.pids, defaults.pids, maximums.pids),
+        }
+    }
+
+    pub fn validate(&self) -> Result<(), String> {
+        // NaN is neither positive nor negative, so it has to be ruled out explicitly
+        if self
+            .cpus
+            .is_some_and(|cpus| !cpus.is_finite() || cpus <= 0.0)
+        {
+            return Err("cpus have to be a positive number".to_owned());
+        }
+
+        if self.memory.is_some_and(|memory| memory.0 < MIN_MEMORY) {
+            return Err(format!(
+                "memory has to be at least {}",
+                MemorySize(MIN_MEMORY)
+            ));
+        }
+
+        if self.pids == Some(0) {
+            return Err("pids have to be positive".to_owned());
+        }
+
+        Ok(())
+    }
+}
+
+/// Bytes, written as a number or as a size like `512m` or `2g`
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+#[serde(try_from = "RawMemorySize", into = "u64")]
+pub struct MemorySize(pub u64);
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum RawMemorySize {
+    Bytes(u64),
+    // Jsonnet only knows floating point numbers
+    Float(f64),
+    Size(String),
+}
+
+impl TryFrom<RawMemorySize> for MemorySize {
+    type Error = String;
+
+    fn try_from(raw: RawMemorySize) -> Result<Self, Self::Error> {
+        match raw {
+            RawMemorySize::Bytes(bytes) => Ok(MemorySize(bytes)),
+            RawMemorySize::Float(bytes) if bytes.fract() == 0.0 && bytes >= 0.0 => {
+                Ok(MemorySize(bytes as u64))
+            }
+            RawMemorySize::Float(bytes) => Err(format!("Invalid memory size {bytes}")),
+            RawMemorySize::Size(size) => size.parse(),
+        }
+    }
+}
+
+impl From<MemorySize> for u64 {
+    fn from(size: MemorySize) -> Self {
+        size.0
+    }
+}
+
+impl FromStr for MemorySize {
+    type Err = String;
+
+    fn from_str(size: &str) -> Result<Self, Self::Err> {
+        let invalid = || format!("Invalid memory size \"{size}\", expected e.g. 512m or 2g");
+        let size = size.trim().to_ascii_lowercase();
+        let size = size.strip_suffix('b').unwrap_or(&size);
+
+        let (digits, unit) = match size.char_indices().last() {
+            Some((index, 'k')) => (&size[..index], 1024),
+            Some((index, 'm')) => (&size[..index], 1024 * 1024),
+            Some((index, 'g')) => (&size[..index], 1024 * 1024 * 1024),
+            _ => (size, 1),
+        };
+
+        let value: u64 = digits.parse().map_err(|_| invalid())?;
+
+        value.checked_mul(unit).map(MemorySize).ok_or_else(invalid)
+    }
+}
+
+impl Display for MemorySize {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        const UNITS: [(u64, &str); 3] =
+            [(1024 * 1024 * 1024, "g"), (1024 * 1024, "m"), (1024, "k")];
+
+        match UNITS
+            .iter()
+            .find(|(unit, _)| self.0 >= *unit && self.0.is_multiple_of(*unit))
+        {
+            Some((unit, suffix)) => write!(f, "{}{suffix}", self.0 / unit),
+            None => write!(f, "{}", self.0),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn memory_size_should_parse_numbers_and_units() {
+        let sizes: Vec<MemorySize> =
+            serde_json::from_str(r#"[1048576, 1048576.0, "512m", "2g", "64K", "1gb"]"#).unwrap();
+
+        assert_eq!(
+            sizes,
+            vec![
+                MemorySize(1024 * 1024),
+                MemorySize(1024 * 1024),
+                MemorySize(512 * 1024 * 1024),
+                MemorySize(2 * 1024 * 1024 * 1024),
+                MemorySize(64 * 1024),
+                MemorySize(1024 * 1024 * 1024),
+            ]
+        );
+        assert_eq!(MemorySize(512 * 1024 * 1024).to_string(), "512m");
+
+        assert!(serde_json::from_str::<MemorySize>(r#""lots""#).is_err());
+        assert!(serde_json::from_str::<MemorySize>(r#""1.5g""#).is_err());
+    }
+
+    #[test]
+    fn resolve_should_apply_defaults_and_cap_at_maximums() {
+        let defaults = ResourcesConfiguration {
+            cpus: Some(1.0),
+            memory: Some(MemorySize(1024)),
+            pids: None,
+        };
+        let maximums = ResourcesConfiguration {
+            cpus: Some(4.0),
+            memory: None,
+            pids: Some(512),
+        };
+        let step = ResourcesConfiguration {
+            cpus: Some(8.0),
+            memory: None,
+            pids: Some(100),
+        };
+
+        assert_eq!(
+            step.resolve(&defaults, &maximums),
+            ResourcesConfiguration {
+                cpus: Some(4.0),
+                memory: Some(MemorySize(1024)),
+                pids: Some(100),
+            }
+        );
+        assert_eq!(
+            ResourcesConfiguration::default().resolve(&defaults, &maximums),
+            ResourcesConfiguration {
+                cpus: Some(1.0),
+                memory: Some(MemorySize(1024)),
+                pids: Some(512),
+            }
+        );
+    }
+
+    #[test]
+    fn validate_should_reject_cpus_that_are_not_positive_numbers() {
+        let cpus = |cpus| ResourcesConfiguration {
+            cpus: Some(cpus),
+            ..Default::default()
+        };
+
+        assert!(cpus(0.5).validate().is_ok());
+        assert!(cpus(0.0).validate().is_err());
+        assert!(cpus(-1.0).validate().is_err());
+        assert!(cpus(f64::NAN).validate().is_err());
+        assert!(cpus(f64::INFINITY).validate().is_err());
+    }
+}
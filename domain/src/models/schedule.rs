@@ -0,0 +1,192 @@
+use std::{collections::BTreeSet, str::FromStr};
+
+use chrono::{DateTime, Utc};
+use chrono_tz::Tz;
+
+/// A cron expression that is evaluated in a specific timezone.
+///
+/// Accepts the common five field format (`minute hour day month weekday`), whose weekdays
+/// are numbered like POSIX cron with 0 or 7 for Sunday, as well as the extended format with
+/// a leading seconds field, whose weekdays are numbered from 1 for Sunday.
+#[derive(Clone, Debug)]
+pub struct CronSchedule {
+    schedule: cron::Schedule,
+    timezone: Tz,
+}
+
+impl CronSchedule {
+    pub fn parse(expression: &str, timezone: Option<&str>) -> Result<Self, String> {
+        let fields: Vec<_> = expression.split_whitespace().collect();
+        let expression = match fields.as_slice() {
+            [minute, hour, day, month, weekday] => format!(
+                "0 {minute} {hour} {day} {month} {}",
+                translate_weekdays(weekday)
+                    .map_err(|err| format!("Invalid cron expression \"{expression}\": {err}"))?
+            ),
+            _ => expression.to_owned(),
+        };
+
+        let schedule = cron::Schedule::from_str(&expression)
+            .map_err(|err| format!("Invalid cron expression \"{expression}\": {err}"))?;
+
+        let timezone = timezone
+            .map(|timezone| {
+                timezone
+                    .parse()
+                    .map_err(|_| format!("Unknown timezone \"{timezone}\""))
+            })
+            .transpose()?
+            .unwrap_or(Tz::UTC);
+
+        Ok(Self { schedule, timezone })
+    }
+
+    /// The first time the schedule fires after the given time
+    pub fn next_after(&self, time: DateTime<Utc>) -> Option<DateTime<Utc>> {
+        self.schedule
+            .after(&time.with_timezone(&self.timezone))
+            .next()
+            .map(|time| time.with_timezone(&Utc))
+    }
+}
+
+/// Translates a POSIX weekday field, where 0 and 7 are Sunday, into a list of the weekdays
+/// of the cron crate, where 1 is Sunday
+fn translate_weekdays(field: &str) -> Result<String, String> {
+    if field == "*" || field == "?" {
+        return Ok(field.to_owned());
+    }
+
+    let mut weekdays = BTreeSet::new();
+
+    for part in field.split(',') {
+        let (range, step) = match part.split_once('/') {
+            Some((range, step)) => {
+                let step = step
+                    .parse::<usize>()
+                    .ok()
+                    .filter(|step| *step > 0)
+                    .ok_or_else(|| format!("invalid step \"{step}\" of weekdays"))?;
+
+                (range, step)
+            }
+            None => (part, 1),
+        };
+
+        let (start, end) = match range.split_once('-') {
+            _ if range == "*" => (0, 6),
+            Some((start, end)) => (parse_weekday(start)?, parse_weekday(end)?),
+            // A single weekday with a step repeats until the end of the week
+            None if step > 1 => (parse_weekday(range)?, 6),
+            None => (parse_weekday(range)?, parse_weekday(range)?),
+        };
+
+        if start > end {
+            return Err(format!("weekday range \"{range}\" has to ascend"));
+        }
+
+        weekdays.extend((start..=end).step_by(step).map(|weekday| weekday % 7 + 1));
+    }
+
+    Ok(weekdays
+        .iter()
+        .map(u32::to_string)
+        .collect::<Vec<_>>()
+        .join(","))
+}
+
+/// Returns the POSIX number of a weekday given as number or name
+fn parse_weekday(weekday: &str) -> Result<u32, String> {
+    if let Ok(number) = weekday.parse::<u32>() {
+        return match number {
+            0..=7 => Ok(number),
+            _ => Err(format!("weekday {number} is not between 0 and 7")),
+        };
+    }
+
+    // The names the cron crate accepts
+    let number = match weekday.to_ascii_lowercase().as_str() {
+        "sun" | "sunday" => 0,
+        "mon" | "monday" => 1,
+        "tue" | "tues" | "tuesday" => 2,
+        "wed" | "wednesday" => 3,
+        "thu" | "thurs" | "thursday" => 4,
+        "fri" | "friday" => 5,
+        "sat" | "saturday" => 6,
+        _ => return Err(format!("unknown weekday \"{weekday}\"")),
+    };
+
+    Ok(number)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn utc(value: &str) -> DateTime<Utc> {
+        DateTime::parse_from_rfc3339(value)
+            .unwrap()
+            .with_timezone(&Utc)
+    }
+
+    #[test]
+    fn parse_should_accept_five_field_expressions() {
+        let schedule = CronSchedule::parse("30 2 * * *", None).unwrap();
+
+        assert_eq!(
+            schedule.next_after(utc("2024-01-01T03:00:00Z")),
+            Some(utc("2024-01-02T02:30:00Z"))
+        );
+    }
+
+    #[test]
+    fn next_after_should_respect_timezone() {
+        let schedule = CronSchedule::parse("0 2 * * *", Some("Europe/Vienna")).unwrap();
+
+        assert_eq!(
+            schedule.next_after(utc("2024-01-01T00:00:00Z")),
+            Some(utc("2024-01-01T01:00:00Z"))
+        );
+    }
+
+    #[test]
+    fn parse_should_reject_invalid_expressions() {
+        assert!(CronSchedule::parse("every day", None).is_err());
+        assert!(CronSchedule::parse("0 2 * * *", Some("Mars/Olympus")).is_err());
+    }
+
+    #[test]
+    fn parse_should_number_weekdays_like_posix_cron() {
+        // 2024-01-06 is a Saturday
+        let weekdays = CronSchedule::parse("0 9 * * 1-5", None).unwrap();
+        let fired: Vec<_> =
+            std::iter::successors(weekdays.next_after(utc("2024-01-06T00:00:00Z")), |time| {
+                weekdays.next_after(*time)
+            })
+            .take(5)
+            .collect();
+
+        assert_eq!(
+            fired,
+            [8, 9, 10, 11, 12].map(|day| utc(&format!("2024-01-{day:02}T09:00:00Z")))
+        );
+
+        let sunday = utc("2024-01-07T09:00:00Z");
+        for expression in ["0 9 * * 0", "0 9 * * 7", "0 9 * * sun", "0 9 * * 6-7"] {
+            let schedule = CronSchedule::parse(expression, None).unwrap();
+            assert_eq!(
+                schedule.next_after(utc("2024-01-06T10:00:00Z")),
+                Some(sunday),
+                "{expression}"
+            );
+        }
+    }
+
+    #[test]
+    fn parse_should_reject_invalid_weekdays() {
+        assert!(CronSchedule::parse("0 9 * * 8", None).is_err());
+        assert!(CronSchedule::parse("0 9 * * 5-1", None).is_err());
+        assert!(CronSchedule::parse("0 9 * * someday", None).is_err());
+        assert!(CronSchedule::parse("0 9 * * */0", None).is_err());
+    }
+}
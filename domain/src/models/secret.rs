@@ -0,0 +1,48 @@
+use std::fmt::Display;
+
+use chrono::NaiveDateTime;
+use serde::Serialize;
+
+/// Where a secret is visible. Repository secrets take precedence over
+/// installation secrets with the same name.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum SecretScope {
+    /// Every repository of a GitHub app installation
+    Installation(u64),
+    Repository {
+        owner: String,
+        name: String,
+    },
+}
+
+impl Display for SecretScope {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Installation(id) => write!(f, "installation/{id}"),
+            Self::Repository { owner, name } => write!(f, "repository/{owner}/{name}"),
+        }
+    }
+}
+
+/// A secret as returned by the API, without its value
+#[derive(Serialize, Clone, Debug, PartialEq, Eq)]
+pub struct Secret {
+    pub name: String,
+    pub created_at: NaiveDateTime,
+    pub updated_at: NaiveDateTime,
+}
+
+/// A secret value encrypted with AES-256-GCM
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct EncryptedSecret {
+    pub nonce: Vec<u8>,
+    pub ciphertext: Vec<u8>,
+}
+
+/// Secret names may only contain letters, digits, `_` and `-`
+pub fn is_valid_secret_name(name: &str) -> bool {
+    !name.is_empty()
+        && name
+            .chars()
+            .all(|char| char.is_ascii_alphanumeric() || char == '_' || char == '-')
+}
@@ -0,0 +1,64 @@
+use std::{collections::BTreeMap, time::Duration};
+
+use serde::{Deserialize, Serialize};
+
+use super::{docker_image_reference::DockerImageReference, timeout::Timeout};
+
+/// A container running alongside the steps of a pipeline, e.g. a database.
+///
+/// Steps reach it by its name as hostname, it is stopped once the pipeline finished.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct ServiceConfiguration {
+    pub name: String,
+    pub image: DockerImageReference,
+    pub environment: Option<BTreeMap<String, String>>,
+    /// Overrides the command of the image
+    pub command: Option<Vec<String>>,
+    /// Steps start once the check passed, without one only once the container is running
+    pub health_check: Option<HealthCheckConfiguration>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct HealthCheckConfiguration {
+    /// Shell command that exits with 0 once the service is ready, e.g. `pg_isready`
+    pub command: String,
+    /// Defaults to 2 seconds
+    pub interval: Option<Timeout>,
+    /// Maximum duration of a single check, defaults to 5 seconds
+    pub timeout: Option<Timeout>,
+    /// Failed checks until the service is considered unhealthy, defaults to 30
+    pub retries: Option<u32>,
+}
+
+impl HealthCheckConfiguration {
+    pub fn interval(&self) -> Duration {
+        self.interval
+            .map_or(Duration::from_secs(2), |interval| interval.0)
+    }
+
+    pub fn timeout(&self) -> Duration {
+        self.timeout
+            .map_or(Duration::from_secs(5), |timeout| timeout.0)
+    }
+
+    pub fn retries(&self) -> u32 {
+        self.retries.unwrap_or(30)
+    }
+
+    /// Longest time Docker keeps checking before it considers the service unhealthy,
+    /// as every retry may wait for the interval and run into the timeout
+    pub fn startup_time(&self) -> Duration {
+        (self.interval() + self.timeout()) * self.retries()
+    }
+}
+
+/// Service names are used as hostnames, so they are restricted to lowercase letters,
+/// digits and dashes
+pub fn is_valid_service_name(name: &str) -> bool {
+    !name.is_empty()
+        && !name.starts_with('-')
+        && !name.ends_with('-')
+        && name
+            .chars()
+            .all(|char| char.is_ascii_lowercase() || char.is_ascii_digit() || char == '-')
+}
@@ -0,0 +1,174 @@
+use std::{fmt::Display, time::Duration};
+
+use serde::{de::Visitor, Deserialize, Serialize};
+
+/// A timeout written as seconds or as a duration like `90s`, `10m` or `1h30m`
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Timeout(pub Duration);
+
+impl Display for Timeout {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let seconds = self.0.as_secs();
+        let (hours, minutes, seconds) = (seconds / 3600, seconds / 60 % 60, seconds % 60);
+
+        if hours > 0 {
+            write!(f, "{hours}h")?;
+        }
+
+        if minutes > 0 {
+            write!(f, "{minutes}m")?;
+        }
+
+        if seconds > 0 {
+            write!(f, "{seconds}s")?;
+        }
+
+        Ok(())
+    }
+}
+
+impl Serialize for Timeout {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        serializer.serialize_str(self.to_string().as_str())
+    }
+}
+
+impl<'de> Deserialize<'de> for Timeout {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        deserializer.deserialize_any(TimeoutVisitor)
+    }
+}
+
+struct TimeoutVisitor;
+
+impl<'de> Visitor<'de> for TimeoutVisitor {
+    type Value = Timeout;
+
+    fn expecting(&self, formatter: &mut std::fmt::Formatter) -> std::fmt::Result {
+        formatter.write_str("A positive number of seconds or a duration like 1h30m")
+    }
+
+    fn visit_u64<E>(self, v: u64) -> Result<Self::Value, E>
+    where
+        E: serde::de::Error,
+    {
+        self.parse_seconds(v)
+    }
+
+    fn visit_i64<E>(self, v: i64) -> Result<Self::Value, E>
+    where
+        E: serde::de::Error,
+    {
+        let seconds = u64::try_from(v).map_err(|_| E::custom("Timeout must be positive"))?;
+        self.parse_seconds(seconds)
+    }
+
+    fn visit_f64<E>(self, v: f64) -> Result<Self::Value, E>
+    where
+        E: serde::de::Error,
+    {
+        // Jsonnet only knows floating point numbers
+        if v.fract() != 0.0 || v < 0.0 {
+            return Err(E::custom("Timeout must be a whole number of seconds"));
+        }
+
+        self.parse_seconds(v as u64)
+    }
+
+    fn visit_str<E>(self, v: &str) -> Result<Self::Value, E>
+    where
+        E: serde::de::Error,
+    {
+        self.parse_string(v)
+    }
+}
+
+impl TimeoutVisitor {
+    fn parse_seconds<E>(self, seconds: u64) -> Result<Timeout, E>
+    where
+        E: serde::de::Error,
+    {
+        if seconds == 0 {
+            return Err(E::custom("Timeout must be positive"));
+        }
+
+        Ok(Timeout(Duration::from_secs(seconds)))
+    }
+
+    fn parse_string<E>(self, v: &str) -> Result<Timeout, E>
+    where
+        E: serde::de::Error,
+    {
+        let invalid = || E::custom(format!("Invalid timeout \"{v}\", expected e.g. 1h30m"));
+
+        let mut seconds = 0u64;
+        let mut digits = String::new();
+
+        for character in v.trim().chars() {
+            if character.is_ascii_digit() {
+                digits.push(character);
+                continue;
+            }
+
+            let unit = match character {
+                'h' => 3600,
+                'm' => 60,
+                's' => 1,
+                _ => return Err(invalid()),
+            };
+            let value: u64 = digits.parse().map_err(|_| invalid())?;
+            seconds = value
+                .checked_mul(unit)
+                .and_then(|value| seconds.checked_add(value))
+                .ok_or_else(invalid)?;
+            digits.clear();
+        }
+
+        if !digits.is_empty() {
+            return Err(invalid());
+        }
+
+        self.parse_seconds(seconds)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(value: &str) -> Result<Timeout, serde_json::Error> {
+        serde_json::from_str(value)
+    }
+
+    #[test]
+    fn timeout_should_parse_seconds_and_durations() {
+        assert_eq!(parse("90").unwrap().0, Duration::from_secs(90));
+        assert_eq!(parse(r#""90s""#).unwrap().0, Duration::from_secs(90));
+        assert_eq!(parse(r#""10m""#).unwrap().0, Duration::from_secs(600));
+        assert_eq!(parse(r#""1h30m""#).unwrap().0, Duration::from_secs(5400));
+    }
+
+    #[test]
+    fn timeout_should_reject_invalid_durations() {
+        assert!(parse("0").is_err());
+        assert!(parse("-5").is_err());
+        assert!(parse(r#""10""#).is_err());
+        assert!(parse(r#""1d""#).is_err());
+        assert!(parse(r#""m""#).is_err());
+    }
+
+    #[test]
+    fn timeout_should_serialize_to_parsable_duration() {
+        let timeout = parse(r#""1h0m5s""#).unwrap();
+        let serialized = serde_json::to_string(&timeout).unwrap();
+
+        assert_eq!(serialized, r#""1h5s""#);
+        assert_eq!(parse(&serialized).unwrap(), timeout);
+    }
+}
@@ -1,18 +1,42 @@
+use std::collections::BTreeMap;
+
 use serde::{Deserialize, Serialize};
 
-#[derive(Serialize, Deserialize, PartialEq, Eq, Debug)]
+use super::{input::InputConfiguration, path_filter::PathFilter, ref_pattern::RefPatterns};
+
+#[derive(Serialize, Deserialize, Clone, PartialEq, Eq, Debug)]
 #[serde(tag = "event")]
 pub enum TriggerConfiguration {
     #[serde(rename = "push")]
-    Push { branch: Option<String> },
+    Push {
+        #[serde(alias = "branches")]
+        branch: Option<RefPatterns>,
+        exclude: Option<RefPatterns>,
+        paths: Option<Vec<String>>,
+        paths_ignore: Option<Vec<String>>,
+    },
     #[serde(rename = "pull_request")]
     PullRequest {
-        target: Option<String>,
-        source: Option<String>,
+        target: Option<RefPatterns>,
+        source: Option<RefPatterns>,
+        paths: Option<Vec<String>>,
+        paths_ignore: Option<Vec<String>>,
+    },
+    #[serde(rename = "schedule")]
+    Schedule {
+        cron: String,
+        branch: String,
+        timezone: Option<String>,
+    },
+    #[serde(rename = "tag")]
+    Tag { pattern: Option<RefPatterns> },
+    #[serde(rename = "manual")]
+    Manual {
+        inputs: Option<BTreeMap<String, InputConfiguration>>,
     },
 }
 
-#[derive(Clone, Debug, PartialEq, Eq)]
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
 pub struct Trigger {
     pub repository_owner: String,
     pub repository_name: String,
@@ -20,55 +44,215 @@ pub struct Trigger {
     pub event: TriggerEvent,
 }
 
-#[derive(Clone, Debug, PartialEq, Eq)]
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+#[serde(tag = "event")]
 pub enum TriggerEvent {
-    Push { branch: Branch },
-    PullRequest { source: Branch, target: Branch },
+    #[serde(rename = "push")]
+    Push {
+        branch: Branch,
+        /// The commit the branch pointed to before the push, if the branch already existed
+        #[serde(default)]
+        before: Option<String>,
+    },
+    #[serde(rename = "pull_request")]
+    PullRequest {
+        /// Zero for pipelines stored before the number was recorded
+        #[serde(default)]
+        number: u64,
+        source: Branch,
+        target: Branch,
+        /// Whether the source branch lives in a fork of the repository
+        #[serde(default)]
+        from_fork: bool,
+    },
+    #[serde(rename = "schedule")]
+    Schedule {
+        branch: Branch,
+        cron: String,
+        /// `None` for UTC and for pipelines stored before the timezone was recorded
+        #[serde(default)]
+        timezone: Option<String>,
+    },
+    #[serde(rename = "tag")]
+    Tag { tag: Branch },
+    /// Started through the API, `inputs` are already validated and converted to strings
+    #[serde(rename = "manual")]
+    Manual {
+        branch: Branch,
+        pipeline: String,
+        inputs: BTreeMap<String, String>,
+    },
 }
 
-#[derive(Clone, Debug, PartialEq, Eq)]
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
 pub struct Branch {
     pub name: String,
     pub commit: String,
 }
 
+impl TriggerEvent {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Push { .. } => "push",
+            Self::PullRequest { .. } => "pull_request",
+            Self::Schedule { .. } => "schedule",
+            Self::Tag { .. } => "tag",
+            Self::Manual { .. } => "manual",
+        }
+    }
+
+    /// The branch the pipeline runs for, for pull requests this is the source branch
+    /// and for tag pushes the tag
+    pub fn branch(&self) -> &Branch {
+        match self {
+            Self::Push { branch, .. } => branch,
+            Self::PullRequest { source, .. } => source,
+            Self::Schedule { branch, .. } => branch,
+            Self::Tag { tag } => tag,
+            Self::Manual { branch, .. } => branch,
+        }
+    }
+
+    pub fn commit(&self) -> &str {
+        &self.branch().commit
+    }
+
+    /// Secrets are withheld from pull requests opened from forks, as anyone can open those
+    pub fn allows_secrets(&self) -> bool {
+        !matches!(
+            self,
+            Self::PullRequest {
+                from_fork: true,
+                ..
+            }
+        )
+    }
+
+    /// The inputs of a manually started pipeline
+    pub fn inputs(&self) -> Option<&BTreeMap<String, String>> {
+        match self {
+            Self::Manual { inputs, .. } => Some(inputs),
+            _ => None,
+        }
+    }
+
+    /// The base and head commits to compare to find the files changed by this event
+    pub fn changed_range(&self) -> Option<(&str, &str)> {
+        match self {
+            Self::Push { branch, before } => before
+                .as_deref()
+                .map(|before| (before, branch.commit.as_str())),
+            Self::PullRequest { source, target, .. } => {
+                Some((target.commit.as_str(), source.commit.as_str()))
+            }
+            Self::Schedule { .. } | Self::Tag { .. } | Self::Manual { .. } => None,
+        }
+    }
+}
+
 impl TriggerConfiguration {
     pub fn matches(&self, trigger: &Trigger) -> bool {
         match self {
             Self::Push {
                 branch: expected_branch,
+                exclude,
+                ..
             } => match &trigger.event {
                 TriggerEvent::Push {
                     branch: Branch { name: branch, .. },
                     ..
-                } => match expected_branch {
-                    None => true,
-                    Some(expected_branch) => expected_branch == branch,
-                },
+                } => {
+                    let branch_matches = expected_branch
+                        .as_ref()
+                        .is_none_or(|expected_branch| expected_branch.matches(branch));
+
+                    // Unlike an empty list of branches, an empty exclude list matches nothing
+                    let branch_excluded = exclude.as_ref().is_some_and(|exclude| {
+                        !exclude.patterns().is_empty() && exclude.matches(branch)
+                    });
+
+                    branch_matches && !branch_excluded
+                }
                 _ => false,
             },
             Self::PullRequest {
                 target: expected_target,
                 source: expected_source,
+                ..
             } => match &trigger.event {
                 TriggerEvent::PullRequest {
                     source: Branch { name: source, .. },
                     target: Branch { name: target, .. },
+                    ..
                 } => {
                     let source_matches = expected_source
                         .as_ref()
-                        .is_none_or(|expected_source| expected_source == source);
+                        .is_none_or(|expected_source| expected_source.matches(source));
 
                     let target_matches = expected_target
                         .as_ref()
-                        .is_none_or(|expected_target| expected_target == target);
+                        .is_none_or(|expected_target| expected_target.matches(target));
 
                     source_matches && target_matches
                 }
                 _ => false,
             },
+            Self::Schedule {
+                cron: expected_cron,
+                branch: expected_branch,
+                timezone: expected_timezone,
+            } => match &trigger.event {
+                TriggerEvent::Schedule {
+                    branch: Branch { name: branch, .. },
+                    cron,
+                    timezone,
+                } => {
+                    expected_cron == cron
+                        && expected_branch == branch
+                        && expected_timezone == timezone
+                }
+                _ => false,
+            },
+            Self::Tag { pattern } => match &trigger.event {
+                TriggerEvent::Tag {
+                    tag: Branch { name: tag, .. },
+                } => pattern.as_ref().is_none_or(|pattern| pattern.matches(tag)),
+                _ => false,
+            },
+            Self::Manual { .. } => matches!(trigger.event, TriggerEvent::Manual { .. }),
         }
     }
+
+    /// The changed-path filter of this trigger, if it has one
+    pub fn path_filter(&self) -> Option<PathFilter<'_>> {
+        let (paths, paths_ignore) = match self {
+            Self::Push {
+                paths,
+                paths_ignore,
+                ..
+            }
+            | Self::PullRequest {
+                paths,
+                paths_ignore,
+                ..
+            } => (paths.as_deref(), paths_ignore.as_deref()),
+            Self::Schedule { .. } | Self::Tag { .. } | Self::Manual { .. } => (None, None),
+        };
+
+        (paths.is_some() || paths_ignore.is_some()).then_some(PathFilter {
+            paths,
+            paths_ignore,
+        })
+    }
+
+    /// Whether the trigger matches the files changed by the event. If the changed files are
+    /// unknown, e.g. for the first push to a new branch or for too many changes to list,
+    /// path filters are ignored.
+    pub fn matches_changed_files(&self, changed_files: Option<&[String]>) -> bool {
+        self.path_filter()
+            .zip(changed_files)
+            .is_none_or(|(filter, changed_files)| filter.matches(changed_files))
+    }
 }
 
 #[cfg(test)]
@@ -88,7 +272,10 @@ mod tests {
         assert_eq!(
             trigger,
             TriggerConfiguration::Push {
-                branch: Some("main".to_owned())
+                branch: Some(RefPatterns::Single("main".to_owned())),
+                exclude: None,
+                paths: None,
+                paths_ignore: None
             }
         )
     }
@@ -102,7 +289,249 @@ mod tests {
       "#;
 
         let trigger: TriggerConfiguration = serde_json::from_str(json).unwrap();
-        assert_eq!(trigger, TriggerConfiguration::Push { branch: None })
+        assert_eq!(
+            trigger,
+            TriggerConfiguration::Push {
+                branch: None,
+                exclude: None,
+                paths: None,
+                paths_ignore: None
+            }
+        )
+    }
+
+    #[test]
+    fn deserialize_schedule_trigger_configuration() {
+        let json = r#"
+      {
+          "event": "schedule",
+          "cron": "0 2 * * *",
+          "branch": "main",
+          "timezone": "Europe/Vienna"
+      }
+      "#;
+
+        let trigger: TriggerConfiguration = serde_json::from_str(json).unwrap();
+        assert_eq!(
+            trigger,
+            TriggerConfiguration::Schedule {
+                cron: "0 2 * * *".to_owned(),
+                branch: "main".to_owned(),
+                timezone: Some("Europe/Vienna".to_owned())
+            }
+        )
+    }
+
+    #[test]
+    fn schedule_trigger_configuration_should_match_same_cron_and_branch() {
+        let configuration = TriggerConfiguration::Schedule {
+            cron: "0 2 * * *".to_owned(),
+            branch: "main".to_owned(),
+            timezone: None,
+        };
+        let trigger = |cron: &str, branch: &str| Trigger {
+            repository_owner: "Owner".to_owned(),
+            repository_name: "Repo".to_owned(),
+            installation_id: 789,
+            event: TriggerEvent::Schedule {
+                branch: Branch {
+                    name: branch.to_owned(),
+                    commit: "123".to_owned(),
+                },
+                cron: cron.to_owned(),
+                timezone: None,
+            },
+        };
+
+        assert!(configuration.matches(&trigger("0 2 * * *", "main")));
+        assert!(!configuration.matches(&trigger("0 3 * * *", "main")));
+        assert!(!configuration.matches(&trigger("0 2 * * *", "develop")));
+    }
+
+    #[test]
+    fn schedule_trigger_configuration_should_only_match_same_timezone() {
+        let configuration = |timezone: Option<&str>| TriggerConfiguration::Schedule {
+            cron: "0 2 * * *".to_owned(),
+            branch: "main".to_owned(),
+            timezone: timezone.map(str::to_owned),
+        };
+        let trigger = |timezone: Option<&str>| Trigger {
+            repository_owner: "Owner".to_owned(),
+            repository_name: "Repo".to_owned(),
+            installation_id: 789,
+            event: TriggerEvent::Schedule {
+                branch: Branch {
+                    name: "main".to_owned(),
+                    commit: "123".to_owned(),
+                },
+                cron: "0 2 * * *".to_owned(),
+                timezone: timezone.map(str::to_owned),
+            },
+        };
+
+        let vienna = configuration(Some("Europe/Vienna"));
+        let tokyo = configuration(Some("Asia/Tokyo"));
+
+        assert!(vienna.matches(&trigger(Some("Europe/Vienna"))));
+        assert!(!vienna.matches(&trigger(Some("Asia/Tokyo"))));
+        assert!(!tokyo.matches(&trigger(Some("Europe/Vienna"))));
+        assert!(!vienna.matches(&trigger(None)));
+        assert!(configuration(None).matches(&trigger(None)));
+    }
+
+    #[test]
+    fn push_trigger_configuration_should_match_ref_patterns_without_excluded() {
+        let json = r#"
+      {
+          "event": "push",
+          "branches": ["*", "release/**"],
+          "exclude": ["wip/*"]
+      }
+      "#;
+
+        let configuration: TriggerConfiguration = serde_json::from_str(json).unwrap();
+        let trigger = |branch: &str| Trigger {
+            repository_owner: "Owner".to_owned(),
+            repository_name: "Repo".to_owned(),
+            installation_id: 789,
+            event: TriggerEvent::Push {
+                branch: Branch {
+                    name: branch.to_owned(),
+                    commit: "123".to_owned(),
+                },
+                before: None,
+            },
+        };
+
+        assert!(configuration.matches(&trigger("main")));
+        assert!(configuration.matches(&trigger("release/1.0/hotfix")));
+        assert!(!configuration.matches(&trigger("wip/experiment")));
+        assert!(!configuration.matches(&trigger("feature/login")));
+    }
+
+    #[test]
+    fn push_trigger_configuration_should_not_exclude_anything_with_empty_exclude() {
+        let json = r#"
+      {
+          "event": "push",
+          "exclude": []
+      }
+      "#;
+
+        let configuration: TriggerConfiguration = serde_json::from_str(json).unwrap();
+        let trigger = Trigger {
+            repository_owner: "Owner".to_owned(),
+            repository_name: "Repo".to_owned(),
+            installation_id: 789,
+            event: TriggerEvent::Push {
+                branch: Branch {
+                    name: "main".to_owned(),
+                    commit: "123".to_owned(),
+                },
+                before: None,
+            },
+        };
+
+        assert!(configuration.matches(&trigger));
+    }
+
+    #[test]
+    fn pull_request_trigger_configuration_should_match_ref_patterns() {
+        let json = r#"
+      {
+          "event": "pull_request",
+          "target": "main",
+          "source": ["feature/**", "!feature/wip/**"]
+      }
+      "#;
+
+        let configuration: TriggerConfiguration = serde_json::from_str(json).unwrap();
+        let trigger = |source: &str, target: &str| Trigger {
+            repository_owner: "Owner".to_owned(),
+            repository_name: "Repo".to_owned(),
+            installation_id: 789,
+            event: TriggerEvent::PullRequest {
+                number: 1,
+                source: Branch {
+                    name: source.to_owned(),
+                    commit: "123".to_owned(),
+                },
+                target: Branch {
+                    name: target.to_owned(),
+                    commit: "456".to_owned(),
+                },
+                from_fork: false,
+            },
+        };
+
+        assert!(configuration.matches(&trigger("feature/login", "main")));
+        assert!(!configuration.matches(&trigger("feature/wip/login", "main")));
+        assert!(!configuration.matches(&trigger("feature/login", "develop")));
+        assert!(!configuration.matches(&trigger("bugfix/login", "main")));
+    }
+
+    #[test]
+    fn trigger_configuration_should_match_changed_files_with_path_filter() {
+        let json = r#"
+      {
+          "event": "push",
+          "paths": ["services/api/**"],
+          "paths_ignore": ["**/*.md"]
+      }
+      "#;
+
+        let configuration: TriggerConfiguration = serde_json::from_str(json).unwrap();
+        let files =
+            |files: &[&str]| -> Vec<String> { files.iter().map(|&file| file.to_owned()).collect() };
+
+        assert!(configuration.matches_changed_files(Some(&files(&["services/api/main.rs"]))));
+        assert!(!configuration.matches_changed_files(Some(&files(&["services/api/README.md"]))));
+        assert!(!configuration.matches_changed_files(Some(&files(&["services/web/main.rs"]))));
+        assert!(configuration.matches_changed_files(None));
+    }
+
+    #[test]
+    fn trigger_configuration_without_path_filter_should_match_any_changed_files() {
+        let configuration = TriggerConfiguration::Push {
+            branch: None,
+            exclude: None,
+            paths: None,
+            paths_ignore: None,
+        };
+
+        assert!(configuration.path_filter().is_none());
+        assert!(configuration.matches_changed_files(Some(&[])));
+    }
+
+    #[test]
+    fn tag_trigger_configuration_should_match_tag_pattern() {
+        let json = r#"
+      {
+          "event": "tag",
+          "pattern": "v*"
+      }
+      "#;
+
+        let configuration: TriggerConfiguration = serde_json::from_str(json).unwrap();
+        let trigger = |event: TriggerEvent| Trigger {
+            repository_owner: "Owner".to_owned(),
+            repository_name: "Repo".to_owned(),
+            installation_id: 789,
+            event,
+        };
+        let tag = |name: &str| Branch {
+            name: name.to_owned(),
+            commit: "123".to_owned(),
+        };
+
+        assert!(configuration.matches(&trigger(TriggerEvent::Tag { tag: tag("v1.0.0") })));
+        assert!(!configuration.matches(&trigger(TriggerEvent::Tag {
+            tag: tag("nightly")
+        })));
+        assert!(!configuration.matches(&trigger(TriggerEvent::Push {
+            branch: tag("v1.0.0"),
+            before: None
+        })));
     }
 
     #[test]
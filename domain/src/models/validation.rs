@@ -0,0 +1,750 @@
+use std::collections::HashSet;
+
+use thiserror::Error;
+
+use super::{
+    artifact::is_valid_artifact_path,
+    cache::parse_cache_key,
+    input::InputType,
+    pipeline::{PipelineConfiguration, CLONE_STEP_NAME},
+    ref_pattern::RefPatterns,
+    schedule::CronSchedule,
+    secret::is_valid_secret_name,
+    service::is_valid_service_name,
+    trigger::TriggerConfiguration,
+};
+
+#[derive(Debug, Error, PartialEq, Eq)]
+pub enum ConfigurationError {
+    #[error("Step \"{0}\" is defined more than once")]
+    DuplicateStep(String),
+    #[error(
+        "Step name \"{0}\" is reserved for the implicit clone step, set `clone: false` to use it"
+    )]
+    ReservedStepName(String),
+    #[error("Step \"{step}\" depends on unknown step \"{dependency}\"")]
+    UnknownDependency { step: String, dependency: String },
+    #[error("Step \"{0}\" is part of a dependency cycle")]
+    DependencyCycle(String),
+    #[error("{0}")]
+    InvalidSchedule(String),
+    #[error("Invalid branch or tag pattern \"{pattern}\": {reason}")]
+    InvalidRefPattern { pattern: String, reason: String },
+    #[error("Invalid path pattern \"{pattern}\": {reason}")]
+    InvalidPathPattern { pattern: String, reason: String },
+    #[error("Invalid input \"{input}\": {reason}")]
+    InvalidInput { input: String, reason: String },
+    #[error("Invalid environment variable name \"{0}\"")]
+    InvalidEnvironmentVariable(String),
+    #[error("Invalid secret name \"{0}\"")]
+    InvalidSecretName(String),
+    #[error("Invalid matrix: {0}")]
+    InvalidMatrix(String),
+    #[error("Invalid artifact path \"{0}\", paths have to be relative to the workspace")]
+    InvalidArtifactPath(String),
+    #[error("Invalid cache path \"{0}\", paths have to be relative to the workspace")]
+    InvalidCachePath(String),
+    #[error("Invalid cache key \"{key}\": {reason}")]
+    InvalidCacheKey { key: String, reason: String },
+    #[error("Invalid resources of step \"{step}\": {reason}")]
+    InvalidResources { step: String, reason: String },
+    #[error("Service \"{0}\" is defined more than once")]
+    DuplicateService(String),
+    #[error("Invalid service name \"{0}\", only lowercase letters, digits and dashes are allowed")]
+    InvalidServiceName(String),
+}
+
+impl PipelineConfiguration {
+    pub fn validate(&self) -> Result<(), ConfigurationError> {
+        self.validate_step_names()?;
+        self.validate_dependencies()?;
+        self.validate_schedules()?;
+        self.validate_ref_patterns()?;
+        self.validate_inputs()?;
+        self.validate_environment()?;
+        self.validate_matrix()?;
+        self.validate_services()?;
+        self.validate_artifacts()?;
+        self.validate_caches()?;
+        self.validate_resources()?;
+
+        Ok(())
+    }
+
+    fn validate_resources(&self) -> Result<(), ConfigurationError> {
+        for step in &self.steps {
+            if let Some(resources) = &step.resources {
+                resources
+                    .validate()
+                    .map_err(|reason| ConfigurationError::InvalidResources {
+                        step: step.name.clone(),
+                        reason,
+                    })?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Keys are checked after expanding the matrix, as they can contain matrix values
+    fn validate_caches(&self) -> Result<(), ConfigurationError> {
+        for configuration in self.clone().expand_matrix()? {
+            let caches = configuration
+                .steps
+                .iter()
+                .flat_map(|step| step.cache.iter().flatten());
+
+            for cache in caches {
+                if let Some(path) = cache
+                    .paths
+                    .iter()
+                    .find(|path| !is_valid_artifact_path(path))
+                {
+                    return Err(ConfigurationError::InvalidCachePath(path.clone()));
+                }
+
+                let keys = std::iter::once(&cache.key).chain(cache.restore_keys.iter().flatten());
+
+                for key in keys {
+                    if key.is_empty() {
+                        return Err(ConfigurationError::InvalidCacheKey {
+                            key: key.clone(),
+                            reason: "keys can not be empty".to_owned(),
+                        });
+                    }
+
+                    parse_cache_key(key).map_err(|reason| ConfigurationError::InvalidCacheKey {
+                        key: key.clone(),
+                        reason,
+                    })?;
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    fn validate_artifacts(&self) -> Result<(), ConfigurationError> {
+        let paths = self
+            .steps
+            .iter()
+            .flat_map(|step| step.artifacts.iter().flatten());
+
+        for path in paths {
+            if !is_valid_artifact_path(path) {
+                return Err(ConfigurationError::InvalidArtifactPath(path.clone()));
+            }
+        }
+
+        Ok(())
+    }
+
+    fn validate_services(&self) -> Result<(), ConfigurationError> {
+        let mut names = HashSet::new();
+
+        for service in self.services.iter().flatten() {
+            if !is_valid_service_name(&service.name) {
+                return Err(ConfigurationError::InvalidServiceName(service.name.clone()));
+            }
+
+            if !names.insert(service.name.as_str()) {
+                return Err(ConfigurationError::DuplicateService(service.name.clone()));
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Expands the matrix to catch references to unknown values before the pipeline is run
+    fn validate_matrix(&self) -> Result<(), ConfigurationError> {
+        if self.matrix.is_some() {
+            self.clone().expand_matrix()?;
+        }
+
+        Ok(())
+    }
+
+    fn validate_environment(&self) -> Result<(), ConfigurationError> {
+        let environments = std::iter::once(&self.environment)
+            .chain(self.steps.iter().map(|step| &step.environment))
+            .chain(self.steps.iter().map(|step| &step.secrets))
+            .chain(
+                self.services
+                    .iter()
+                    .flatten()
+                    .map(|service| &service.environment),
+            )
+            .flatten();
+
+        for name in environments.flat_map(|environment| environment.keys()) {
+            if !is_variable_name(name) {
+                return Err(ConfigurationError::InvalidEnvironmentVariable(name.clone()));
+            }
+        }
+
+        let secret_names = self
+            .steps
+            .iter()
+            .flat_map(|step| step.secrets.iter().flatten())
+            .map(|(_, secret)| secret);
+
+        for name in secret_names {
+            if !is_valid_secret_name(name) {
+                return Err(ConfigurationError::InvalidSecretName(name.clone()));
+            }
+        }
+
+        Ok(())
+    }
+
+    fn validate_inputs(&self) -> Result<(), ConfigurationError> {
+        let mut variables = HashSet::new();
+
+        for (name, input) in self.manual_inputs().iter().flatten() {
+            let invalid = |reason: String| ConfigurationError::InvalidInput {
+                input: name.clone(),
+                reason,
+            };
+
+            // Inputs are passed to steps as `INPUT_<NAME>` environment variables
+            if !is_variable_name(name) {
+                return Err(invalid(
+                    "names may only contain letters, digits and underscores".to_owned(),
+                ));
+            }
+
+            if !variables.insert(name.to_uppercase()) {
+                return Err(invalid(
+                    "names have to differ in more than upper and lower case".to_owned(),
+                ));
+            }
+
+            if input.input_type == InputType::Choice
+                && input.options.as_ref().is_none_or(Vec::is_empty)
+            {
+                return Err(invalid("choice inputs need options".to_owned()));
+            }
+
+            if let Some(default) = &input.default {
+                input
+                    .convert(name, default)
+                    .map_err(|err| invalid(format!("invalid default, {err}")))?;
+            }
+        }
+
+        Ok(())
+    }
+
+    fn validate_ref_patterns(&self) -> Result<(), ConfigurationError> {
+        for trigger in &self.trigger {
+            let patterns: Vec<&RefPatterns> = match trigger {
+                TriggerConfiguration::Push {
+                    branch, exclude, ..
+                } => branch.iter().chain(exclude).collect(),
+                TriggerConfiguration::PullRequest { target, source, .. } => {
+                    target.iter().chain(source).collect()
+                }
+                TriggerConfiguration::Tag { pattern } => pattern.iter().collect(),
+                TriggerConfiguration::Schedule { .. } | TriggerConfiguration::Manual { .. } => {
+                    vec![]
+                }
+            };
+
+            for patterns in patterns {
+                if let Some((pattern, reason)) = patterns.find_invalid() {
+                    return Err(ConfigurationError::InvalidRefPattern {
+                        pattern: pattern.to_owned(),
+                        reason,
+                    });
+                }
+            }
+
+            if let Some((pattern, reason)) = trigger
+                .path_filter()
+                .and_then(|filter| filter.find_invalid())
+            {
+                return Err(ConfigurationError::InvalidPathPattern {
+                    pattern: pattern.to_owned(),
+                    reason,
+                });
+            }
+        }
+
+        Ok(())
+    }
+
+    fn validate_schedules(&self) -> Result<(), ConfigurationError> {
+        for trigger in &self.trigger {
+            if let TriggerConfiguration::Schedule { cron, timezone, .. } = trigger {
+                CronSchedule::parse(cron, timezone.as_deref())
+                    .map_err(ConfigurationError::InvalidSchedule)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    fn validate_step_names(&self) -> Result<(), ConfigurationError> {
+        let mut names = HashSet::new();
+
+        for step in &self.steps {
+            if !names.insert(step.name.as_str()) {
+                return Err(ConfigurationError::DuplicateStep(step.name.clone()));
+            }
+
+            if step.name == CLONE_STEP_NAME && self.clone_options().is_some() {
+                return Err(ConfigurationError::ReservedStepName(step.name.clone()));
+            }
+        }
+
+        Ok(())
+    }
+
+    fn validate_dependencies(&self) -> Result<(), ConfigurationError> {
+        for step in &self.steps {
+            for dependency in step.depends_on.iter().flatten() {
+                let is_clone_step = dependency == CLONE_STEP_NAME && self.clone_options().is_some();
+
+                if !is_clone_step && !self.steps.iter().any(|step| &step.name == dependency) {
+                    return Err(ConfigurationError::UnknownDependency {
+                        step: step.name.clone(),
+                        dependency: dependency.clone(),
+                    });
+                }
+            }
+        }
+
+        let dependencies = self.step_dependencies();
+        let mut states = vec![VisitState::Unvisited; self.steps.len()];
+
+        for index in 0..self.steps.len() {
+            if let Some(index) = find_cycle(index, &dependencies, &mut states) {
+                return Err(ConfigurationError::DependencyCycle(
+                    self.steps[index].name.clone(),
+                ));
+            }
+        }
+
+        Ok(())
+    }
+}
+
+fn is_variable_name(name: &str) -> bool {
+    name.starts_with(|char: char| char.is_ascii_alphabetic() || char == '_')
+        && name
+            .chars()
+            .all(|char| char.is_ascii_alphanumeric() || char == '_')
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum VisitState {
+    Unvisited,
+    InProgress,
+    Done,
+}
+
+fn find_cycle(
+    index: usize,
+    dependencies: &[Vec<usize>],
+    states: &mut [VisitState],
+) -> Option<usize> {
+    match states[index] {
+        VisitState::Done => return None,
+        VisitState::InProgress => return Some(index),
+        VisitState::Unvisited => {}
+    }
+
+    states[index] = VisitState::InProgress;
+
+    for dependency in &dependencies[index] {
+        if let Some(index) = find_cycle(*dependency, dependencies, states) {
+            return Some(index);
+        }
+    }
+
+    states[index] = VisitState::Done;
+
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(json: &str) -> PipelineConfiguration {
+        serde_json::from_str(json).unwrap()
+    }
+
+    #[test]
+    fn validate_should_accept_steps_without_dependencies() {
+        let configuration = parse(
+            r#"{
+                "name": "Pipeline",
+                "trigger": [],
+                "steps": [
+                    { "name": "a", "image": "alpine" },
+                    { "name": "b", "image": "alpine" }
+                ]
+            }"#,
+        );
+
+        assert_eq!(configuration.validate(), Ok(()));
+        assert_eq!(configuration.step_dependencies(), vec![vec![], vec![0]]);
+    }
+
+    #[test]
+    fn validate_should_accept_dependency_graph() {
+        let configuration = parse(
+            r#"{
+                "name": "Pipeline",
+                "trigger": [],
+                "clone": false,
+                "steps": [
+                    { "name": "clone", "image": "alpine" },
+                    { "name": "lint", "image": "alpine", "depends_on": ["clone"] },
+                    { "name": "test", "image": "alpine", "depends_on": ["clone"] },
+                    { "name": "publish", "image": "alpine", "depends_on": ["lint", "test"] }
+                ]
+            }"#,
+        );
+
+        assert_eq!(configuration.validate(), Ok(()));
+        assert_eq!(
+            configuration.step_dependencies(),
+            vec![vec![], vec![0], vec![0], vec![1, 2]]
+        );
+    }
+
+    #[test]
+    fn validate_should_reject_unknown_dependency() {
+        let configuration = parse(
+            r#"{
+                "name": "Pipeline",
+                "trigger": [],
+                "steps": [
+                    { "name": "a", "image": "alpine", "depends_on": ["b"] }
+                ]
+            }"#,
+        );
+
+        assert_eq!(
+            configuration.validate(),
+            Err(ConfigurationError::UnknownDependency {
+                step: "a".to_owned(),
+                dependency: "b".to_owned()
+            })
+        );
+    }
+
+    #[test]
+    fn validate_should_reject_dependency_cycle() {
+        let configuration = parse(
+            r#"{
+                "name": "Pipeline",
+                "trigger": [],
+                "steps": [
+                    { "name": "a", "image": "alpine", "depends_on": ["c"] },
+                    { "name": "b", "image": "alpine", "depends_on": ["a"] },
+                    { "name": "c", "image": "alpine", "depends_on": ["b"] }
+                ]
+            }"#,
+        );
+
+        assert_eq!(
+            configuration.validate(),
+            Err(ConfigurationError::DependencyCycle("a".to_owned()))
+        );
+    }
+
+    #[test]
+    fn validate_should_reject_invalid_schedule() {
+        let configuration = parse(
+            r#"{
+                "name": "Pipeline",
+                "trigger": [{ "event": "schedule", "cron": "0 25 * * *", "branch": "main" }],
+                "steps": []
+            }"#,
+        );
+
+        assert!(matches!(
+            configuration.validate(),
+            Err(ConfigurationError::InvalidSchedule(_))
+        ));
+    }
+
+    #[test]
+    fn validate_should_reject_invalid_ref_pattern() {
+        let configuration = parse(
+            r#"{
+                "name": "Pipeline",
+                "trigger": [{ "event": "push", "branches": ["main"], "exclude": ["wip/[a"] }],
+                "steps": []
+            }"#,
+        );
+
+        assert!(matches!(
+            configuration.validate(),
+            Err(ConfigurationError::InvalidRefPattern { pattern, .. }) if pattern == "wip/[a"
+        ));
+    }
+
+    #[test]
+    fn validate_should_reject_invalid_path_pattern() {
+        let configuration = parse(
+            r#"{
+                "name": "Pipeline",
+                "trigger": [{ "event": "pull_request", "paths": ["services/{api"] }],
+                "steps": []
+            }"#,
+        );
+
+        assert!(matches!(
+            configuration.validate(),
+            Err(ConfigurationError::InvalidPathPattern { pattern, .. }) if pattern == "services/{api"
+        ));
+    }
+
+    #[test]
+    fn validate_should_reject_invalid_input_default() {
+        let configuration = parse(
+            r#"{
+                "name": "Pipeline",
+                "trigger": [{ "event": "manual", "inputs": { "dry_run": { "type": "boolean", "default": "yes" } } }],
+                "steps": []
+            }"#,
+        );
+
+        assert!(matches!(
+            configuration.validate(),
+            Err(ConfigurationError::InvalidInput { input, .. }) if input == "dry_run"
+        ));
+    }
+
+    #[test]
+    fn validate_should_reject_input_name_unusable_as_variable() {
+        let configuration = parse(
+            r#"{
+                "name": "Pipeline",
+                "trigger": [{ "event": "manual", "inputs": { "dry-run": {} } }],
+                "steps": []
+            }"#,
+        );
+
+        assert!(matches!(
+            configuration.validate(),
+            Err(ConfigurationError::InvalidInput { input, .. }) if input == "dry-run"
+        ));
+    }
+
+    #[test]
+    fn validate_should_reject_input_names_differing_only_in_case() {
+        let configuration = parse(
+            r#"{
+                "name": "Pipeline",
+                "trigger": [{ "event": "manual", "inputs": { "dry_run": {}, "DRY_RUN": {} } }],
+                "steps": []
+            }"#,
+        );
+
+        assert!(matches!(
+            configuration.validate(),
+            Err(ConfigurationError::InvalidInput { input, .. }) if input.eq_ignore_ascii_case("dry_run")
+        ));
+    }
+
+    #[test]
+    fn validate_should_reject_invalid_environment_variable_name() {
+        let configuration = parse(
+            r#"{
+                "name": "Pipeline",
+                "trigger": [],
+                "environment": { "RUST_LOG": "debug" },
+                "steps": [
+                    { "name": "a", "image": "alpine", "environment": { "CARGO=TERM": "1" } }
+                ]
+            }"#,
+        );
+
+        assert_eq!(
+            configuration.validate(),
+            Err(ConfigurationError::InvalidEnvironmentVariable(
+                "CARGO=TERM".to_owned()
+            ))
+        );
+    }
+
+    #[test]
+    fn validate_should_reject_invalid_secret_name() {
+        let configuration = parse(
+            r#"{
+                "name": "Pipeline",
+                "trigger": [],
+                "steps": [
+                    { "name": "a", "image": "alpine", "secrets": { "TOKEN": "npm token" } }
+                ]
+            }"#,
+        );
+
+        assert_eq!(
+            configuration.validate(),
+            Err(ConfigurationError::InvalidSecretName(
+                "npm token".to_owned()
+            ))
+        );
+    }
+
+    #[test]
+    fn validate_should_reserve_clone_step_name_unless_clone_is_disabled() {
+        let configuration = parse(
+            r#"{
+                "name": "Pipeline",
+                "trigger": [],
+                "steps": [
+                    { "name": "clone", "image": "alpine" }
+                ]
+            }"#,
+        );
+
+        assert_eq!(
+            configuration.validate(),
+            Err(ConfigurationError::ReservedStepName("clone".to_owned()))
+        );
+
+        let configuration = parse(
+            r#"{
+                "name": "Pipeline",
+                "trigger": [],
+                "clone": false,
+                "steps": [
+                    { "name": "clone", "image": "alpine" }
+                ]
+            }"#,
+        );
+
+        assert_eq!(configuration.validate(), Ok(()));
+    }
+
+    #[test]
+    fn validate_should_accept_dependency_on_clone_step() {
+        let configuration = parse(
+            r#"{
+                "name": "Pipeline",
+                "trigger": [],
+                "steps": [
+                    { "name": "a", "image": "alpine", "depends_on": ["clone"] }
+                ]
+            }"#,
+        );
+
+        assert_eq!(configuration.validate(), Ok(()));
+    }
+
+    #[test]
+    fn validate_should_reject_duplicate_step_names() {
+        let configuration = parse(
+            r#"{
+                "name": "Pipeline",
+                "trigger": [],
+                "steps": [
+                    { "name": "a", "image": "alpine" },
+                    { "name": "a", "image": "alpine" }
+                ]
+            }"#,
+        );
+
+        assert_eq!(
+            configuration.validate(),
+            Err(ConfigurationError::DuplicateStep("a".to_owned()))
+        );
+    }
+
+    #[test]
+    fn validate_should_reject_invalid_or_duplicate_service_names() {
+        let configuration = parse(
+            r#"{
+                "name": "Pipeline",
+                "trigger": [],
+                "services": [{ "name": "Postgres_DB", "image": "postgres:16" }],
+                "steps": []
+            }"#,
+        );
+
+        assert_eq!(
+            configuration.validate(),
+            Err(ConfigurationError::InvalidServiceName(
+                "Postgres_DB".to_owned()
+            ))
+        );
+
+        let configuration = parse(
+            r#"{
+                "name": "Pipeline",
+                "trigger": [],
+                "services": [
+                    {
+                        "name": "postgres",
+                        "image": "postgres:16",
+                        "environment": { "POSTGRES_PASSWORD": "postgres" },
+                        "health_check": { "command": "pg_isready", "interval": "1s" }
+                    },
+                    { "name": "postgres", "image": "postgres:15" }
+                ],
+                "steps": []
+            }"#,
+        );
+
+        assert_eq!(
+            configuration.validate(),
+            Err(ConfigurationError::DuplicateService("postgres".to_owned()))
+        );
+    }
+
+    #[test]
+    fn validate_should_reject_invalid_cache_keys_and_paths() {
+        let configuration = |cache: &str| {
+            parse(&format!(
+                r#"{{
+                    "name": "Build",
+                    "trigger": [],
+                    "matrix": {{ "rust": ["stable"] }},
+                    "steps": [{{ "name": "build", "image": "rust", "cache": [{cache}] }}]
+                }}"#
+            ))
+        };
+
+        assert_eq!(
+            configuration(
+                r#"{ "key": "${{ matrix.rust }}-${{ hashFiles('Cargo.lock') }}", "paths": ["target"] }"#
+            )
+            .validate(),
+            Ok(())
+        );
+        assert!(matches!(
+            configuration(r#"{ "key": "cargo-${{ commit }}", "paths": ["target"] }"#).validate(),
+            Err(ConfigurationError::InvalidCacheKey { key, .. }) if key == "cargo-${{ commit }}"
+        ));
+        assert_eq!(
+            configuration(r#""../target""#).validate(),
+            Err(ConfigurationError::InvalidCachePath("../target".to_owned()))
+        );
+    }
+
+    #[test]
+    fn validate_should_reject_invalid_resources() {
+        let configuration = parse(
+            r#"{
+                "name": "Build",
+                "trigger": [],
+                "steps": [
+                    { "name": "a", "image": "rust", "resources": { "cpus": 1.5, "memory": "2g", "pids": 256 } },
+                    { "name": "b", "image": "rust", "resources": { "memory": "1m" } }
+                ]
+            }"#,
+        );
+
+        assert!(matches!(
+            configuration.validate(),
+            Err(ConfigurationError::InvalidResources { step, .. }) if step == "b"
+        ));
+    }
+}
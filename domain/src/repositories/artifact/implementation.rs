@@ -0,0 +1,211 @@
+use std::collections::HashSet;
+
+use chrono::NaiveDateTime;
+use diesel::prelude::*;
+
+use crate::{
+    repositories::{connection, RepositoryError},
+    Artifact, PipelineId, StepId,
+};
+
+pub struct ArtifactsRepository {
+    connection: SqliteConnection,
+}
+
+impl ArtifactsRepository {
+    pub fn create(database_url: &str) -> Result<Self, RepositoryError> {
+        let connection = connection::establish(database_url)?;
+
+        Ok(Self { connection })
+    }
+}
+
+#[derive(Queryable, Selectable)]
+#[diesel(table_name = crate::schema::artifacts)]
+#[diesel(check_for_backend(diesel::sqlite::Sqlite))]
+struct RawArtifact {
+    id: i32,
+    pipeline_id: PipelineId,
+    step_id: StepId,
+    path: String,
+    digest: String,
+    size: i64,
+    created_at: NaiveDateTime,
+}
+
+impl RawArtifact {
+    fn into_artifact(self) -> Artifact {
+        Artifact {
+            id: self.id,
+            pipeline_id: self.pipeline_id,
+            step_id: self.step_id,
+            path: self.path,
+            digest: self.digest,
+            size: self.size,
+            created_at: self.created_at,
+        }
+    }
+}
+
+#[derive(Insertable)]
+#[diesel(table_name = crate::schema::artifacts)]
+#[diesel(check_for_backend(diesel::sqlite::Sqlite))]
+struct NewArtifact<'a> {
+    pipeline_id: PipelineId,
+    step_id: StepId,
+    path: &'a str,
+    digest: &'a str,
+    size: i64,
+    created_at: NaiveDateTime,
+}
+
+impl super::ArtifactsRepository for ArtifactsRepository {
+    fn create(
+        &mut self,
+        pipeline_id: PipelineId,
+        step_id: StepId,
+        path: &str,
+        digest: &str,
+        size: i64,
+        time: NaiveDateTime,
+    ) -> Result<Artifact, RepositoryError> {
+        use crate::schema::artifacts;
+
+        let artifact = diesel::insert_into(artifacts::table)
+            .values(NewArtifact {
+                pipeline_id,
+                step_id,
+                path,
+                digest,
+                size,
+                created_at: time,
+            })
+            .returning(RawArtifact::as_returning())
+            .get_result(&mut self.connection)?;
+
+        Ok(artifact.into_artifact())
+    }
+
+    fn list_for_pipeline(
+        &mut self,
+        pipeline_id: PipelineId,
+    ) -> Result<Vec<Artifact>, RepositoryError> {
+        use crate::schema::artifacts;
+
+        let artifacts = artifacts::table
+            .filter(artifacts::pipeline_id.eq(pipeline_id))
+            .order_by(artifacts::id)
+            .select(RawArtifact::as_select())
+            .load(&mut self.connection)?;
+
+        Ok(artifacts
+            .into_iter()
+            .map(RawArtifact::into_artifact)
+            .collect())
+    }
+
+    fn find(
+        &mut self,
+        pipeline_id: PipelineId,
+        id: i32,
+    ) -> Result<Option<Artifact>, RepositoryError> {
+        use crate::schema::artifacts;
+
+        let artifact = artifacts::table
+            .find(id)
+            .filter(artifacts::pipeline_id.eq(pipeline_id))
+            .select(RawArtifact::as_select())
+            .first(&mut self.connection)
+            .optional()?;
+
+        Ok(artifact.map(RawArtifact::into_artifact))
+    }
+
+    fn delete_created_before(&mut self, time: NaiveDateTime) -> Result<usize, RepositoryError> {
+        use crate::schema::artifacts;
+
+        let deleted = diesel::delete(artifacts::table.filter(artifacts::created_at.lt(time)))
+            .execute(&mut self.connection)?;
+
+        Ok(deleted)
+    }
+
+    fn referenced_digests(&mut self) -> Result<HashSet<String>, RepositoryError> {
+        use crate::schema::artifacts;
+
+        let digests = artifacts::table
+            .select(artifacts::digest)
+            .distinct()
+            .load::<String>(&mut self.connection)?;
+
+        Ok(digests.into_iter().collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::DateTime;
+
+    use super::*;
+    use crate::{
+        repositories::{pipeline, ArtifactsRepository as _, PipelinesRepository as _},
+        Branch, PipelineConfiguration, Trigger, TriggerEvent,
+    };
+
+    fn time(seconds: i64) -> NaiveDateTime {
+        DateTime::from_timestamp(seconds, 0).unwrap().naive_utc()
+    }
+
+    #[test]
+    fn artifacts_should_be_listed_per_pipeline_and_expire() {
+        let database_url = "file:artifacts_repository?mode=memory&cache=shared";
+        let mut connection = connection::establish(database_url).unwrap();
+        connection::run_migrations(&mut connection).unwrap();
+        let mut pipelines =
+            pipeline::implementation::PipelinesRepository::create(database_url).unwrap();
+        let mut repository = ArtifactsRepository::create(database_url).unwrap();
+
+        let trigger = Trigger {
+            repository_owner: "Owner".to_owned(),
+            repository_name: "Repo".to_owned(),
+            installation_id: 1,
+            event: TriggerEvent::Push {
+                branch: Branch {
+                    name: "main".to_owned(),
+                    commit: "abc123".to_owned(),
+                },
+                before: None,
+            },
+        };
+        let configuration: PipelineConfiguration = serde_json::from_str(
+            r#"{
+                "name": "Build",
+                "trigger": [],
+                "steps": [{ "name": "build", "image": "alpine", "artifacts": ["target/app"] }]
+            }"#,
+        )
+        .unwrap();
+        let pipeline = pipelines.create_new(&trigger, configuration).unwrap();
+        let step_id = pipeline.steps[0].id;
+
+        let old = repository
+            .create(pipeline.id, step_id, "target/app", "aaa", 10, time(0))
+            .unwrap();
+        let new = repository
+            .create(pipeline.id, step_id, "target/app", "bbb", 20, time(100))
+            .unwrap();
+
+        assert_eq!(
+            repository.list_for_pipeline(pipeline.id).unwrap(),
+            vec![old.clone(), new.clone()]
+        );
+        assert_eq!(repository.find(pipeline.id, new.id).unwrap(), Some(new));
+        assert_eq!(repository.find(PipelineId(99), old.id).unwrap(), None);
+
+        assert_eq!(repository.delete_created_before(time(50)).unwrap(), 1);
+        assert_eq!(
+            repository.referenced_digests().unwrap(),
+            HashSet::from(["bbb".to_owned()])
+        );
+    }
+}
@@ -0,0 +1,33 @@
+use std::collections::HashSet;
+
+use chrono::NaiveDateTime;
+
+use super::RepositoryError;
+use crate::{Artifact, PipelineId, StepId};
+
+pub mod implementation;
+
+pub trait ArtifactsRepository: Send {
+    fn create(
+        &mut self,
+        pipeline_id: PipelineId,
+        step_id: StepId,
+        path: &str,
+        digest: &str,
+        size: i64,
+        time: NaiveDateTime,
+    ) -> Result<Artifact, RepositoryError>;
+    fn list_for_pipeline(
+        &mut self,
+        pipeline_id: PipelineId,
+    ) -> Result<Vec<Artifact>, RepositoryError>;
+    fn find(
+        &mut self,
+        pipeline_id: PipelineId,
+        id: i32,
+    ) -> Result<Option<Artifact>, RepositoryError>;
+    /// Deletes the records of artifacts created before the time, returning how many there were
+    fn delete_created_before(&mut self, time: NaiveDateTime) -> Result<usize, RepositoryError>;
+    /// The digests of all stored archives that are still referenced by an artifact
+    fn referenced_digests(&mut self) -> Result<HashSet<String>, RepositoryError>;
+}
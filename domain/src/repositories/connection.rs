@@ -0,0 +1,29 @@
+use diesel::{connection::SimpleConnection, prelude::*};
+use diesel_migrations::{embed_migrations, EmbeddedMigrations, MigrationHarness};
+
+use super::error::RepositoryError;
+
+const MIGRATIONS: EmbeddedMigrations = embed_migrations!("migrations");
+
+pub fn establish(database_url: &str) -> Result<SqliteConnection, RepositoryError> {
+    let mut connection = SqliteConnection::establish(database_url)?;
+
+    // Every repository holds its own connection, so writers need to wait for each other
+    connection.batch_execute("PRAGMA busy_timeout = 5000; PRAGMA foreign_keys = ON;")?;
+
+    Ok(connection)
+}
+
+pub fn run_migrations(connection: &mut SqliteConnection) -> Result<(), RepositoryError> {
+    connection
+        .run_pending_migrations(MIGRATIONS)
+        .map_err(|e| RepositoryError::Migration(e.to_string()))?;
+
+    Ok(())
+}
+
+/// An in-memory database that is shared between all connections opened with the same name
+#[cfg(test)]
+pub fn test_database_url(name: &str) -> String {
+    format!("file:{name}?mode=memory&cache=shared")
+}
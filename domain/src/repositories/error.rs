@@ -0,0 +1,14 @@
+use diesel::{result::Error as DieselError, ConnectionError};
+use thiserror::Error;
+
+#[derive(Debug, Error)]
+pub enum RepositoryError {
+    #[error("Could not establish database connection: {0}")]
+    Connection(#[from] ConnectionError),
+    #[error("Could not run database migrations: {0}")]
+    Migration(String),
+    #[error(transparent)]
+    Database(#[from] DieselError),
+    #[error("Could not (de)serialize stored value: {0}")]
+    Serialization(#[from] serde_json::Error),
+}
@@ -0,0 +1,209 @@
+use chrono::NaiveDateTime;
+use diesel::prelude::*;
+
+use crate::{
+    repositories::{connection, Page, Pagination, RepositoryError},
+    LogLine, LogStream, PipelineId, StepId,
+};
+
+pub struct LogsRepository {
+    connection: SqliteConnection,
+}
+
+impl LogsRepository {
+    pub fn create(database_url: &str) -> Result<Self, RepositoryError> {
+        let connection = connection::establish(database_url)?;
+
+        Ok(Self { connection })
+    }
+}
+
+impl super::LogsRepository for LogsRepository {
+    fn append(
+        &mut self,
+        pipeline_id: PipelineId,
+        step_id: StepId,
+        lines: &[LogLine],
+    ) -> Result<(), RepositoryError> {
+        use crate::schema::logs;
+
+        let lines = lines
+            .iter()
+            .map(|line| NewLogLine {
+                pipeline_id,
+                step_id,
+                number: line.number,
+                timestamp: line.timestamp,
+                stream: line.stream,
+                content: &line.content,
+            })
+            .collect::<Vec<_>>();
+
+        diesel::insert_into(logs::table)
+            .values(lines)
+            .execute(&mut self.connection)?;
+
+        Ok(())
+    }
+
+    fn find_for_step(
+        &mut self,
+        pipeline_id: PipelineId,
+        step_id: StepId,
+    ) -> Result<Vec<LogLine>, RepositoryError> {
+        use crate::schema::logs;
+
+        let lines = logs::table
+            .filter(logs::pipeline_id.eq(pipeline_id))
+            .filter(logs::step_id.eq(step_id))
+            .order_by(logs::number)
+            .select(RawLogLine::as_select())
+            .load(&mut self.connection)?;
+
+        Ok(lines.into_iter().map(RawLogLine::into_log_line).collect())
+    }
+
+    fn find_page_for_step(
+        &mut self,
+        pipeline_id: PipelineId,
+        step_id: StepId,
+        pagination: Pagination,
+    ) -> Result<Page<LogLine>, RepositoryError> {
+        use crate::schema::logs;
+
+        let step_logs = logs::table
+            .filter(logs::pipeline_id.eq(pipeline_id))
+            .filter(logs::step_id.eq(step_id));
+
+        let total = step_logs.count().get_result(&mut self.connection)?;
+        let lines = step_logs
+            .order_by(logs::number)
+            .limit(pagination.limit())
+            .offset(pagination.offset())
+            .select(RawLogLine::as_select())
+            .load(&mut self.connection)?;
+
+        let lines = lines.into_iter().map(RawLogLine::into_log_line).collect();
+
+        Ok(Page::new(lines, pagination, total))
+    }
+}
+
+#[derive(Queryable, Selectable)]
+#[diesel(table_name = crate::schema::logs)]
+#[diesel(check_for_backend(diesel::sqlite::Sqlite))]
+struct RawLogLine {
+    pub number: i32,
+    pub timestamp: NaiveDateTime,
+    pub stream: LogStream,
+    pub content: String,
+}
+
+impl RawLogLine {
+    fn into_log_line(self) -> LogLine {
+        LogLine {
+            number: self.number,
+            timestamp: self.timestamp,
+            stream: self.stream,
+            content: self.content,
+        }
+    }
+}
+
+#[derive(Insertable)]
+#[diesel(table_name = crate::schema::logs)]
+#[diesel(check_for_backend(diesel::sqlite::Sqlite))]
+struct NewLogLine<'a> {
+    pub pipeline_id: PipelineId,
+    pub step_id: StepId,
+    pub number: i32,
+    pub timestamp: NaiveDateTime,
+    pub stream: LogStream,
+    pub content: &'a str,
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::DateTime;
+
+    use super::*;
+    use crate::{
+        repositories::{connection::test_database_url, Repositories},
+        Branch, Trigger, TriggerEvent,
+    };
+
+    fn line(number: i32, stream: LogStream, content: &str) -> LogLine {
+        LogLine {
+            number,
+            timestamp: DateTime::from_timestamp(1_700_000_000 + number as i64, 0)
+                .unwrap()
+                .naive_utc(),
+            stream,
+            content: content.to_owned(),
+        }
+    }
+
+    #[test]
+    fn find_for_step_should_return_appended_lines_in_order() {
+        let repositories = Repositories::build(&test_database_url("logs")).unwrap();
+        let trigger = Trigger {
+            repository_owner: "Owner".to_owned(),
+            repository_name: "Repo".to_owned(),
+            installation_id: 789,
+            event: TriggerEvent::Push {
+                branch: Branch {
+                    name: "main".to_owned(),
+                    commit: "123".to_owned(),
+                },
+                before: None,
+            },
+        };
+        let configuration = serde_json::from_str(
+            r#"{
+                "name": "Pipeline",
+                "trigger": [],
+                "steps": [{ "name": "a", "image": "alpine" }]
+            }"#,
+        )
+        .unwrap();
+        let pipeline = repositories
+            .pipelines
+            .lock()
+            .unwrap()
+            .create_new(&trigger, configuration)
+            .unwrap();
+        let step_id = pipeline.steps[0].id;
+
+        let mut logs = repositories.logs.lock().unwrap();
+        logs.append(
+            pipeline.id,
+            step_id,
+            &[
+                line(2, LogStream::Stdout, "second"),
+                line(1, LogStream::Stderr, "first"),
+            ],
+        )
+        .unwrap();
+        logs.append(pipeline.id, step_id, &[line(3, LogStream::Stdout, "third")])
+            .unwrap();
+
+        assert_eq!(
+            logs.find_for_step(pipeline.id, step_id).unwrap(),
+            vec![
+                line(1, LogStream::Stderr, "first"),
+                line(2, LogStream::Stdout, "second"),
+                line(3, LogStream::Stdout, "third"),
+            ]
+        );
+        assert_eq!(
+            logs.find_page_for_step(pipeline.id, step_id, Pagination::new(Some(2), Some(2)))
+                .unwrap(),
+            Page {
+                items: vec![line(3, LogStream::Stdout, "third")],
+                page: 2,
+                per_page: 2,
+                total: 3
+            }
+        );
+    }
+}
@@ -0,0 +1,25 @@
+use crate::{LogLine, PipelineId, StepId};
+
+use super::{Page, Pagination, RepositoryError};
+
+pub mod implementation;
+
+pub trait LogsRepository: Send {
+    fn append(
+        &mut self,
+        pipeline_id: PipelineId,
+        step_id: StepId,
+        lines: &[LogLine],
+    ) -> Result<(), RepositoryError>;
+    fn find_for_step(
+        &mut self,
+        pipeline_id: PipelineId,
+        step_id: StepId,
+    ) -> Result<Vec<LogLine>, RepositoryError>;
+    fn find_page_for_step(
+        &mut self,
+        pipeline_id: PipelineId,
+        step_id: StepId,
+        pagination: Pagination,
+    ) -> Result<Page<LogLine>, RepositoryError>;
+}
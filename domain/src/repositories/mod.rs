@@ -1,18 +1,57 @@
+mod artifact;
+mod connection;
+mod error;
+mod log;
+mod pagination;
 mod pipeline;
+mod schedule;
+mod secret;
 
 use std::sync::{Arc, Mutex};
 
-pub use pipeline::PipelinesRepository;
+pub use artifact::ArtifactsRepository;
+pub use error::RepositoryError;
+pub use log::LogsRepository;
+pub use pagination::{Page, Pagination};
+pub use pipeline::{PipelineFilter, PipelinesRepository};
+pub use schedule::{ScheduleKey, SchedulesRepository};
+pub use secret::SecretsRepository;
 
+#[derive(Clone)]
 pub struct Repositories {
     pub pipelines: Arc<Mutex<dyn PipelinesRepository>>,
+    pub logs: Arc<Mutex<dyn LogsRepository>>,
+    pub schedules: Arc<Mutex<dyn SchedulesRepository>>,
+    pub secrets: Arc<Mutex<dyn SecretsRepository>>,
+    pub artifacts: Arc<Mutex<dyn ArtifactsRepository>>,
 }
 
 impl Repositories {
-    pub fn build(database_url: &str) -> Result<Repositories, String> {
+    pub fn build(database_url: &str) -> Result<Repositories, RepositoryError> {
+        let mut connection = connection::establish(database_url)?;
+        connection::run_migrations(&mut connection)?;
+
         let pipelines = pipeline::implementation::PipelinesRepository::create(database_url)?;
         let pipelines = Arc::new(Mutex::new(pipelines));
 
-        Ok(Repositories { pipelines })
+        let logs = log::implementation::LogsRepository::create(database_url)?;
+        let logs = Arc::new(Mutex::new(logs));
+
+        let schedules = schedule::implementation::SchedulesRepository::create(database_url)?;
+        let schedules = Arc::new(Mutex::new(schedules));
+
+        let secrets = secret::implementation::SecretsRepository::create(database_url)?;
+        let secrets = Arc::new(Mutex::new(secrets));
+
+        let artifacts = artifact::implementation::ArtifactsRepository::create(database_url)?;
+        let artifacts = Arc::new(Mutex::new(artifacts));
+
+        Ok(Repositories {
+            pipelines,
+            logs,
+            schedules,
+            secrets,
+            artifacts,
+        })
     }
 }
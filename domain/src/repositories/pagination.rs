@@ -0,0 +1,80 @@
+use serde::{Deserialize, Serialize};
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Pagination {
+    pub page: u32,
+    pub per_page: u32,
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq, Eq)]
+pub struct Page<T> {
+    pub items: Vec<T>,
+    pub page: u32,
+    pub per_page: u32,
+    pub total: i64,
+}
+
+impl Pagination {
+    pub const DEFAULT_PER_PAGE: u32 = 20;
+    pub const MAX_PER_PAGE: u32 = 100;
+
+    pub fn new(page: Option<u32>, per_page: Option<u32>) -> Self {
+        Self {
+            page: page.unwrap_or(1).max(1),
+            per_page: per_page
+                .unwrap_or(Self::DEFAULT_PER_PAGE)
+                .clamp(1, Self::MAX_PER_PAGE),
+        }
+    }
+
+    pub fn limit(&self) -> i64 {
+        self.per_page.into()
+    }
+
+    pub fn offset(&self) -> i64 {
+        i64::from(self.page - 1) * i64::from(self.per_page)
+    }
+}
+
+impl<T> Page<T> {
+    pub fn new(items: Vec<T>, pagination: Pagination, total: i64) -> Self {
+        Self {
+            items,
+            page: pagination.page,
+            per_page: pagination.per_page,
+            total,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn new_should_apply_defaults() {
+        assert_eq!(
+            Pagination::new(None, None),
+            Pagination {
+                page: 1,
+                per_page: Pagination::DEFAULT_PER_PAGE
+            }
+        );
+    }
+
+    #[test]
+    fn new_should_clamp_values() {
+        let pagination = Pagination::new(Some(0), Some(1000));
+
+        assert_eq!(pagination.page, 1);
+        assert_eq!(pagination.per_page, Pagination::MAX_PER_PAGE);
+    }
+
+    #[test]
+    fn offset_should_skip_previous_pages() {
+        let pagination = Pagination::new(Some(3), Some(10));
+
+        assert_eq!(pagination.offset(), 20);
+        assert_eq!(pagination.limit(), 10);
+    }
+}
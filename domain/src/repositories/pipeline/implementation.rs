@@ -1,49 +1,660 @@
-use diesel::prelude::*;
+use std::collections::HashMap;
 
-use crate::{PipelineId, PipelineStatus};
+use chrono::{NaiveDateTime, Utc};
+use diesel::{prelude::*, sqlite::Sqlite};
+
+use crate::{
+    repositories::{connection, Page, Pagination, RepositoryError},
+    schema::pipelines,
+    Pipeline, PipelineConfiguration, PipelineId, PipelineStatus, Step, StepId, Trigger,
+};
+
+use super::PipelineFilter;
 
 pub struct PipelinesRepository {
     connection: SqliteConnection,
 }
 
 impl PipelinesRepository {
-    pub fn create(database_url: &str) -> Result<Self, String> {
-        let connection = SqliteConnection::establish(database_url)
-            .map_err(|e| format!("Could not establish database connection: {e}"))?;
+    pub fn create(database_url: &str) -> Result<Self, RepositoryError> {
+        let connection = connection::establish(database_url)?;
 
         Ok(Self { connection })
     }
+
+    fn load_steps(
+        &mut self,
+        pipelines: Vec<RawPipeline>,
+    ) -> Result<Vec<Pipeline>, RepositoryError> {
+        use crate::schema::steps;
+
+        let mut steps_by_pipeline = steps::table
+            .filter(steps::pipeline_id.eq_any(pipelines.iter().map(|pipeline| pipeline.id)))
+            .order_by((steps::pipeline_id, steps::id))
+            .select((steps::pipeline_id, RawStep::as_select()))
+            .load::<(PipelineId, RawStep)>(&mut self.connection)?
+            .into_iter()
+            .fold(
+                HashMap::<_, Vec<_>>::new(),
+                |mut steps, (pipeline_id, step)| {
+                    steps.entry(pipeline_id).or_default().push(step);
+                    steps
+                },
+            );
+
+        pipelines
+            .into_iter()
+            .map(|pipeline| {
+                let steps = steps_by_pipeline.remove(&pipeline.id).unwrap_or_default();
+                pipeline.into_pipeline(steps)
+            })
+            .collect()
+    }
 }
 
 impl super::PipelinesRepository for PipelinesRepository {
-    fn create_new(&mut self) -> Result<PipelineId, ()> {
-        use crate::schema::pipelines;
+    fn create_new(
+        &mut self,
+        trigger: &Trigger,
+        configuration: PipelineConfiguration,
+    ) -> Result<Pipeline, RepositoryError> {
+        use crate::schema::{pipelines, steps};
 
+        let created_at = Utc::now().naive_utc();
         let pipeline = NewPipeline {
             status: PipelineStatus::Pending,
+            repository_owner: &trigger.repository_owner,
+            repository_name: &trigger.repository_name,
+            event: trigger.event.name(),
+            branch: &trigger.event.branch().name,
+            commit_sha: trigger.event.commit(),
+            trigger: serde_json::to_string(trigger)?,
+            configuration_name: &configuration.name,
+            configuration: serde_json::to_string(&configuration)?,
+            created_at,
+        };
+
+        let id = self.connection.transaction(|connection| {
+            let id = diesel::insert_into(pipelines::table)
+                .values(pipeline)
+                .returning(pipelines::id)
+                .get_result(connection)?;
+
+            let steps = configuration
+                .steps
+                .iter()
+                .enumerate()
+                .map(|(index, step)| NewStep {
+                    pipeline_id: id,
+                    id: StepId::new(index as i32 + 1),
+                    name: &step.name,
+                    status: PipelineStatus::Pending,
+                })
+                .collect::<Vec<_>>();
+
+            diesel::insert_into(steps::table)
+                .values(steps)
+                .execute(connection)?;
+
+            Ok::<_, RepositoryError>(id)
+        })?;
+
+        Ok(Pipeline::new(
+            id,
+            trigger.clone(),
+            configuration,
+            created_at,
+        ))
+    }
+
+    fn find(&mut self, id: PipelineId) -> Result<Option<Pipeline>, RepositoryError> {
+        use crate::schema::{pipelines, steps};
+
+        let Some(pipeline) = pipelines::table
+            .find(id)
+            .select(RawPipeline::as_select())
+            .first(&mut self.connection)
+            .optional()?
+        else {
+            return Ok(None);
         };
 
-        let result = diesel::insert_into(pipelines::table)
-            .values(pipeline)
-            .returning(RawPipeline::as_returning())
-            .get_result(&mut self.connection)
-            .map_err(|_| ())?;
+        let steps = steps::table
+            .filter(steps::pipeline_id.eq(id))
+            .order_by(steps::id)
+            .select(RawStep::as_select())
+            .load(&mut self.connection)?;
+
+        Ok(Some(pipeline.into_pipeline(steps)?))
+    }
+
+    fn list(
+        &mut self,
+        filter: &PipelineFilter,
+        pagination: Pagination,
+    ) -> Result<Page<Pipeline>, RepositoryError> {
+        let total = filtered_pipelines(filter)
+            .count()
+            .get_result(&mut self.connection)?;
+
+        let pipelines = filtered_pipelines(filter)
+            .order_by(pipelines::id.desc())
+            .limit(pagination.limit())
+            .offset(pagination.offset())
+            .select(RawPipeline::as_select())
+            .load(&mut self.connection)?;
+        let pipelines = self.load_steps(pipelines)?;
+
+        Ok(Page::new(pipelines, pagination, total))
+    }
+
+    fn list_queued(&mut self) -> Result<Vec<Pipeline>, RepositoryError> {
+        let pipelines = pipelines::table
+            .filter(pipelines::status.eq(PipelineStatus::Pending))
+            .order_by(pipelines::id)
+            .select(RawPipeline::as_select())
+            .load(&mut self.connection)?;
+
+        self.load_steps(pipelines)
+    }
+
+    fn list_running(&mut self) -> Result<Vec<Pipeline>, RepositoryError> {
+        let pipelines = pipelines::table
+            .filter(pipelines::status.eq(PipelineStatus::Running))
+            .order_by(pipelines::id)
+            .select(RawPipeline::as_select())
+            .load(&mut self.connection)?;
+
+        self.load_steps(pipelines)
+    }
+
+    fn requeue_interrupted(&mut self) -> Result<Vec<PipelineId>, RepositoryError> {
+        use crate::schema::{artifacts, logs, steps};
+
+        self.connection.transaction(|connection| {
+            let mut ids = vec![];
+            let running = pipelines::table
+                .filter(pipelines::status.eq(PipelineStatus::Running))
+                .order_by(pipelines::id)
+                .select((pipelines::id, pipelines::configuration))
+                .load::<(PipelineId, String)>(connection)?;
+
+            for (id, configuration) in running {
+                let configuration: PipelineConfiguration = serde_json::from_str(&configuration)?;
 
-        Ok(result.id)
+                if configuration.runs_on.is_none() {
+                    ids.push(id);
+                }
+            }
+
+            diesel::update(pipelines::table.filter(pipelines::id.eq_any(&ids)))
+                .set((
+                    pipelines::status.eq(PipelineStatus::Pending),
+                    pipelines::started_at.eq(None::<NaiveDateTime>),
+                ))
+                .execute(connection)?;
+
+            diesel::update(steps::table.filter(steps::pipeline_id.eq_any(&ids)))
+                .set((
+                    steps::status.eq(PipelineStatus::Pending),
+                    steps::exit_code.eq(None::<i64>),
+                    steps::failure_reason.eq(None::<String>),
+                    steps::started_at.eq(None::<NaiveDateTime>),
+                    steps::finished_at.eq(None::<NaiveDateTime>),
+                ))
+                .execute(connection)?;
+
+            diesel::delete(logs::table.filter(logs::pipeline_id.eq_any(&ids)))
+                .execute(connection)?;
+
+            diesel::delete(artifacts::table.filter(artifacts::pipeline_id.eq_any(&ids)))
+                .execute(connection)?;
+
+            Ok(ids)
+        })
+    }
+
+    fn update_status(
+        &mut self,
+        id: PipelineId,
+        status: PipelineStatus,
+    ) -> Result<(), RepositoryError> {
+        use crate::schema::pipelines;
+
+        let now = Utc::now().naive_utc();
+        let pipeline = pipelines::table.find(id);
+
+        if status == PipelineStatus::Running {
+            diesel::update(pipeline)
+                .set((pipelines::status.eq(status), pipelines::started_at.eq(now)))
+                .execute(&mut self.connection)?;
+        } else if status.is_finished() {
+            diesel::update(pipeline)
+                .set((pipelines::status.eq(status), pipelines::finished_at.eq(now)))
+                .execute(&mut self.connection)?;
+        } else {
+            diesel::update(pipeline)
+                .set(pipelines::status.eq(status))
+                .execute(&mut self.connection)?;
+        }
+
+        Ok(())
+    }
+
+    fn update_step_status(
+        &mut self,
+        pipeline_id: PipelineId,
+        step_id: StepId,
+        status: PipelineStatus,
+        exit_code: Option<i64>,
+        failure_reason: Option<&str>,
+    ) -> Result<(), RepositoryError> {
+        use crate::schema::steps;
+
+        let now = Utc::now().naive_utc();
+        let step = steps::table.find((pipeline_id, step_id));
+
+        if status == PipelineStatus::Running {
+            diesel::update(step)
+                .set((steps::status.eq(status), steps::started_at.eq(now)))
+                .execute(&mut self.connection)?;
+        } else if status.is_finished() {
+            diesel::update(step)
+                .set((
+                    steps::status.eq(status),
+                    steps::exit_code.eq(exit_code),
+                    steps::failure_reason.eq(failure_reason),
+                    steps::finished_at.eq(now),
+                ))
+                .execute(&mut self.connection)?;
+        } else {
+            diesel::update(step)
+                .set(steps::status.eq(status))
+                .execute(&mut self.connection)?;
+        }
+
+        Ok(())
     }
 }
 
+fn filtered_pipelines(filter: &PipelineFilter) -> pipelines::BoxedQuery<'_, Sqlite> {
+    let mut query = pipelines::table.into_boxed();
+
+    if let Some(repository_owner) = &filter.repository_owner {
+        query = query.filter(pipelines::repository_owner.eq(repository_owner));
+    }
+
+    if let Some(repository_name) = &filter.repository_name {
+        query = query.filter(pipelines::repository_name.eq(repository_name));
+    }
+
+    if let Some(branch) = &filter.branch {
+        query = query.filter(pipelines::branch.eq(branch));
+    }
+
+    if let Some(status) = filter.status {
+        query = query.filter(pipelines::status.eq(status));
+    }
+
+    query
+}
+
 #[derive(Queryable, Selectable)]
 #[diesel(table_name = crate::schema::pipelines)]
 #[diesel(check_for_backend(diesel::sqlite::Sqlite))]
 struct RawPipeline {
     pub id: PipelineId,
     pub status: PipelineStatus,
+    pub trigger: String,
+    pub configuration: String,
+    pub created_at: NaiveDateTime,
+    pub started_at: Option<NaiveDateTime>,
+    pub finished_at: Option<NaiveDateTime>,
+}
+
+impl RawPipeline {
+    fn into_pipeline(self, steps: Vec<RawStep>) -> Result<Pipeline, RepositoryError> {
+        let trigger = serde_json::from_str(&self.trigger)?;
+        let configuration: PipelineConfiguration = serde_json::from_str(&self.configuration)?;
+
+        let steps = steps
+            .into_iter()
+            .zip(configuration.steps.iter())
+            .map(|(step, step_configuration)| Step {
+                id: step.id,
+                configuration: step_configuration.clone(),
+                status: step.status,
+                exit_code: step.exit_code,
+                failure_reason: step.failure_reason,
+                started_at: step.started_at,
+                finished_at: step.finished_at,
+            })
+            .collect();
+
+        Ok(Pipeline {
+            id: self.id,
+            trigger,
+            configuration,
+            steps,
+            status: self.status,
+            created_at: self.created_at,
+            started_at: self.started_at,
+            finished_at: self.finished_at,
+        })
+    }
+}
+
+#[derive(Queryable, Selectable)]
+#[diesel(table_name = crate::schema::steps)]
+#[diesel(check_for_backend(diesel::sqlite::Sqlite))]
+struct RawStep {
+    pub id: StepId,
+    pub status: PipelineStatus,
+    pub exit_code: Option<i64>,
+    pub started_at: Option<NaiveDateTime>,
+    pub finished_at: Option<NaiveDateTime>,
+    pub failure_reason: Option<String>,
 }
 
 #[derive(Insertable)]
 #[diesel(table_name = crate::schema::pipelines)]
 #[diesel(check_for_backend(diesel::sqlite::Sqlite))]
-struct NewPipeline {
+struct NewPipeline<'a> {
+    pub status: PipelineStatus,
+    pub repository_owner: &'a str,
+    pub repository_name: &'a str,
+    pub event: &'a str,
+    pub branch: &'a str,
+    pub commit_sha: &'a str,
+    pub trigger: String,
+    pub configuration_name: &'a str,
+    pub configuration: String,
+    pub created_at: NaiveDateTime,
+}
+
+#[derive(Insertable)]
+#[diesel(table_name = crate::schema::steps)]
+#[diesel(check_for_backend(diesel::sqlite::Sqlite))]
+struct NewStep<'a> {
+    pub pipeline_id: PipelineId,
+    pub id: StepId,
+    pub name: &'a str,
     pub status: PipelineStatus,
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{repositories::PipelinesRepository as _, Branch, TriggerEvent};
+
+    fn repository() -> PipelinesRepository {
+        let mut connection = connection::establish(":memory:").unwrap();
+        connection::run_migrations(&mut connection).unwrap();
+
+        PipelinesRepository { connection }
+    }
+
+    fn trigger() -> Trigger {
+        trigger_for_branch("main")
+    }
+
+    fn trigger_for_branch(branch: &str) -> Trigger {
+        Trigger {
+            repository_owner: "Owner".to_owned(),
+            repository_name: "Repo".to_owned(),
+            installation_id: 789,
+            event: TriggerEvent::Push {
+                branch: Branch {
+                    name: branch.to_owned(),
+                    commit: "123".to_owned(),
+                },
+                before: None,
+            },
+        }
+    }
+
+    fn configuration() -> PipelineConfiguration {
+        serde_json::from_str(
+            r#"{
+                "name": "Pipeline",
+                "trigger": [{ "event": "push" }],
+                "steps": [
+                    { "name": "a", "image": "alpine" },
+                    { "name": "b", "image": "alpine" }
+                ]
+            }"#,
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn create_new_should_persist_pipeline_and_steps() {
+        let mut repository = repository();
+
+        let pipeline = repository.create_new(&trigger(), configuration()).unwrap();
+        let stored = repository.find(pipeline.id).unwrap().unwrap();
+
+        assert_eq!(stored.id, pipeline.id);
+        assert_eq!(stored.trigger, trigger());
+        assert_eq!(stored.status, PipelineStatus::Pending);
+        assert_eq!(
+            stored
+                .steps
+                .iter()
+                .map(|step| (step.id, step.configuration.name.as_str()))
+                .collect::<Vec<_>>(),
+            vec![(StepId::new(1), "a"), (StepId::new(2), "b")]
+        );
+    }
+
+    #[test]
+    fn find_should_return_none_for_unknown_pipeline() {
+        let mut repository = repository();
+
+        assert!(repository.find(PipelineId::new(1)).unwrap().is_none());
+    }
+
+    #[test]
+    fn update_status_should_record_timestamps() {
+        let mut repository = repository();
+        let pipeline = repository.create_new(&trigger(), configuration()).unwrap();
+
+        repository
+            .update_status(pipeline.id, PipelineStatus::Running)
+            .unwrap();
+        repository
+            .update_step_status(
+                pipeline.id,
+                StepId::new(1),
+                PipelineStatus::Failed,
+                Some(1),
+                None,
+            )
+            .unwrap();
+        repository
+            .update_step_status(
+                pipeline.id,
+                StepId::new(2),
+                PipelineStatus::Skipped,
+                None,
+                None,
+            )
+            .unwrap();
+        repository
+            .update_status(pipeline.id, PipelineStatus::Failed)
+            .unwrap();
+
+        let stored = repository.find(pipeline.id).unwrap().unwrap();
+
+        assert_eq!(stored.status, PipelineStatus::Failed);
+        assert!(stored.started_at.is_some());
+        assert!(stored.finished_at.is_some());
+        assert_eq!(stored.steps[0].status, PipelineStatus::Failed);
+        assert_eq!(stored.steps[0].exit_code, Some(1));
+        assert_eq!(stored.steps[1].status, PipelineStatus::Skipped);
+        assert_eq!(stored.steps[1].exit_code, None);
+    }
+
+    #[test]
+    fn update_step_status_should_record_failure_reason() {
+        let mut repository = repository();
+        let pipeline = repository.create_new(&trigger(), configuration()).unwrap();
+
+        repository
+            .update_step_status(
+                pipeline.id,
+                StepId::new(1),
+                PipelineStatus::Failed,
+                Some(137),
+                Some("OOMKilled"),
+            )
+            .unwrap();
+
+        let stored = repository.find(pipeline.id).unwrap().unwrap();
+
+        assert_eq!(stored.steps[0].exit_code, Some(137));
+        assert_eq!(stored.steps[0].failure_reason.as_deref(), Some("OOMKilled"));
+        assert_eq!(stored.steps[1].failure_reason, None);
+    }
+
+    #[test]
+    fn list_queued_should_return_pending_pipelines_oldest_first() {
+        let mut repository = repository();
+        let first = repository.create_new(&trigger(), configuration()).unwrap();
+        let second = repository.create_new(&trigger(), configuration()).unwrap();
+        let third = repository.create_new(&trigger(), configuration()).unwrap();
+
+        repository
+            .update_status(second.id, PipelineStatus::Running)
+            .unwrap();
+
+        let queued = repository.list_queued().unwrap();
+
+        assert_eq!(
+            queued
+                .iter()
+                .map(|pipeline| pipeline.id)
+                .collect::<Vec<_>>(),
+            vec![first.id, third.id]
+        );
+        assert_eq!(queued[0].steps.len(), 2);
+    }
+
+    #[test]
+    fn requeue_interrupted_should_reset_running_pipelines() {
+        use crate::schema::artifacts;
+
+        let mut repository = repository();
+        let running = repository.create_new(&trigger(), configuration()).unwrap();
+        let finished = repository.create_new(&trigger(), configuration()).unwrap();
+        let remote = repository
+            .create_new(
+                &trigger(),
+                PipelineConfiguration {
+                    runs_on: Some(vec!["gpu".to_owned()]),
+                    ..configuration()
+                },
+            )
+            .unwrap();
+
+        repository
+            .update_status(running.id, PipelineStatus::Running)
+            .unwrap();
+        repository
+            .update_status(remote.id, PipelineStatus::Running)
+            .unwrap();
+        diesel::insert_into(artifacts::table)
+            .values((
+                artifacts::pipeline_id.eq(running.id),
+                artifacts::step_id.eq(StepId::new(1)),
+                artifacts::path.eq("dist"),
+                artifacts::digest.eq("abc123"),
+                artifacts::size.eq(1),
+                artifacts::created_at.eq(Utc::now().naive_utc()),
+            ))
+            .execute(&mut repository.connection)
+            .unwrap();
+        repository
+            .update_step_status(
+                running.id,
+                StepId::new(1),
+                PipelineStatus::Passed,
+                Some(0),
+                None,
+            )
+            .unwrap();
+        repository
+            .update_status(finished.id, PipelineStatus::Passed)
+            .unwrap();
+
+        assert_eq!(repository.requeue_interrupted().unwrap(), vec![running.id]);
+
+        let stored = repository.find(running.id).unwrap().unwrap();
+        assert_eq!(stored.status, PipelineStatus::Pending);
+        assert!(stored.started_at.is_none());
+        assert_eq!(stored.steps[0].status, PipelineStatus::Pending);
+        assert!(stored.steps[0].exit_code.is_none());
+
+        let artifacts = artifacts::table
+            .filter(artifacts::pipeline_id.eq(running.id))
+            .count()
+            .get_result::<i64>(&mut repository.connection)
+            .unwrap();
+        assert_eq!(artifacts, 0);
+
+        let stored = repository.find(finished.id).unwrap().unwrap();
+        assert_eq!(stored.status, PipelineStatus::Passed);
+
+        // Agents keep running their pipelines, so these are not started a second time
+        let running = repository.list_running().unwrap();
+        assert_eq!(running.len(), 1);
+        assert_eq!(running[0].id, remote.id);
+    }
+
+    #[test]
+    fn list_should_filter_and_paginate_pipelines() {
+        let mut repository = repository();
+
+        let first = repository
+            .create_new(&trigger_for_branch("main"), configuration())
+            .unwrap();
+        repository
+            .create_new(&trigger_for_branch("feature"), configuration())
+            .unwrap();
+        let third = repository
+            .create_new(&trigger_for_branch("main"), configuration())
+            .unwrap();
+        repository
+            .update_status(third.id, PipelineStatus::Passed)
+            .unwrap();
+
+        let filter = PipelineFilter {
+            repository_owner: Some("Owner".to_owned()),
+            repository_name: Some("Repo".to_owned()),
+            branch: Some("main".to_owned()),
+            ..Default::default()
+        };
+
+        let page = repository
+            .list(&filter, Pagination::new(Some(1), Some(1)))
+            .unwrap();
+        assert_eq!(page.total, 2);
+        assert_eq!(page.items[0].id, third.id);
+        assert_eq!(page.items[0].steps.len(), 2);
+
+        let page = repository
+            .list(&filter, Pagination::new(Some(2), Some(1)))
+            .unwrap();
+        assert_eq!(page.items[0].id, first.id);
+
+        let filter = PipelineFilter {
+            status: Some(PipelineStatus::Passed),
+            ..Default::default()
+        };
+        let page = repository
+            .list(&filter, Pagination::new(None, None))
+            .unwrap();
+        assert_eq!(page.total, 1);
+        assert_eq!(page.items[0].id, third.id);
+    }
+}
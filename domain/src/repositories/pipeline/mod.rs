@@ -1,7 +1,49 @@
-use crate::PipelineId;
+use crate::{Pipeline, PipelineConfiguration, PipelineId, PipelineStatus, StepId, Trigger};
+
+use super::{Page, Pagination, RepositoryError};
 
 pub mod implementation;
 
-pub trait PipelinesRepository {
-    fn create_new(&mut self) -> Result<PipelineId, ()>;
+#[derive(Default)]
+pub struct PipelineFilter {
+    pub repository_owner: Option<String>,
+    pub repository_name: Option<String>,
+    pub branch: Option<String>,
+    pub status: Option<PipelineStatus>,
+}
+
+pub trait PipelinesRepository: Send {
+    fn create_new(
+        &mut self,
+        trigger: &Trigger,
+        configuration: PipelineConfiguration,
+    ) -> Result<Pipeline, RepositoryError>;
+    fn find(&mut self, id: PipelineId) -> Result<Option<Pipeline>, RepositoryError>;
+    /// Lists the pipelines matching the filter, most recent first
+    fn list(
+        &mut self,
+        filter: &PipelineFilter,
+        pagination: Pagination,
+    ) -> Result<Page<Pipeline>, RepositoryError>;
+    /// Lists the pipelines waiting to run, oldest first
+    fn list_queued(&mut self) -> Result<Vec<Pipeline>, RepositoryError>;
+    /// Lists the running pipelines, oldest first
+    fn list_running(&mut self) -> Result<Vec<Pipeline>, RepositoryError>;
+    /// Puts pipelines that were running on this host when the backend stopped back into
+    /// the queue, discarding the results, logs and artifacts of their steps. Pipelines
+    /// running on agents are left as they are.
+    fn requeue_interrupted(&mut self) -> Result<Vec<PipelineId>, RepositoryError>;
+    fn update_status(
+        &mut self,
+        id: PipelineId,
+        status: PipelineStatus,
+    ) -> Result<(), RepositoryError>;
+    fn update_step_status(
+        &mut self,
+        pipeline_id: PipelineId,
+        step_id: StepId,
+        status: PipelineStatus,
+        exit_code: Option<i64>,
+        failure_reason: Option<&str>,
+    ) -> Result<(), RepositoryError>;
 }
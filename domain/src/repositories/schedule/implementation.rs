@@ -0,0 +1,122 @@
+use chrono::NaiveDateTime;
+use diesel::prelude::*;
+
+use crate::repositories::{connection, RepositoryError};
+
+use super::ScheduleKey;
+
+pub struct SchedulesRepository {
+    connection: SqliteConnection,
+}
+
+impl SchedulesRepository {
+    pub fn create(database_url: &str) -> Result<Self, RepositoryError> {
+        let connection = connection::establish(database_url)?;
+
+        Ok(Self { connection })
+    }
+}
+
+impl super::SchedulesRepository for SchedulesRepository {
+    fn find_last_triggered(
+        &mut self,
+        key: &ScheduleKey,
+    ) -> Result<Option<NaiveDateTime>, RepositoryError> {
+        use crate::schema::schedules;
+
+        let last_triggered_at = schedules::table
+            .find((
+                &key.repository_owner,
+                &key.repository_name,
+                &key.pipeline,
+                &key.branch,
+                &key.cron,
+            ))
+            .select(schedules::last_triggered_at)
+            .first(&mut self.connection)
+            .optional()?;
+
+        Ok(last_triggered_at)
+    }
+
+    fn insert(&mut self, key: &ScheduleKey, time: NaiveDateTime) -> Result<(), RepositoryError> {
+        use crate::schema::schedules;
+
+        diesel::insert_or_ignore_into(schedules::table)
+            .values((
+                schedules::repository_owner.eq(&key.repository_owner),
+                schedules::repository_name.eq(&key.repository_name),
+                schedules::pipeline.eq(&key.pipeline),
+                schedules::branch.eq(&key.branch),
+                schedules::cron.eq(&key.cron),
+                schedules::last_triggered_at.eq(time),
+            ))
+            .execute(&mut self.connection)?;
+
+        Ok(())
+    }
+
+    fn advance(
+        &mut self,
+        key: &ScheduleKey,
+        previous: NaiveDateTime,
+        time: NaiveDateTime,
+    ) -> Result<bool, RepositoryError> {
+        use crate::schema::schedules;
+
+        let updated = diesel::update(
+            schedules::table
+                .find((
+                    &key.repository_owner,
+                    &key.repository_name,
+                    &key.pipeline,
+                    &key.branch,
+                    &key.cron,
+                ))
+                .filter(schedules::last_triggered_at.eq(previous)),
+        )
+        .set(schedules::last_triggered_at.eq(time))
+        .execute(&mut self.connection)?;
+
+        Ok(updated == 1)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::DateTime;
+
+    use super::*;
+    use crate::repositories::SchedulesRepository as _;
+
+    fn time(seconds: i64) -> NaiveDateTime {
+        DateTime::from_timestamp(seconds, 0).unwrap().naive_utc()
+    }
+
+    #[test]
+    fn advance_should_only_succeed_once_per_previous_time() {
+        let mut connection = connection::establish(":memory:").unwrap();
+        connection::run_migrations(&mut connection).unwrap();
+        let mut repository = SchedulesRepository { connection };
+        let key = ScheduleKey {
+            repository_owner: "Owner".to_owned(),
+            repository_name: "Repo".to_owned(),
+            pipeline: "Nightly".to_owned(),
+            branch: "main".to_owned(),
+            cron: "0 2 * * *".to_owned(),
+        };
+
+        assert_eq!(repository.find_last_triggered(&key).unwrap(), None);
+
+        repository.insert(&key, time(0)).unwrap();
+        repository.insert(&key, time(10)).unwrap();
+        assert_eq!(repository.find_last_triggered(&key).unwrap(), Some(time(0)));
+
+        assert!(repository.advance(&key, time(0), time(60)).unwrap());
+        assert!(!repository.advance(&key, time(0), time(120)).unwrap());
+        assert_eq!(
+            repository.find_last_triggered(&key).unwrap(),
+            Some(time(60))
+        );
+    }
+}
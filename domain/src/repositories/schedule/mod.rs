@@ -0,0 +1,32 @@
+use chrono::NaiveDateTime;
+
+use super::RepositoryError;
+
+pub mod implementation;
+
+/// Identifies one schedule trigger of a pipeline
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ScheduleKey {
+    pub repository_owner: String,
+    pub repository_name: String,
+    pub pipeline: String,
+    pub branch: String,
+    pub cron: String,
+}
+
+pub trait SchedulesRepository: Send {
+    fn find_last_triggered(
+        &mut self,
+        key: &ScheduleKey,
+    ) -> Result<Option<NaiveDateTime>, RepositoryError>;
+    /// Records a schedule that was seen for the first time, without triggering it
+    fn insert(&mut self, key: &ScheduleKey, time: NaiveDateTime) -> Result<(), RepositoryError>;
+    /// Moves the last trigger time of a schedule forward, returns false if another
+    /// process already did so since `previous` was read
+    fn advance(
+        &mut self,
+        key: &ScheduleKey,
+        previous: NaiveDateTime,
+        time: NaiveDateTime,
+    ) -> Result<bool, RepositoryError>;
+}
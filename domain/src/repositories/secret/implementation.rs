@@ -0,0 +1,186 @@
+use chrono::NaiveDateTime;
+use diesel::{prelude::*, upsert::excluded};
+
+use crate::{
+    repositories::{connection, RepositoryError},
+    EncryptedSecret, Secret, SecretScope,
+};
+
+pub struct SecretsRepository {
+    connection: SqliteConnection,
+}
+
+impl SecretsRepository {
+    pub fn create(database_url: &str) -> Result<Self, RepositoryError> {
+        let connection = connection::establish(database_url)?;
+
+        Ok(Self { connection })
+    }
+}
+
+#[derive(Queryable, Selectable)]
+#[diesel(table_name = crate::schema::secrets)]
+#[diesel(check_for_backend(diesel::sqlite::Sqlite))]
+struct RawSecret {
+    name: String,
+    created_at: NaiveDateTime,
+    updated_at: NaiveDateTime,
+}
+
+#[derive(Queryable, Selectable)]
+#[diesel(table_name = crate::schema::secrets)]
+#[diesel(check_for_backend(diesel::sqlite::Sqlite))]
+struct RawEncryptedSecret {
+    nonce: Vec<u8>,
+    ciphertext: Vec<u8>,
+}
+
+#[derive(Insertable)]
+#[diesel(table_name = crate::schema::secrets)]
+#[diesel(check_for_backend(diesel::sqlite::Sqlite))]
+struct NewSecret<'a> {
+    scope: &'a str,
+    name: &'a str,
+    nonce: &'a [u8],
+    ciphertext: &'a [u8],
+    created_at: NaiveDateTime,
+    updated_at: NaiveDateTime,
+}
+
+impl super::SecretsRepository for SecretsRepository {
+    fn list(&mut self, scope: &SecretScope) -> Result<Vec<Secret>, RepositoryError> {
+        use crate::schema::secrets;
+
+        let secrets = secrets::table
+            .filter(secrets::scope.eq(scope.to_string()))
+            .order(secrets::name.asc())
+            .select(RawSecret::as_select())
+            .load(&mut self.connection)?
+            .into_iter()
+            .map(|secret| Secret {
+                name: secret.name,
+                created_at: secret.created_at,
+                updated_at: secret.updated_at,
+            })
+            .collect();
+
+        Ok(secrets)
+    }
+
+    fn find(
+        &mut self,
+        scope: &SecretScope,
+        name: &str,
+    ) -> Result<Option<EncryptedSecret>, RepositoryError> {
+        use crate::schema::secrets;
+
+        let secret = secrets::table
+            .find((scope.to_string(), name))
+            .select(RawEncryptedSecret::as_select())
+            .first(&mut self.connection)
+            .optional()?
+            .map(|secret| EncryptedSecret {
+                nonce: secret.nonce,
+                ciphertext: secret.ciphertext,
+            });
+
+        Ok(secret)
+    }
+
+    fn upsert(
+        &mut self,
+        scope: &SecretScope,
+        name: &str,
+        secret: &EncryptedSecret,
+        time: NaiveDateTime,
+    ) -> Result<(), RepositoryError> {
+        use crate::schema::secrets;
+
+        let scope = scope.to_string();
+
+        diesel::insert_into(secrets::table)
+            .values(NewSecret {
+                scope: &scope,
+                name,
+                nonce: &secret.nonce,
+                ciphertext: &secret.ciphertext,
+                created_at: time,
+                updated_at: time,
+            })
+            .on_conflict((secrets::scope, secrets::name))
+            .do_update()
+            .set((
+                secrets::nonce.eq(excluded(secrets::nonce)),
+                secrets::ciphertext.eq(excluded(secrets::ciphertext)),
+                secrets::updated_at.eq(excluded(secrets::updated_at)),
+            ))
+            .execute(&mut self.connection)?;
+
+        Ok(())
+    }
+
+    fn delete(&mut self, scope: &SecretScope, name: &str) -> Result<bool, RepositoryError> {
+        use crate::schema::secrets;
+
+        let deleted = diesel::delete(secrets::table.find((scope.to_string(), name)))
+            .execute(&mut self.connection)?;
+
+        Ok(deleted == 1)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::DateTime;
+
+    use super::*;
+    use crate::repositories::SecretsRepository as _;
+
+    fn time(seconds: i64) -> NaiveDateTime {
+        DateTime::from_timestamp(seconds, 0).unwrap().naive_utc()
+    }
+
+    fn encrypted(value: u8) -> EncryptedSecret {
+        EncryptedSecret {
+            nonce: vec![value; 12],
+            ciphertext: vec![value; 4],
+        }
+    }
+
+    #[test]
+    fn secrets_should_be_scoped_and_replaced_on_upsert() {
+        let mut connection = connection::establish(":memory:").unwrap();
+        connection::run_migrations(&mut connection).unwrap();
+        let mut repository = SecretsRepository { connection };
+        let repository_scope = SecretScope::Repository {
+            owner: "Owner".to_owned(),
+            name: "Repo".to_owned(),
+        };
+        let installation_scope = SecretScope::Installation(789);
+
+        repository
+            .upsert(&repository_scope, "token", &encrypted(1), time(0))
+            .unwrap();
+        repository
+            .upsert(&repository_scope, "token", &encrypted(2), time(60))
+            .unwrap();
+
+        assert_eq!(
+            repository.find(&repository_scope, "token").unwrap(),
+            Some(encrypted(2))
+        );
+        assert_eq!(repository.find(&installation_scope, "token").unwrap(), None);
+        assert_eq!(
+            repository.list(&repository_scope).unwrap(),
+            vec![Secret {
+                name: "token".to_owned(),
+                created_at: time(0),
+                updated_at: time(60),
+            }]
+        );
+
+        assert!(repository.delete(&repository_scope, "token").unwrap());
+        assert!(!repository.delete(&repository_scope, "token").unwrap());
+        assert!(repository.list(&repository_scope).unwrap().is_empty());
+    }
+}
@@ -0,0 +1,25 @@
+use chrono::NaiveDateTime;
+
+use super::RepositoryError;
+use crate::{EncryptedSecret, Secret, SecretScope};
+
+pub mod implementation;
+
+pub trait SecretsRepository: Send {
+    fn list(&mut self, scope: &SecretScope) -> Result<Vec<Secret>, RepositoryError>;
+    fn find(
+        &mut self,
+        scope: &SecretScope,
+        name: &str,
+    ) -> Result<Option<EncryptedSecret>, RepositoryError>;
+    /// Creates the secret or replaces the value of an existing one
+    fn upsert(
+        &mut self,
+        scope: &SecretScope,
+        name: &str,
+        secret: &EncryptedSecret,
+        time: NaiveDateTime,
+    ) -> Result<(), RepositoryError>;
+    /// Returns false if there was no such secret
+    fn delete(&mut self, scope: &SecretScope, name: &str) -> Result<bool, RepositoryError>;
+}
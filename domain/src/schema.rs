@@ -1,8 +1,83 @@
 // @generated automatically by Diesel CLI.
 
+diesel::table! {
+    artifacts (id) {
+        id -> Integer,
+        pipeline_id -> Integer,
+        step_id -> Integer,
+        path -> Text,
+        digest -> Text,
+        size -> BigInt,
+        created_at -> Timestamp,
+    }
+}
+
+diesel::table! {
+    logs (pipeline_id, step_id, number) {
+        pipeline_id -> Integer,
+        step_id -> Integer,
+        number -> Integer,
+        timestamp -> Timestamp,
+        stream -> Text,
+        content -> Text,
+    }
+}
+
 diesel::table! {
     pipelines (id) {
         id -> Integer,
         status -> Text,
+        repository_owner -> Text,
+        repository_name -> Text,
+        event -> Text,
+        branch -> Text,
+        commit_sha -> Text,
+        trigger -> Text,
+        configuration_name -> Text,
+        configuration -> Text,
+        created_at -> Timestamp,
+        started_at -> Nullable<Timestamp>,
+        finished_at -> Nullable<Timestamp>,
+    }
+}
+
+diesel::table! {
+    schedules (repository_owner, repository_name, pipeline, branch, cron) {
+        repository_owner -> Text,
+        repository_name -> Text,
+        pipeline -> Text,
+        branch -> Text,
+        cron -> Text,
+        last_triggered_at -> Timestamp,
     }
 }
+
+diesel::table! {
+    secrets (scope, name) {
+        scope -> Text,
+        name -> Text,
+        nonce -> Binary,
+        ciphertext -> Binary,
+        created_at -> Timestamp,
+        updated_at -> Timestamp,
+    }
+}
+
+diesel::table! {
+    steps (pipeline_id, id) {
+        pipeline_id -> Integer,
+        id -> Integer,
+        name -> Text,
+        status -> Text,
+        exit_code -> Nullable<BigInt>,
+        started_at -> Nullable<Timestamp>,
+        finished_at -> Nullable<Timestamp>,
+        failure_reason -> Nullable<Text>,
+    }
+}
+
+diesel::joinable!(steps -> pipelines (pipeline_id));
+
+diesel::allow_tables_to_appear_in_same_query!(
+    artifacts, logs, pipelines, schedules, secrets, steps,
+);
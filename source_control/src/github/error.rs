@@ -8,7 +8,7 @@ pub enum GitHubError {
     #[error(transparent)]
     GitHub(#[from] OctocrabGitHubError),
     #[error(transparent)]
-    Octocrab(#[from] OctocrabError),
+    Octocrab(Box<OctocrabError>),
     #[error(transparent)]
     JWT(#[from] JwtError),
     #[error(transparent)]
@@ -16,3 +16,9 @@ pub enum GitHubError {
     #[error("{0}")]
     Generic(String),
 }
+
+impl From<OctocrabError> for GitHubError {
+    fn from(error: OctocrabError) -> Self {
+        Self::Octocrab(Box::new(error))
+    }
+}
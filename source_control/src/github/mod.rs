@@ -2,7 +2,7 @@ pub mod error;
 
 use std::path::Path;
 
-use crate::{CheckStatus, File, Folder, SourceControl, SourceControlInstallation};
+use crate::{CheckStatus, File, Folder, Repository, SourceControl, SourceControlInstallation};
 use base64::{engine::general_purpose::STANDARD, Engine as _};
 use jsonwebtoken::EncodingKey;
 use octocrab::{
@@ -10,11 +10,20 @@ use octocrab::{
     params::checks::{CheckRunConclusion, CheckRunStatus},
     Octocrab,
 };
+use percent_encoding::{utf8_percent_encode, AsciiSet, NON_ALPHANUMERIC};
 use secrecy::{ExposeSecret, SecretString};
 use serde::{Deserialize, Serialize};
 
 use self::error::GitHubError;
 
+/// Everything but the unreserved characters of RFC 3986, so branch names like `feature/a#b`
+/// stay a single path segment
+const PATH_SEGMENT: &AsciiSet = &NON_ALPHANUMERIC
+    .remove(b'-')
+    .remove(b'.')
+    .remove(b'_')
+    .remove(b'~');
+
 pub struct GitHub {
     octocrab: Octocrab,
 }
@@ -57,6 +66,96 @@ impl SourceControl for GitHub {
             token,
         })
     }
+
+    async fn find_installation_id(&self, owner: &str, repo: &str) -> Result<u64, Self::Error> {
+        let installation = self
+            .octocrab
+            .apps()
+            .get_repository_installation(owner, repo)
+            .await?;
+
+        Ok(installation.id.0)
+    }
+
+    async fn list_installations(&self) -> Result<Vec<u64>, Self::Error> {
+        let page = self
+            .octocrab
+            .apps()
+            .installations()
+            .per_page(100)
+            .send()
+            .await?;
+
+        let installations = self.octocrab.all_pages(page).await?;
+
+        Ok(installations
+            .into_iter()
+            .map(|installation| installation.id.0)
+            .collect())
+    }
+
+    async fn list_repositories(
+        &self,
+        installation_id: u64,
+    ) -> Result<Vec<Repository>, Self::Error> {
+        #[derive(Serialize)]
+        struct Params {
+            per_page: u8,
+            page: u32,
+        }
+
+        #[derive(Deserialize)]
+        struct InstallationRepositories {
+            total_count: usize,
+            repositories: Vec<InstallationRepository>,
+        }
+
+        #[derive(Deserialize)]
+        struct InstallationRepository {
+            name: String,
+            owner: RepositoryOwner,
+            default_branch: String,
+        }
+
+        #[derive(Deserialize)]
+        struct RepositoryOwner {
+            login: String,
+        }
+
+        let octocrab = self.octocrab.installation(InstallationId(installation_id));
+        let mut repositories = vec![];
+
+        for page in 1.. {
+            let response: InstallationRepositories = octocrab
+                .get(
+                    "/installation/repositories",
+                    Some(&Params {
+                        per_page: 100,
+                        page,
+                    }),
+                )
+                .await?;
+
+            let is_last_page = response.repositories.is_empty();
+
+            repositories.extend(
+                response
+                    .repositories
+                    .into_iter()
+                    .map(|repository| Repository {
+                        owner: repository.owner.login,
+                        name: repository.name,
+                        default_branch: repository.default_branch,
+                    }),
+            );
+
+            if is_last_page || repositories.len() >= response.total_count {
+                break;
+            }
+        }
+
+        Ok(repositories)
+    }
 }
 
 #[derive(Clone)]
@@ -144,6 +243,112 @@ impl SourceControlInstallation for GitHubInstallation {
         Ok(Folder { items })
     }
 
+    async fn default_branch(&self) -> Result<String, Self::Error> {
+        #[derive(Deserialize)]
+        struct RepositoryResponse {
+            default_branch: String,
+        }
+
+        let RepositoryResponse { default_branch } = self
+            .octocrab
+            .get(format!("/repos/{}/{}", self.owner, self.repo), None::<&()>)
+            .await?;
+
+        Ok(default_branch)
+    }
+
+    async fn resolve_branch(&self, branch: &str) -> Result<String, Self::Error> {
+        #[derive(Deserialize)]
+        struct BranchResponse {
+            commit: Commit,
+        }
+
+        #[derive(Deserialize)]
+        struct Commit {
+            sha: String,
+        }
+
+        let BranchResponse { commit } = self
+            .octocrab
+            .get(
+                format!(
+                    "/repos/{}/{}/branches/{}",
+                    self.owner,
+                    self.repo,
+                    utf8_percent_encode(branch, PATH_SEGMENT)
+                ),
+                None::<&()>,
+            )
+            .await?;
+
+        Ok(commit.sha)
+    }
+
+    async fn resolve_ref(&self, r#ref: &str) -> Result<String, Self::Error> {
+        #[derive(Deserialize)]
+        struct Commit {
+            sha: String,
+        }
+
+        let Commit { sha } = self
+            .octocrab
+            .get(
+                format!(
+                    "/repos/{}/{}/commits/{}",
+                    self.owner,
+                    self.repo,
+                    utf8_percent_encode(r#ref, PATH_SEGMENT)
+                ),
+                None::<&()>,
+            )
+            .await?;
+
+        Ok(sha)
+    }
+
+    async fn list_changed_files(
+        &self,
+        base: &str,
+        head: &str,
+    ) -> Result<Option<Vec<String>>, Self::Error> {
+        // The compare API silently truncates the list of files beyond this
+        const MAX_FILES: usize = 300;
+
+        #[derive(Deserialize)]
+        struct Comparison {
+            #[serde(default)]
+            files: Vec<ChangedFile>,
+        }
+
+        #[derive(Deserialize)]
+        struct ChangedFile {
+            filename: String,
+            previous_filename: Option<String>,
+        }
+
+        let Comparison { files } = self
+            .octocrab
+            .get(
+                format!(
+                    "/repos/{}/{}/compare/{}...{}",
+                    self.owner, self.repo, base, head
+                ),
+                None::<&()>,
+            )
+            .await?;
+
+        if files.len() >= MAX_FILES {
+            return Ok(None);
+        }
+
+        Ok(Some(
+            files
+                .into_iter()
+                .flat_map(|file| std::iter::once(file.filename).chain(file.previous_filename))
+                .collect(),
+        ))
+    }
+
     async fn print_rate_limit(&self) -> Result<(), Self::Error> {
         let limit = self.octocrab.ratelimit().get().await?;
         println!("{:?}", limit.resources.core);
@@ -170,6 +375,9 @@ impl SourceControlInstallation for GitHubInstallation {
             check_run = check_run.conclusion(match status {
                 CheckStatus::Failed => CheckRunConclusion::Failure,
                 CheckStatus::Passed => CheckRunConclusion::Success,
+                CheckStatus::Skipped => CheckRunConclusion::Skipped,
+                CheckStatus::TimedOut => CheckRunConclusion::TimedOut,
+                CheckStatus::Cancelled => CheckRunConclusion::Cancelled,
                 CheckStatus::Pending | CheckStatus::Running => CheckRunConclusion::Neutral,
             });
         }
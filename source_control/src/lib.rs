@@ -12,6 +12,16 @@ pub trait SourceControl {
         repo: &str,
         installation_id: u64,
     ) -> impl Future<Output = Result<Self::Installation, Self::Error>> + Send;
+    fn find_installation_id(
+        &self,
+        owner: &str,
+        repo: &str,
+    ) -> impl Future<Output = Result<u64, Self::Error>> + Send;
+    fn list_installations(&self) -> impl Future<Output = Result<Vec<u64>, Self::Error>> + Send;
+    fn list_repositories(
+        &self,
+        installation_id: u64,
+    ) -> impl Future<Output = Result<Vec<Repository>, Self::Error>> + Send;
 }
 
 pub trait SourceControlInstallation {
@@ -27,6 +37,22 @@ pub trait SourceControlInstallation {
         path: &str,
         r#ref: &str,
     ) -> impl Future<Output = Result<Folder, Self::Error>> + Send;
+    fn default_branch(&self) -> impl Future<Output = Result<String, Self::Error>> + Send;
+    fn resolve_branch(
+        &self,
+        branch: &str,
+    ) -> impl Future<Output = Result<String, Self::Error>> + Send;
+    /// Resolves a branch, tag or commit sha to the sha of the commit it points to
+    fn resolve_ref(&self, r#ref: &str) -> impl Future<Output = Result<String, Self::Error>> + Send;
+    /// Lists the paths of all files changed between the two commits, compared from their
+    /// merge base. Renamed files are listed with both their old and new path.
+    ///
+    /// Returns nothing if the changes are too large to be listed completely.
+    fn list_changed_files(
+        &self,
+        base: &str,
+        head: &str,
+    ) -> impl Future<Output = Result<Option<Vec<String>>, Self::Error>> + Send;
     fn print_rate_limit(&self) -> impl Future<Output = Result<(), Self::Error>> + Send;
     fn update_status_check(
         &self,
@@ -37,6 +63,13 @@ pub trait SourceControlInstallation {
     ) -> impl Future<Output = Result<(), Self::Error>> + Send;
 }
 
+#[derive(Clone, Debug)]
+pub struct Repository {
+    pub owner: String,
+    pub name: String,
+    pub default_branch: String,
+}
+
 #[derive(Debug)]
 pub struct Folder {
     pub items: Vec<File>,
@@ -53,13 +86,20 @@ pub enum CheckStatus {
     Running,
     Failed,
     Passed,
+    Skipped,
+    TimedOut,
+    Cancelled,
 }
 
 impl CheckStatus {
     pub fn is_completed(&self) -> bool {
         match &self {
             CheckStatus::Pending | CheckStatus::Running => false,
-            CheckStatus::Failed | CheckStatus::Passed => true,
+            CheckStatus::Failed
+            | CheckStatus::Passed
+            | CheckStatus::Skipped
+            | CheckStatus::TimedOut
+            | CheckStatus::Cancelled => true,
         }
     }
 }